@@ -12,7 +12,6 @@ lib.rs -> DioxusNative -> Window -> Document
 
 mod devtools;
 pub mod renderer;
-mod util;
 
 pub use devtools::*;
 pub use renderer::*;
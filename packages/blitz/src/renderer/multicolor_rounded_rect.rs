@@ -13,7 +13,7 @@ use vello::kurbo::{Arc, BezPath, Ellipse, PathEl, Point, Rect, Shape, Vec2};
 
 /// Resolved positions, thicknesses, and radii using the document scale and layout data
 ///
-/// This should be calculated once and then used to stroke borders, outlines, and frames
+/// This should be calculated once and then used to stroke borders and frames
 ///
 /// It contains all the information needed to draw the frame, border, etc
 ///
@@ -21,8 +21,6 @@ use vello::kurbo::{Arc, BezPath, Ellipse, PathEl, Point, Rect, Shape, Vec2};
 pub struct ElementFrame {
     pub outer_rect: Rect,
     pub inner_rect: Rect,
-    pub outline_width: f64,
-
     pub border_top_width: f64,
     pub border_left_width: f64,
     pub border_right_width: f64,
@@ -42,7 +40,7 @@ pub struct ElementFrame {
 impl ElementFrame {
     #[rustfmt::skip]
     pub fn new(style: &ComputedValues, layout: &Layout, scale: f64) -> Self {
-        let (border, outline) = (style.get_border(), style.get_outline());
+        let border = style.get_border();
 
         // let scale = 1.0;
 
@@ -52,7 +50,6 @@ impl ElementFrame {
         let border_left_width = scale * border.border_left_width.to_f64_px();
         let border_right_width = scale * border.border_right_width.to_f64_px();
         let border_bottom_width = scale * border.border_bottom_width.to_f64_px();
-        let outline_width =  scale * outline.outline_width.to_f64_px();
 
         let width: f64 = layout.size.width.into();
         let height: f64 = layout.size.height.into();
@@ -105,7 +102,6 @@ impl ElementFrame {
         Self {
             inner_rect,
             outer_rect,
-            outline_width,
             border_top_width,
             border_left_width,
             border_right_width,
@@ -168,22 +164,6 @@ impl ElementFrame {
         path
     }
 
-    /// Construct a bezpath drawing the outline
-    pub fn outline(&self) -> BezPath {
-        use Corner::*;
-
-        let mut path = BezPath::new();
-
-        // todo: this has been known to produce quirky outputs with hugely rounded edges
-        self.shape(&mut path, ArcSide::Outline, Direction::Clockwise);
-        path.move_to(self.corner(TopLeft, ArcSide::Outer));
-
-        self.shape(&mut path, ArcSide::Outer, Direction::Anticlockwise);
-        path.move_to(self.corner(TopLeft, ArcSide::Outer));
-
-        path
-    }
-
     /// Construct a bezpath drawing the frame
     pub fn frame(&self) -> BezPath {
         let mut path = BezPath::new();
@@ -215,25 +195,21 @@ impl ElementFrame {
             Corner::TopLeft => match side {
                 ArcSide::Inner => (x0 + self.border_left_width, y0 + self.border_top_width),
                 ArcSide::Outer => (x0, y0),
-                ArcSide::Outline => (x0 - self.outline_width, y0 - self.outline_width),
                 ArcSide::Middle => unimplemented!(),
             },
             Corner::TopRight => match side {
                 ArcSide::Inner => (x1 - self.border_right_width, y0 + self.border_top_width),
                 ArcSide::Outer => (x1, y0),
-                ArcSide::Outline => (x1 + self.outline_width, y0 - self.outline_width),
                 ArcSide::Middle => unimplemented!(),
             },
             Corner::BottomRight => match side {
                 ArcSide::Inner => (x1 - self.border_right_width, y1 - self.border_bottom_width),
                 ArcSide::Outer => (x1, y1),
-                ArcSide::Outline => (x1 + self.outline_width, y1 + self.outline_width),
                 ArcSide::Middle => unimplemented!(),
             },
             Corner::BottomLeft => match side {
                 ArcSide::Inner => (x0 + self.border_left_width, y1 - self.border_bottom_width),
                 ArcSide::Outer => (x0, y1),
-                ArcSide::Outline => (x0 - self.outline_width, y1 + self.outline_width),
                 ArcSide::Middle => unimplemented!(),
             },
         };
@@ -427,7 +403,6 @@ impl ElementFrame {
             border_bottom_left_radius_height,
             border_bottom_right_radius_width,
             border_bottom_right_radius_height,
-            outline_width,
             ..
         } = self;
 
@@ -447,12 +422,6 @@ impl ElementFrame {
 
         let radii = match side {
             Outer => outer,
-            Outline => match corner {
-                TopLeft => (border_top_left_radius_width + outline_width, border_top_left_radius_height + outline_width),
-                TopRight => (border_top_right_radius_width + outline_width, border_top_right_radius_height + outline_width),
-                BottomRight => (border_bottom_right_radius_width + outline_width, border_bottom_right_radius_height + outline_width),
-                BottomLeft => (border_bottom_left_radius_width + outline_width, border_bottom_left_radius_height + outline_width),
-            },
             Middle => todo!("Implement border midline offset"),
             Inner => match corner {
                 TopLeft => (border_top_left_radius_width - border_left_width, border_top_left_radius_height - border_top_width),
@@ -495,7 +464,7 @@ pub enum Edge {
 /// We combine the corners and their distances to have twelve options here.
 ///
 /// ```a
-///    *-----------------------------------------------------------------*  <--- ArcSide::Outline
+///    *-----------------------------------------------------------------*
 ///    |                               Outline                           |
 ///    |    *--------------------------------------------------------*   |  <--- ArcSide::Outer
 ///    |    |                         Border Outer                   |   |
@@ -528,7 +497,6 @@ enum Corner {
 
 #[derive(Debug, Clone, Copy)]
 enum ArcSide {
-    Outline,
     Outer,
     #[allow(unused)]
     Middle,
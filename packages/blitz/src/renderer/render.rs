@@ -1,48 +1,27 @@
+use std::collections::HashMap;
 use std::sync::atomic::{self, AtomicUsize};
 use std::sync::Arc;
 
 use super::multicolor_rounded_rect::{Edge, ElementFrame};
-use crate::{
-    devtools::Devtools,
-    util::{GradientSlice, StyloGradient, ToVelloColor},
+use crate::devtools::Devtools;
+use blitz_dom::display_list::{
+    background_image, resolve_stop_positions, Background, BoxShadow, DisplayItem, GradientStop,
+    Outline,
 };
-use blitz_dom::node::{NodeData, TextBrush, TextInputData, TextNodeData};
-use blitz_dom::{local_name, Document, Node};
+use blitz_dom::node::{TextBrush, TextInputData};
+use blitz_dom::{Document, Node};
 
 use style::{
-    dom::TElement,
-    properties::{
-        generated::longhands::visibility::computed_value::T as StyloVisibility,
-        style_structs::{Font, Outline},
-        ComputedValues,
-    },
-    values::{
-        computed::{
-            Angle, AngleOrPercentage, CSSPixelLength, LengthPercentage, LineDirection, Overflow,
-            Percentage,
-        },
-        generics::{
-            color::Color as StyloColor,
-            image::{
-                EndingShape, GenericGradient, GenericGradientItem, GenericImage, GradientFlags,
-            },
-            position::GenericPosition,
-            NonNegative,
-        },
-        specified::{
-            position::{HorizontalPositionKeyword, VerticalPositionKeyword},
-            BorderStyle, OutlineStyle,
-        },
-    },
-    OwnedSlice,
+    properties::{style_structs::Font, ComputedValues},
+    values::generics::image::GenericImage,
 };
 
 use image::{imageops::FilterType, DynamicImage};
 use parley::layout::PositionedLayoutItem;
 use taffy::prelude::Layout;
 use vello::{
-    kurbo::{Affine, Point, Rect, Shape, Stroke, Vec2},
-    peniko::{self, Brush, Color, Fill, Mix},
+    kurbo::{Affine, Point, Rect, Stroke, Vec2},
+    peniko::{self, BlendMode, Brush, Color, Compose, Fill, Mix},
     Scene,
 };
 use vello_svg::usvg;
@@ -53,6 +32,9 @@ static CLIP_DEPTH: AtomicUsize = AtomicUsize::new(0);
 static CLIP_DEPTH_USED: AtomicUsize = AtomicUsize::new(0);
 static CLIPS_WANTED: AtomicUsize = AtomicUsize::new(0);
 
+/// The clip shape (in device pixels) of the layers which apply opacity, which is large enough not to clip anything
+const LAYER_BOUNDS: Rect = Rect::new(-1.0e6, -1.0e6, 1.0e6, 1.0e6);
+
 /// Draw the current tree to current render surface
 /// Eventually we'll want the surface itself to be passed into the render function, along with things like the viewport
 ///
@@ -94,12 +76,6 @@ pub struct VelloSceneGenerator<'dom> {
 }
 
 impl<'dom> VelloSceneGenerator<'dom> {
-    fn node_position(&self, node: usize, location: Point) -> (Layout, Point) {
-        let layout = self.layout(node);
-        let pos = location + Vec2::new(layout.location.x as f64, layout.location.y as f64);
-        (layout, pos)
-    }
-
     fn layout(&self, child: usize) -> Layout {
         self.dom.as_ref().tree()[child].unrounded_layout
         // self.dom.tree()[child].final_layout
@@ -111,16 +87,9 @@ impl<'dom> VelloSceneGenerator<'dom> {
     /// This assumes styles are resolved and layout is complete.
     /// Make sure you do those before trying to render
     pub fn generate_vello_scene(&self, scene: &mut Scene) {
-        // Simply render the document (the root element (note that this is not the same as the root node)))
         scene.reset();
-        self.render_element(
-            scene,
-            self.dom.as_ref().root_element().id,
-            Point {
-                x: 0.0,
-                y: self.scroll_offset,
-            },
-        );
+        let display_list = self.dom.as_ref().build_display_list();
+        self.paint_display_list(scene, &display_list);
 
         // Render debug overlay
         if self.devtools.highlight_hover {
@@ -248,200 +217,212 @@ impl<'dom> VelloSceneGenerator<'dom> {
         );
     }
 
-    /// Renders a node, but is guaranteed that the node is an element
-    /// This is because the font_size is calculated from layout resolution and all text is rendered directly here, instead
-    /// of a separate text stroking phase.
-    ///
-    /// In Blitz, text styling gets its attributes from its container element/resolved styles
-    /// In other libraries, text gets its attributes from a `text` element - this is not how HTML works.
-    ///
-    /// Approaching rendering this way guarantees we have all the styles we need when rendering text with not having
-    /// to traverse back to the parent for its styles, or needing to pass down styles
-    fn render_element(&self, scene: &mut Scene, node_id: usize, location: Point) {
-        // Need to do research on how we can cache most of the bezpaths - there's gonna be a lot of encoding between frames.
-        // Might be able to cache resources deeper in vello.
-        //
-        // Implemented (completely):
-        //  - nothing is completely done:
-        //  - vello is limiting all the styles we can implement (performantly)
-        //  - servo is missing a number of features (like space-evenly justify)
-        //
-        // Implemented (partially):
-        //  - background, border, font, margin, outline, padding,
-        //
-        // Not Implemented:
-        //  - list, position, table, text, ui,
-        //  - custom_properties, writing_mode, rules, visited_style, flags,  box_, column, counters, effects,
-        //  - inherited_box, inherited_table, inherited_text, inherited_ui,
-        let element = &self.dom.as_ref().tree()[node_id];
-
-        // Early return if the element is hidden
-        if matches!(element.style.display, taffy::prelude::Display::None) {
-            return;
-        }
-
-        // Only draw elements with a style
-        if element.primary_styles().is_none() {
-            return;
-        }
-
-        // Hide elements with "hidden" attribute
-        if let Some("true" | "") = element.attr(local_name!("hidden")) {
-            return;
-        }
-
-        // Hide inputs with type=hidden
-        // Implemented here rather than using the style engine for performance reasons
-        if element.local_name() == "input" && element.attr(local_name!("type")) == Some("hidden") {
-            return;
-        }
+    /// The transform from CSS pixels relative to the top-left corner of the document to device pixels
+    fn document_transform(&self) -> Affine {
+        Affine::scale(self.scale) * Affine::translate((0.0, self.scroll_offset))
+    }
 
-        // Hide elements with a visibility style other than visible
-        if element
-            .primary_styles()
-            .unwrap()
-            .get_inherited_box()
-            .visibility
-            != StyloVisibility::Visible
-        {
-            return;
-        }
+    /// Paint a display list (see [`Document::build_display_list`])
+    ///
+    /// Each layer is painted into a scene of its own, which is appended to its parent's scene (with the layer's
+    /// transform and opacity applied) once the layer is complete.
+    fn paint_display_list(&self, scene: &mut Scene, items: &[DisplayItem]) {
+        let document_transform = self.document_transform();
+
+        // The layers which are being painted (innermost last), with their transforms (in device pixels) and opacities
+        let mut layers: Vec<(Scene, Affine, f32)> = Vec::new();
+        // Whether each of the clips which are being applied was pushed (clips beyond `CLIP_LIMIT` are skipped)
+        let mut clips: Vec<bool> = Vec::new();
+        // The drawing context of each element, which is created by the element's first item
+        let mut element_cxs: HashMap<usize, ElementCx> = HashMap::new();
+
+        for item in items {
+            match *item {
+                DisplayItem::PushLayer {
+                    transform, opacity, ..
+                } => {
+                    let transform = document_transform * transform * document_transform.inverse();
+                    layers.push((Scene::new(), transform, opacity));
+                    continue;
+                }
+                DisplayItem::PopLayer => {
+                    let (layer, transform, opacity) =
+                        layers.pop().expect("Unbalanced display list layers");
+                    let parent = layers
+                        .last_mut()
+                        .map_or(&mut *scene, |(parent, _, _)| parent);
+                    if opacity < 1.0 {
+                        parent.push_layer(Mix::Normal, opacity, Affine::IDENTITY, &LAYER_BOUNDS);
+                        parent.append(&layer, Some(transform));
+                        parent.pop_layer();
+                    } else {
+                        parent.append(&layer, Some(transform));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
 
-        // We can't fully support opacity yet, but we can hide elements with opacity 0
-        if element.primary_styles().unwrap().get_effects().opacity == 0.0 {
-            return;
+            let scene = layers.last_mut().map_or(&mut *scene, |(layer, _, _)| layer);
+            self.paint_display_item(scene, item, &mut element_cxs, &mut clips);
         }
+    }
 
-        // TODO: account for overflow_x vs overflow_y
-        let styles = &element.primary_styles().unwrap();
-        let overflow = styles.get_box().overflow_x;
-        let should_clip = !matches!(overflow, Overflow::Visible);
-        let clips_available = CLIPS_USED.load(atomic::Ordering::SeqCst) <= CLIP_LIMIT;
+    /// Paint a display item other than a layer
+    ///
+    /// In Blitz, text gets its styles from its container element (its inline root) rather than from a `text` element,
+    /// so all of the text of an inline root (including that of its inline descendants) is drawn by its `Text` item.
+    fn paint_display_item<'w>(
+        &'w self,
+        scene: &mut Scene,
+        item: &DisplayItem,
+        element_cxs: &mut HashMap<usize, ElementCx<'w>>,
+        clips: &mut Vec<bool>,
+    ) {
+        match item {
+            DisplayItem::PushLayer { .. } | DisplayItem::PopLayer => {
+                unreachable!("Layers are painted by paint_display_list")
+            }
+            DisplayItem::PushClip { rect, .. } => {
+                CLIPS_WANTED.fetch_add(1, atomic::Ordering::SeqCst);
+                let clips_available = CLIPS_USED.load(atomic::Ordering::SeqCst) <= CLIP_LIMIT;
+                if clips_available {
+                    scene.push_layer(Mix::Clip, 1.0, self.document_transform(), rect);
+                    CLIPS_USED.fetch_add(1, atomic::Ordering::SeqCst);
+                    let depth = CLIP_DEPTH.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+                    CLIP_DEPTH_USED.fetch_max(depth, atomic::Ordering::SeqCst);
+                }
+                clips.push(clips_available);
+            }
+            DisplayItem::PopClip => {
+                if clips.pop() == Some(true) {
+                    scene.pop_layer();
+                    CLIP_DEPTH.fetch_sub(1, atomic::Ordering::SeqCst);
+                }
+            }
+            DisplayItem::BoxShadow {
+                node_id,
+                rect,
+                shadow,
+            } => {
+                let cx = self.cached_element_cx(element_cxs, *node_id, *rect);
+                if shadow.inset {
+                    cx.draw_inset_box_shadow(scene, shadow);
+                } else {
+                    cx.draw_outset_box_shadow(scene, shadow);
+                }
+            }
+            DisplayItem::Rect {
+                node_id,
+                rect,
+                color,
+            } => {
+                let cx = self.cached_element_cx(element_cxs, *node_id, *rect);
+                cx.fill_frame(scene, *color);
+            }
+            DisplayItem::BackgroundImage {
+                node_id,
+                rect,
+                index,
+            } => {
+                let cx = self.cached_element_cx(element_cxs, *node_id, *rect);
+                cx.draw_background_image(scene, *index);
+            }
+            DisplayItem::Border {
+                node_id,
+                rect,
+                colors,
+                ..
+            } => {
+                let cx = self.cached_element_cx(element_cxs, *node_id, *rect);
+                cx.stroke_border(scene, colors);
+            }
+            DisplayItem::Element { node_id, rect } => {
+                let cx = self.cached_element_cx(element_cxs, *node_id, *rect);
+                cx.stroke_devtools(scene);
+            }
+            DisplayItem::Replaced { node_id, .. } => {
+                if let Some(cx) = element_cxs.get(node_id) {
+                    cx.draw_image(scene);
+                    cx.draw_svg(scene);
+                }
+            }
+            DisplayItem::Text { node_id, rect } => {
+                let Some(cx) = element_cxs.get(node_id) else {
+                    return;
+                };
+                let pos = rect.origin() + Vec2::new(0.0, self.scroll_offset);
 
-        // Apply padding/border offset to inline root
-        let (_layout, pos) = self.node_position(node_id, location);
-        let taffy::Layout {
-            size,
-            border,
-            padding,
-            ..
-        } = element.final_layout;
-        let scaled_pb = (padding + border).map(f64::from);
-        let pos = vello::kurbo::Point {
-            x: pos.x + scaled_pb.left,
-            y: pos.y + scaled_pb.top,
-        };
-        let size = vello::kurbo::Size {
-            width: (size.width as f64 - scaled_pb.left - scaled_pb.right) * self.scale,
-            height: (size.height as f64 - scaled_pb.top - scaled_pb.bottom) * self.scale,
-        };
-        let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
-        let origin = vello::kurbo::Point { x: 0.0, y: 0.0 };
-        let clip = Rect::from_origin_size(origin, size);
+                if let Some(input_data) = cx.text_input {
+                    let text_layout = input_data.editor.layout();
 
-        // Optimise zero-area (/very small area) clips by not rendering at all
-        if should_clip && clip.area() < 0.01 {
-            return;
-        }
+                    // Clip overflowing text to the content box and shift it by the input's scroll offset
+                    scene.push_layer(Mix::Clip, 1.0, self.document_transform(), rect);
+                    let scroll_offset = input_data.scroll_offset as f64 / self.scale;
+                    let text_pos = Point {
+                        x: pos.x - scroll_offset,
+                        y: pos.y,
+                    };
 
-        if should_clip {
-            CLIPS_WANTED.fetch_add(1, atomic::Ordering::SeqCst);
-        }
-        if should_clip && clips_available {
-            scene.push_layer(Mix::Clip, 1.0, transform, &clip);
-            CLIPS_USED.fetch_add(1, atomic::Ordering::SeqCst);
-            let depth = CLIP_DEPTH.fetch_add(1, atomic::Ordering::SeqCst) + 1;
-            CLIP_DEPTH_USED.fetch_max(depth, atomic::Ordering::SeqCst);
-        }
+                    // Render text
+                    cx.stroke_text(scene, text_layout, text_pos);
 
-        let cx = self.element_cx(element, location);
-        cx.stroke_effects(scene);
-        cx.stroke_outline(scene);
-        cx.stroke_frame(scene);
-        cx.stroke_border(scene);
-        cx.stroke_devtools(scene);
-        cx.draw_image(scene);
-        cx.draw_svg(scene);
-
-        // Render the text in text inputs
-        if let Some(input_data) = cx.text_input {
-            let text_layout = input_data.editor.layout();
-
-            // Render text
-            cx.stroke_text(scene, text_layout, pos);
-
-            // Render caret
-            let cursor_line = input_data.editor.get_cursor_line();
-            let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
-            if let Some(line) = cursor_line {
-                scene.stroke(
-                    &Stroke::new(2.),
-                    transform,
-                    &Brush::Solid(Color::BLACK),
-                    None,
-                    &line,
-                );
-            }
-        } else if element.is_inline_root {
-            let text_layout = &element
-                .raw_dom_data
-                .downcast_element()
-                .unwrap()
-                .inline_layout_data()
-                .unwrap_or_else(|| {
-                    panic!("Tried to render node marked as inline root that does not have an inline layout: {:?}", element);
-                });
-
-            // Render text
-            cx.stroke_text(scene, &text_layout.layout, pos);
-
-            // Render inline boxes
-            for line in text_layout.layout.lines() {
-                for item in line.items() {
-                    if let PositionedLayoutItem::InlineBox(ibox) = item {
-                        self.render_node(scene, ibox.id as usize, pos);
+                    // Render caret
+                    let cursor_line = input_data.editor.get_cursor_line();
+                    let transform =
+                        Affine::translate((text_pos.x * self.scale, text_pos.y * self.scale));
+                    if let Some(line) = cursor_line {
+                        scene.stroke(
+                            &Stroke::new(2.),
+                            transform,
+                            &Brush::Solid(Color::BLACK),
+                            None,
+                            &line,
+                        );
                     }
+                    scene.pop_layer();
+                } else {
+                    let text_layout = cx
+                        .element
+                        .raw_dom_data
+                        .downcast_element()
+                        .and_then(|element| element.inline_layout_data())
+                        .unwrap_or_else(|| {
+                            panic!("Tried to render node marked as inline root that does not have an inline layout: {:?}", cx.element);
+                        });
+                    cx.stroke_text(scene, &text_layout.layout, pos);
                 }
             }
-        } else {
-            for child_id in cx
-                .element
-                .layout_children
-                .borrow()
-                .as_ref()
-                .unwrap()
-                .iter()
-                .copied()
-            {
-                self.render_node(scene, child_id, cx.pos);
+            DisplayItem::Outline {
+                node_id,
+                rect,
+                outline,
+            } => {
+                let cx = self.cached_element_cx(element_cxs, *node_id, *rect);
+                cx.stroke_outline(scene, outline);
             }
-        }
-
-        if should_clip {
-            scene.pop_layer();
-            CLIP_DEPTH.fetch_sub(1, atomic::Ordering::SeqCst);
+            // Generated content is drawn as part of its inline root's text
+            DisplayItem::GeneratedText { .. } => {}
         }
     }
 
-    fn render_node(&self, scene: &mut Scene, node_id: usize, location: Point) {
-        let node = &self.dom.as_ref().tree()[node_id];
-
-        match &node.raw_dom_data {
-            NodeData::Element(_) | NodeData::AnonymousBlock(_) => {
-                self.render_element(scene, node_id, location)
-            }
-            NodeData::Text(TextNodeData { .. }) => {
-                // Text nodes should never be rendered directly
-                // (they should always be rendered as part of an inline layout)
-                // unreachable!()
-            }
-            NodeData::Document => {}
-            // NodeData::Doctype => {}
-            NodeData::Comment => {} // NodeData::ProcessingInstruction { .. } => {}
-        }
+    /// The drawing context of the element whose border box (in CSS pixels relative to the top-left corner of the
+    /// document) is `border_box`, which is created the first time it is needed
+    fn cached_element_cx<'w, 'c>(
+        &'w self,
+        element_cxs: &'c mut HashMap<usize, ElementCx<'w>>,
+        node_id: usize,
+        border_box: Rect,
+    ) -> &'c ElementCx<'w> {
+        element_cxs.entry(node_id).or_insert_with(|| {
+            let pos = border_box.origin() + Vec2::new(0.0, self.scroll_offset);
+            self.element_cx(&self.dom.tree()[node_id], pos)
+        })
     }
 
-    fn element_cx<'w>(&'w self, element: &'w Node, location: Point) -> ElementCx {
+    /// The drawing context of an element whose border box's top-left corner is at `pos` (in CSS pixels, including
+    /// the document's scroll offset)
+    fn element_cx<'w>(&'w self, element: &'w Node, pos: Point) -> ElementCx {
         let style = element
             .stylo_element_data
             .borrow()
@@ -451,7 +432,7 @@ impl<'dom> VelloSceneGenerator<'dom> {
                 ComputedValues::initial_values_with_font_override(Font::initial_values()).to_arc(),
             );
 
-        let (layout, pos) = self.node_position(element.id, location);
+        let layout = self.layout(element.id);
         let scale = self.scale;
 
         // the bezpaths for every element are (potentially) cached (not yet, tbd)
@@ -674,18 +655,23 @@ impl ElementCx<'_> {
         // }
     }
 
-    fn stroke_frame(&self, scene: &mut Scene) {
+    /// Draw the element's `index`th background image (the first listed image is the top layer)
+    fn draw_background_image(&self, scene: &mut Scene, index: usize) {
         use GenericImage::*;
 
-        // Draw background color (if any)
-        self.draw_solid_frame(scene);
-
-        for segment in &self.style.get_background().background_image.0 {
+        if let Some(segment) = self.style.get_background().background_image.0.get(index) {
             match segment {
                 None => {
                     // Do nothing
                 }
-                Gradient(gradient) => self.draw_gradient_frame(scene, gradient),
+                Gradient(_) => {
+                    // Radial and conic gradients aren't drawn yet (see `background_image`)
+                    if let Some(Background::LinearGradient { angle, stops }) =
+                        background_image(self.element, index)
+                    {
+                        self.draw_linear_gradient(scene, angle, &stops);
+                    }
+                }
                 Url(_) => {
                     //
                     // todo!("Implement background drawing for Image::Url")
@@ -715,150 +701,39 @@ impl ElementCx<'_> {
         }
     }
 
-    fn draw_gradient_frame(&self, scene: &mut Scene, gradient: &StyloGradient) {
-        match gradient {
-            // https://developer.mozilla.org/en-US/docs/Web/CSS/gradient/linear-gradient
-            GenericGradient::Linear {
-                direction,
-                items,
-                // repeating,
-                // compat_mode,
-                ..
-            } => self.draw_linear_gradient(scene, direction, items),
-            GenericGradient::Radial {
-                shape,
-                position,
-                items,
-                flags,
-                // compat_mode,
-                ..
-            } => self.draw_radial_gradient(scene, shape, position, items, *flags),
-            GenericGradient::Conic {
-                angle,
-                position,
-                items,
-                flags,
-                ..
-            } => self.draw_conic_gradient(scene, angle, position, items, *flags),
-        };
-    }
-
-    fn draw_linear_gradient(
-        &self,
-        scene: &mut Scene,
-        direction: &LineDirection,
-        items: &GradientSlice,
-    ) {
-        let bb = self.frame.outer_rect.bounding_box();
-
+    /// Draw a linear gradient (resolved by [`background_image`]) across the element's padding box
+    ///
+    /// https://developer.mozilla.org/en-US/docs/Web/CSS/gradient/linear-gradient
+    fn draw_linear_gradient(&self, scene: &mut Scene, angle: f32, stops: &[GradientStop]) {
         let shape = self.frame.frame();
-        let center = bb.center();
         let rect = self.frame.inner_rect;
-        let (start, end) = match direction {
-            LineDirection::Angle(angle) => {
-                let start = Point::new(
-                    self.frame.inner_rect.x0 + rect.width() / 2.0,
-                    self.frame.inner_rect.y0,
-                );
-                let end = Point::new(
-                    self.frame.inner_rect.x0 + rect.width() / 2.0,
-                    self.frame.inner_rect.y1,
-                );
+        if rect.width() <= 0.0 || rect.height() <= 0.0 || stops.is_empty() {
+            return;
+        }
 
-                // rotate the lind around the center
-                let line = Affine::rotate_about(-angle.radians64(), center)
-                    * vello::kurbo::Line::new(start, end);
+        // The direction of the gradient line as a unit vector. 0deg points upwards, with angles increasing clockwise.
+        let angle = (angle as f64).to_radians();
+        let direction = Vec2::new(angle.sin(), -angle.cos());
+
+        // The gradient line passes through the center of the box, and is long enough that the
+        // lines perpendicular to it at the start and end points pass through the box's corners
+        let half_length =
+            (rect.width() * direction.x.abs() + rect.height() * direction.y.abs()) / 2.0;
+        let start = rect.center() - direction * half_length;
+        let end = rect.center() + direction * half_length;
 
-                (line.p0, line.p1)
-            }
-            LineDirection::Horizontal(horizontal) => {
-                let start = Point::new(
-                    self.frame.inner_rect.x0,
-                    self.frame.inner_rect.y0 + rect.height() / 2.0,
-                );
-                let end = Point::new(
-                    self.frame.inner_rect.x1,
-                    self.frame.inner_rect.y0 + rect.height() / 2.0,
-                );
-                match horizontal {
-                    HorizontalPositionKeyword::Right => (start, end),
-                    HorizontalPositionKeyword::Left => (end, start),
-                }
-            }
-            LineDirection::Vertical(vertical) => {
-                let start = Point::new(
-                    self.frame.inner_rect.x0 + rect.width() / 2.0,
-                    self.frame.inner_rect.y0,
-                );
-                let end = Point::new(
-                    self.frame.inner_rect.x0 + rect.width() / 2.0,
-                    self.frame.inner_rect.y1,
-                );
-                match vertical {
-                    VerticalPositionKeyword::Top => (end, start),
-                    VerticalPositionKeyword::Bottom => (start, end),
-                }
-            }
-            LineDirection::Corner(horizontal, vertical) => {
-                let (start_x, end_x) = match horizontal {
-                    HorizontalPositionKeyword::Right => {
-                        (self.frame.inner_rect.x0, self.frame.inner_rect.x1)
-                    }
-                    HorizontalPositionKeyword::Left => {
-                        (self.frame.inner_rect.x1, self.frame.inner_rect.x0)
-                    }
-                };
-                let (start_y, end_y) = match vertical {
-                    VerticalPositionKeyword::Top => {
-                        (self.frame.inner_rect.y1, self.frame.inner_rect.y0)
-                    }
-                    VerticalPositionKeyword::Bottom => {
-                        (self.frame.inner_rect.y0, self.frame.inner_rect.y1)
-                    }
-                };
-                (Point::new(start_x, start_y), Point::new(end_x, end_y))
-            }
-        };
         let mut gradient = peniko::Gradient {
             kind: peniko::GradientKind::Linear { start, end },
             extend: Default::default(),
             stops: Default::default(),
         };
 
-        let mut hint: Option<f32> = None;
-
-        for (idx, item) in items.iter().enumerate() {
-            let (color, offset) = match item {
-                GenericGradientItem::SimpleColorStop(color) => {
-                    let step = 1.0 / (items.len() as f32 - 1.0);
-                    let offset = step * idx as f32;
-                    let color = color.as_vello();
-                    (color, offset)
-                }
-                GenericGradientItem::ComplexColorStop { color, position } => {
-                    match position.to_percentage().map(|pos| pos.0) {
-                        Some(offset) => {
-                            let color = color.as_vello();
-                            (color, offset)
-                        }
-                        // TODO: implement absolute and calc stops
-                        None => continue,
-                    }
-                }
-                GenericGradientItem::InterpolationHint(position) => {
-                    hint = match position.to_percentage() {
-                        Some(Percentage(percentage)) => Some(percentage),
-                        _ => None,
-                    };
-                    continue;
-                }
-            };
-
-            match hint {
-                None => gradient.stops.push(peniko::ColorStop { color, offset }),
-                Some(hint) => {
-                    let &last_stop = gradient.stops.last().unwrap();
+        for (stop, offset) in stops.iter().zip(resolve_stop_positions(stops)) {
+            let [r, g, b, a] = stop.color.map(f64::from);
+            let color = Color::rgba(r, g, b, a);
 
+            match (stop.hint, gradient.stops.last().copied()) {
+                (Some(hint), Some(last_stop)) => {
                     if hint <= last_stop.offset {
                         // Upstream code has a bug here, so we're going to do something different
                         match gradient.stops.len() {
@@ -913,6 +788,7 @@ impl ElementCx<'_> {
                         gradient.stops.push(peniko::ColorStop { color, offset });
                     }
                 }
+                _ => gradient.stops.push(peniko::ColorStop { color, offset }),
             }
         }
         let brush = peniko::BrushRef::Gradient(&gradient);
@@ -921,16 +797,10 @@ impl ElementCx<'_> {
 
     // fn draw_image_frame(&self, scene: &mut Scene) {}
 
-    fn draw_solid_frame(&self, scene: &mut Scene) {
-        let background_color = &self.style.get_background().background_color;
-        let bg_color = background_color.as_vello();
-
-        if bg_color != Color::TRANSPARENT {
-            let shape = self.frame.frame();
-
-            // Fill the color
-            scene.fill(Fill::NonZero, self.transform, bg_color, None, &shape);
-        }
+    /// Fill the element's frame with a background colour
+    fn fill_frame(&self, scene: &mut Scene, color: Color) {
+        let shape = self.frame.frame();
+        scene.fill(Fill::NonZero, self.transform, color, None, &shape);
     }
 
     /// Stroke a border
@@ -950,9 +820,10 @@ impl ElementCx<'_> {
     /// ✅ hidden - Defines a hidden border
     ///
     /// The border-style property can have from one to four values (for the top border, right border, bottom border, and the left border).
-    fn stroke_border(&self, sb: &mut Scene) {
-        for edge in [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left] {
-            self.stroke_border_edge(sb, edge);
+    fn stroke_border(&self, sb: &mut Scene, colors: &[Color; 4]) {
+        let edges = [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left];
+        for (edge, color) in edges.into_iter().zip(colors) {
+            self.stroke_border_edge(sb, edge, *color);
         }
     }
 
@@ -973,112 +844,107 @@ impl ElementCx<'_> {
     /// - ✅ hidden: Defines a hidden border
     ///
     /// [*] The effect depends on the border-color value
-    fn stroke_border_edge(&self, sb: &mut Scene, edge: Edge) {
-        let border = self.style.get_border();
+    fn stroke_border_edge(&self, sb: &mut Scene, edge: Edge, color: Color) {
         let path = self.frame.border(edge);
-
-        let color = match edge {
-            Edge::Top => border.border_top_color.as_vello(),
-            Edge::Right => border.border_right_color.as_vello(),
-            Edge::Bottom => border.border_bottom_color.as_vello(),
-            Edge::Left => border.border_left_color.as_vello(),
-        };
-
         sb.fill(Fill::NonZero, self.transform, color, None, &path);
     }
 
-    /// ❌ dotted - Defines a dotted border
-    /// ❌ dashed - Defines a dashed border
-    /// ✅ solid - Defines a solid border
-    /// ❌ double - Defines a double border
-    /// ❌ groove - Defines a 3D grooved border. The effect depends on the border-color value
-    /// ❌ ridge - Defines a 3D ridged border. The effect depends on the border-color value
-    /// ❌ inset - Defines a 3D inset border. The effect depends on the border-color value
-    /// ❌ outset - Defines a 3D outset border. The effect depends on the border-color value
-    /// ✅ none - Defines no border
-    /// ✅ hidden - Defines a hidden border
-    fn stroke_outline(&self, scene: &mut Scene) {
-        let Outline {
-            outline_color,
-            outline_style,
-            ..
-        } = self.style.get_outline();
-
-        let color = outline_color
-            .as_absolute()
-            .map(ToVelloColor::as_vello)
-            .unwrap_or_default();
-
-        let style = match outline_style {
-            OutlineStyle::Auto => return,
-            OutlineStyle::BorderStyle(BorderStyle::Hidden) => return,
-            OutlineStyle::BorderStyle(BorderStyle::None) => return,
-            OutlineStyle::BorderStyle(style) => style,
-        };
-
-        let path = match style {
-            BorderStyle::None | BorderStyle::Hidden => return,
-            BorderStyle::Solid => self.frame.outline(),
-
-            // TODO: Implement other border styles
-            BorderStyle::Inset => self.frame.outline(),
-            BorderStyle::Groove => self.frame.outline(),
-            BorderStyle::Outset => self.frame.outline(),
-            BorderStyle::Ridge => self.frame.outline(),
-            BorderStyle::Dotted => self.frame.outline(),
-            BorderStyle::Dashed => self.frame.outline(),
-            BorderStyle::Double => self.frame.outline(),
-        };
-
-        scene.fill(Fill::NonZero, self.transform, color, None, &path);
+    /// Draws the element's outline outside its border box (see [`blitz_dom::display_list::outline`]), including
+    /// the default focus ring drawn around elements focussed with the keyboard
+    ///
+    /// ❌ dotted, dashed, double, groove, ridge, inset and outset outlines are drawn as solid
+    /// TODO: per-corner radii (we currently use the top-left radius for all corners)
+    fn stroke_outline(&self, scene: &mut Scene, outline: &Outline) {
+        let width = outline.width * self.scale;
+        let distance = outline.offset * self.scale + width / 2.0;
+        let radius = (self.frame.border_top_left_radius_width + distance).max(0.0);
+        let ring = self
+            .frame
+            .outer_rect
+            .inflate(distance, distance)
+            .to_rounded_rect(radius);
+        scene.stroke(
+            &Stroke::new(width),
+            self.transform,
+            outline.color,
+            None,
+            &ring,
+        );
     }
 
-    /// Applies filters to a final frame
-    ///
-    /// Notably, I don't think we can do this here since vello needs to run this as a pass (shadows need to apply everywhere)
+    /// Draw an outset box shadow beneath the element's frame
     ///
-    /// ❌ opacity: The opacity computed value.
-    /// ❌ box_shadow: The box-shadow computed value.
-    /// ❌ clip: The clip computed value.
-    /// ❌ filter: The filter computed value.
-    /// ❌ mix_blend_mode: The mix-blend-mode computed value.
-    fn stroke_effects(&self, _scene: &mut Scene) {
-        // also: if focused, draw a focus ring
-        //
-        //             let stroke_color = Color::rgb(1.0, 1.0, 1.0);
-        //             let stroke = Stroke::new(FOCUS_BORDER_WIDTH as f32 / 2.0);
-        //             scene_builder.stroke(&stroke, Affine::IDENTITY, stroke_color, None, &shape);
-        //             let smaller_rect = shape.rect().inset(-FOCUS_BORDER_WIDTH / 2.0);
-        //             let smaller_shape = RoundedRect::from_rect(smaller_rect, shape.radii());
-        //             let stroke_color = Color::rgb(0.0, 0.0, 0.0);
-        //             scene_builder.stroke(&stroke, Affine::IDENTITY, stroke_color, None, &shape);
-        //             background.draw_shape(scene_builder, &smaller_shape, layout, viewport_size);
-        // let effects = self.style.get_effects();
+    /// TODO: per-corner radii (we currently use the top-left radius for all corners)
+    fn draw_outset_box_shadow(&self, scene: &mut Scene, shadow: &BoxShadow) {
+        let offset = shadow.offset * self.scale;
+        let spread = shadow.spread * self.scale;
+        let rect = self.frame.outer_rect.inflate(spread, spread) + offset;
+        let radius = (self.frame.border_top_left_radius_width + spread).max(0.0);
+
+        scene.draw_blurred_rounded_rect(
+            self.transform,
+            rect,
+            shadow.color,
+            radius,
+            shadow.blur * self.scale / 2.0,
+        );
     }
 
-    // fn stroke_box_shadow(&self, scene: &mut Scene) {
-    //     let effects = self.style.get_effects();
-    // }
-
-    fn draw_radial_gradient(
-        &self,
-        _scene: &mut Scene,
-        _shape: &EndingShape<NonNegative<CSSPixelLength>, NonNegative<LengthPercentage>>,
-        _position: &GenericPosition<LengthPercentage, LengthPercentage>,
-        _items: &OwnedSlice<GenericGradientItem<StyloColor<Percentage>, LengthPercentage>>,
-        _flags: GradientFlags,
-    ) {
-        unimplemented!()
+    /// Draw an inset box shadow inside the element's padding box, above its background
+    ///
+    /// The padding box is filled with the shadow colour, and the shadow's blurred "hole" (the padding box, shrunk by
+    /// the spread and moved by the offset) is then knocked out of it.
+    /// TODO: per-corner radii (we currently use the top-left radius for all corners)
+    fn draw_inset_box_shadow(&self, scene: &mut Scene, shadow: &BoxShadow) {
+        let padding_box = self.frame.inner_rect;
+        let inner_radius =
+            (self.frame.border_top_left_radius_width - self.frame.border_left_width).max(0.0);
+        let clip = padding_box.to_rounded_rect(inner_radius);
+
+        let offset = shadow.offset * self.scale;
+        let spread = shadow.spread * self.scale;
+        let hole = padding_box.inflate(-spread, -spread) + offset;
+        let hole_radius = (inner_radius - spread).max(0.0);
+
+        scene.push_layer(Mix::Normal, 1.0, self.transform, &clip);
+        scene.fill(Fill::NonZero, self.transform, shadow.color, None, &clip);
+        scene.push_layer(
+            BlendMode::new(Mix::Normal, Compose::DestOut),
+            1.0,
+            self.transform,
+            &clip,
+        );
+        scene.draw_blurred_rounded_rect(
+            self.transform,
+            hole,
+            Color::BLACK,
+            hole_radius,
+            shadow.blur * self.scale / 2.0,
+        );
+        scene.pop_layer();
+        scene.pop_layer();
     }
+}
 
-    fn draw_conic_gradient(
-        &self,
-        _scene: &mut Scene,
-        _angle: &Angle,
-        _position: &GenericPosition<LengthPercentage, LengthPercentage>,
-        _items: &OwnedSlice<GenericGradientItem<StyloColor<Percentage>, AngleOrPercentage>>,
-        _flags: GradientFlags,
-    ) {
-        unimplemented!()
-    }
+#[test]
+fn current_color_borders_resolve_to_the_inherited_text_color() {
+    use blitz_dom::testing::{by_id, parse};
+    use blitz_dom::util::{resolve_color, ToPenikoColor};
+
+    let html = r#"<!DOCTYPE html><html><body style="color: red">
+        <div id="box" style="border: 2px solid currentColor"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let generator = VelloSceneGenerator {
+        dom: &doc,
+        scale: 1.0,
+        devtools: Devtools::default(),
+        scroll_offset: 0.0,
+    };
+    let cx = generator.element_cx(doc.get_node(by_id(&doc, "box")).unwrap(), Point::ZERO);
+    let border_color =
+        resolve_color(&cx.style, &cx.style.get_border().border_top_color).as_peniko();
+    assert_eq!(border_color, Color::rgb8(255, 0, 0));
 }
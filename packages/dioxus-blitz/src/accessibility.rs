@@ -1,6 +1,6 @@
 use crate::waker::BlitzEvent;
-use accesskit::{NodeBuilder, NodeId, Role, Tree, TreeUpdate};
-use blitz_dom::{local_name, Document, Node};
+use blitz_dom::accessibility_tree::AccessibilityExporter;
+use blitz_dom::Document;
 use winit::{event_loop::EventLoopProxy, window::Window};
 
 /// State of the accessibility node tree and platform adapter.
@@ -8,85 +8,32 @@ pub struct AccessibilityState {
     /// Adapter to connect to the [`EventLoop`](`winit::event_loop::EventLoop`).
     adapter: accesskit_winit::Adapter,
 
-    /// Next ID to assign an an [`accesskit::Node`].
-    next_id: u64,
+    /// Converts the document into tree updates, remembering what has already been sent to the adapter
+    exporter: AccessibilityExporter,
 }
 
 impl AccessibilityState {
     pub fn new(window: &Window, proxy: EventLoopProxy<BlitzEvent>) -> Self {
         Self {
             adapter: accesskit_winit::Adapter::with_event_loop_proxy(window, proxy.clone()),
-            next_id: 1,
+            exporter: AccessibilityExporter::new(),
         }
     }
-    pub fn build_tree(&mut self, doc: &Document) {
-        let mut nodes = std::collections::HashMap::new();
-        let mut window = NodeBuilder::new(Role::Window);
-
-        doc.visit(|node_id, node| {
-            let parent = node
-                .parent
-                .and_then(|parent_id| nodes.get_mut(&parent_id))
-                .map(|(_, parent)| parent)
-                .unwrap_or(&mut window);
-            let (id, node_builder) = self.build_node(node, parent);
-
-            nodes.insert(node_id, (id, node_builder));
-        });
-
-        let mut nodes: Vec<_> = nodes
-            .into_iter()
-            .map(|(_, (id, node))| (id, node.build()))
-            .collect();
-        nodes.push((NodeId(0), window.build()));
 
-        let tree = Tree::new(NodeId(0));
-        let tree_update = TreeUpdate {
-            nodes,
-            tree: Some(tree),
-            focus: NodeId(0),
-        };
-
-        self.adapter.update_if_active(|| tree_update)
+    /// Send the nodes which have changed since the last update to the adapter (if it is active)
+    pub fn build_tree(&mut self, doc: &Document) {
+        let exporter = &mut self.exporter;
+        self.adapter.update_if_active(|| exporter.update(doc));
     }
 
-    fn build_node(&mut self, node: &Node, parent: &mut NodeBuilder) -> (NodeId, NodeBuilder) {
-        let mut node_builder = NodeBuilder::default();
-
-        let id = NodeId(self.next_id);
-        self.next_id += 1;
-
-        if let Some(element_data) = node.element_data() {
-            let name = element_data.name.local.to_string();
-
-            // TODO match more roles
-            let role = match &*name {
-                "button" => Role::Button,
-                "div" => Role::GenericContainer,
-                "header" => Role::Header,
-                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Role::Heading,
-                "p" => Role::Paragraph,
-                "section" => Role::Section,
-                "input" => {
-                    let ty = element_data.attr(local_name!("type")).unwrap_or("text");
-                    match ty {
-                        "number" => Role::NumberInput,
-                        _ => Role::TextInput,
-                    }
-                }
-                _ => Role::Unknown,
-            };
-
-            node_builder.set_role(role);
-            node_builder.set_html_tag(name);
-        } else if node.is_text_node() {
-            node_builder.set_role(Role::StaticText);
-            node_builder.set_name(node.text_content());
-            parent.push_labelled_by(id)
-        }
-
-        parent.push_child(id);
+    /// Send the whole tree to the adapter, e.g. because assistive technology has just connected
+    pub fn build_initial_tree(&mut self, doc: &Document) {
+        self.exporter.reset();
+        self.build_tree(doc);
+    }
 
-        (id, node_builder)
+    /// Forget what has been sent, so that the next update describes the whole tree
+    pub fn deactivate(&mut self) {
+        self.exporter.reset();
     }
 }
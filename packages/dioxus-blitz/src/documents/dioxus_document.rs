@@ -3,8 +3,8 @@
 use std::rc::Rc;
 
 use blitz_dom::{
-    events::EventData, namespace_url, node::Attribute, ns, Atom, Document, DocumentLike,
-    ElementNodeData, NodeData, QualName, TextNodeData, Viewport,
+    events::EventData, local_name, namespace_url, node::Attribute, ns, Atom, Document,
+    DocumentLike, ElementNodeData, NodeData, QualName, TextNodeData, Viewport,
 };
 
 use dioxus::{
@@ -68,58 +68,34 @@ impl DocumentLike for DioxusDocument {
             doc: &mut self.inner,
             state: &mut self.vdom_state,
         });
+        #[cfg(debug_assertions)]
+        self.assert_invariants();
+        self.apply_autofocus();
 
         true
     }
 
     fn handle_event(&mut self, event: blitz_dom::events::RendererEvent) -> bool {
-        // Collect the nodes into a chain by traversing upwards
-        // This is important so the "capture" phase can be implemented
-        let mut node_id = event.target;
-        let mut chain = Vec::with_capacity(16);
-        chain.push(node_id);
-
-        // if it's a capturing event, we want to fill in the chain with the parent nodes
-        // until we reach the root - that way we can call the listeners in the correct order
-        // otherwise, we just want to call the listeners on the target
-        //
-        // todo: this is harcoded for "click" events - eventually we actually need to handle proper propagation
-        // if event.name == "click" {
-        while let Some(parent) = self.inner.tree()[node_id].parent {
-            chain.push(parent);
-            node_id = parent;
-        }
-
         set_event_converter(Box::new(NativeConverter {}));
 
         if matches!(event.data, EventData::Click { .. }) {
-            // look for the data-dioxus-id attribute on the element
-            // todo: we might need to walk upwards to find the first element with a data-dioxus-id attribute
-            for node in chain.iter() {
-                let Some(element) = self.inner.tree()[*node].element_data() else {
-                    #[cfg(feature = "tracing")]
-                    tracing::info!(
-                        "No element data found for node {}: {:?}",
-                        node,
-                        self.inner.tree()[*node]
-                    );
-
-                    continue;
+            // Dispatch through the document so that it applies propagation and listener options (such as `once`).
+            // The VirtualDom is told not to bubble the event itself, as each listener is called by the document.
+            let name = event.name();
+            let vdom = &mut self.vdom;
+            let vdom_state = &self.vdom_state;
+            let mut handled = false;
+            self.inner.dispatch_event(event.target, name, |dispatched| {
+                let Some(element_id) = vdom_state.node_to_element_id(dispatched.current_target)
+                else {
+                    return;
                 };
-
-                for attr in element.attrs() {
-                    if attr.name.local.as_ref() == "data-dioxus-id" {
-                        if let Ok(value) = attr.value.parse::<usize>() {
-                            let id = ElementId(value);
-                            // let data = dioxus::html::EventData::Mouse()
-
-                            let data =
-                                Rc::new(PlatformEventData::new(Box::new(NativeClickData {})));
-                            self.vdom.handle_event(event.name(), data, id, true);
-                            return true;
-                        }
-                    }
-                }
+                let data = Rc::new(PlatformEventData::new(Box::new(NativeClickData {})));
+                vdom.handle_event(name, data, element_id, false);
+                handled = true;
+            });
+            if handled {
+                return true;
             }
         }
 
@@ -159,11 +135,90 @@ impl DioxusDocument {
             state: &mut self.vdom_state,
         };
         self.vdom.rebuild(&mut writer);
+        #[cfg(debug_assertions)]
+        self.assert_invariants();
+        self.apply_autofocus();
         // dbg!(self.vdom.rebuild_to_vec());
         // std::process::exit(0);
         // dbg!(writer.state);
     }
 
+    /// Panic if applying mutations left the document's tree or its ElementId mappings inconsistent (see
+    /// [`Document::debug_check_invariants`] and [`DioxusState::debug_check_invariants`])
+    #[cfg(debug_assertions)]
+    fn assert_invariants(&self) {
+        let tree_violations = self.inner.debug_check_invariants();
+        assert!(
+            tree_violations.is_empty(),
+            "Mutations left the document's tree inconsistent: {tree_violations:?}"
+        );
+        let mapping_violations = self.vdom_state.debug_check_invariants(&self.inner);
+        assert!(
+            mapping_violations.is_empty(),
+            "Mutations left the ElementId mappings inconsistent: {mapping_violations:?}"
+        );
+    }
+
+    /// Focus the last element with an `autofocus` attribute inserted by the latest batch of mutations (if any)
+    fn apply_autofocus(&mut self) {
+        if let Some(node_id) = self.inner.take_autofocus_request() {
+            self.inner.set_focus_to(node_id);
+        }
+    }
+
+    /// The name and root index of the template from which a node originated (if any)
+    pub fn template_origin(&self, node_id: NodeId) -> Option<(&str, usize)> {
+        self.vdom_state.template_origin(node_id)
+    }
+
+    /// The ElementId of the element which currently has focus (if any)
+    pub fn focused_element(&self) -> Option<ElementId> {
+        let node_id = self.inner.get_focus_node_id()?;
+        self.vdom_state.node_to_element_id(node_id)
+    }
+
+    /// Reserve capacity for at least `additional` more nodes (and ElementId mappings). Mutations are streamed to
+    /// the document one at a time, so their number can't be known up front, but callers which expect a large
+    /// render can use this to avoid repeatedly growing the document's storage.
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve_nodes(additional);
+        self.vdom_state.node_id_mapping.reserve(additional);
+        self.vdom_state.element_ids.reserve(additional);
+    }
+
+    /// Replace the node `target` with a new instance of root `index` of the registered template `template_name`.
+    /// See [`MutationWriter::replace_subtree_with_template`].
+    pub fn replace_subtree_with_template(
+        &mut self,
+        target: NodeId,
+        template_name: &str,
+        index: usize,
+    ) -> Option<NodeId> {
+        let mut writer = MutationWriter {
+            doc: &mut self.inner,
+            state: &mut self.vdom_state,
+        };
+        writer.replace_subtree_with_template(target, template_name, index)
+    }
+
+    /// Remove nodes which are detached from the document, keeping those which Dioxus may still refer to (see
+    /// [`DioxusState::retained_nodes`]). Returns the number of nodes removed.
+    pub fn collect_garbage(&mut self) -> usize {
+        let retained: Vec<NodeId> = self.vdom_state.retained_nodes().collect();
+        self.inner.collect_garbage_with_roots(retained)
+    }
+
+    /// Register an observer which is notified of each mutation as it is applied to the document
+    pub fn set_mutation_observer(&mut self, observer: Box<dyn MutationObserver>) {
+        self.vdom_state.set_mutation_observer(observer);
+    }
+
+    /// Register a callback which is invoked whenever the attribute `name` is set (or removed) on any node.
+    /// Any number of callbacks can watch the same attribute.
+    pub fn watch_attribute(&mut self, name: String, callback: AttributeCallback) {
+        self.vdom_state.watch_attribute(name, callback);
+    }
+
     // pub fn apply_mutations(&mut self) {
     //     // Apply the mutations to the actual dom
     //     let mut writer = MutationWriter {
@@ -177,6 +232,39 @@ impl DioxusDocument {
     // }
 }
 
+/// Counts of the templates and ElementId mappings held by a [`DioxusState`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DioxusStateStats {
+    pub templates: usize,
+    /// The length of the ElementId -> NodeId mapping, which is one more than the largest ElementId ever mapped
+    pub node_id_mapping_len: usize,
+    /// The number of ElementIds which are currently mapped to a node
+    pub mapped_element_ids: usize,
+}
+
+/// An inconsistency between a [`DioxusState`] and the document it applies mutations to.
+/// See [`DioxusState::debug_check_invariants`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MappingViolation {
+    /// An ElementId is mapped to a node which does not exist in the document
+    MissingNode {
+        element_id: ElementId,
+        node_id: NodeId,
+    },
+    /// A node is mapped from more than one ElementId
+    DuplicateMapping {
+        node_id: NodeId,
+        element_ids: (ElementId, ElementId),
+    },
+    /// The NodeId -> ElementId mapping of a node doesn't agree with the ElementId -> NodeId mapping
+    StaleReverseMapping {
+        node_id: NodeId,
+        element_id: ElementId,
+    },
+    /// A registered template refers to a node which does not exist in the document
+    MissingTemplateNode { template: String, node_id: NodeId },
+}
+
 /// The state of the Dioxus integration with the RealDom
 #[derive(Debug)]
 pub struct DioxusState {
@@ -186,6 +274,48 @@ pub struct DioxusState {
     stack: Vec<NodeId>,
     /// Mapping from vdom ElementId -> rdom NodeId
     node_id_mapping: Vec<Option<NodeId>>,
+    /// The reverse of `node_id_mapping` (rdom NodeId -> vdom ElementId), kept in sync by `map_element_id`
+    element_ids: FxHashMap<NodeId, ElementId>,
+    /// Observer notified of mutations as they are applied (if any)
+    observer: Option<Box<dyn MutationObserver>>,
+    /// The template name and root index from which each template node (or clone of a template node) originated
+    template_origins: FxHashMap<NodeId, (String, usize)>,
+    /// Callbacks invoked when specific attributes are set, keyed by attribute name
+    attribute_watchers: AttributeWatchers,
+}
+
+/// A callback invoked with the node, the previous value (`None` if the attribute wasn't set) and the new value
+/// when a watched attribute is set
+pub type AttributeCallback = Box<dyn FnMut(NodeId, Option<&str>, &AttributeValue)>;
+
+#[derive(Default)]
+struct AttributeWatchers(FxHashMap<String, Vec<AttributeCallback>>);
+
+impl std::fmt::Debug for AttributeWatchers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Receives callbacks as Dioxus mutations are applied to the document, allowing integrations
+/// (such as an accessibility bridge) to react to changes without diffing the tree.
+pub trait MutationObserver {
+    /// A node was created and assigned an ElementId
+    fn node_created(&mut self, _node_id: NodeId) {}
+    /// A node was removed from the document
+    fn node_removed(&mut self, _node_id: NodeId) {}
+    /// An attribute was set (or removed, if `value` is `None`)
+    fn attribute_set(&mut self, _node_id: NodeId, _name: &str, _value: Option<&str>) {}
+    /// The content of a text node was set
+    fn text_set(&mut self, _node_id: NodeId, _text: &str) {}
+    /// An event listener was added to an element
+    fn listener_added(&mut self, _node_id: NodeId, _name: &str) {}
+}
+
+impl std::fmt::Debug for dyn MutationObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MutationObserver")
+    }
 }
 
 /// A writer for mutations that can be used with the RealDom.
@@ -207,6 +337,10 @@ impl DioxusState {
             templates: FxHashMap::default(),
             stack: vec![root_id],
             node_id_mapping: vec![Some(root_id)],
+            element_ids: FxHashMap::from_iter([(root_id, ElementId(0))]),
+            observer: None,
+            template_origins: FxHashMap::default(),
+            attribute_watchers: AttributeWatchers::default(),
         }
     }
 
@@ -220,6 +354,127 @@ impl DioxusState {
         self.node_id_mapping.get(element_id.0).copied().flatten()
     }
 
+    /// The name and root index of the template from which a node originated (if any). Template nodes report the
+    /// template they were registered by, and nodes cloned from a template report the template they were loaded from.
+    pub fn template_origin(&self, node_id: NodeId) -> Option<(&str, usize)> {
+        self.template_origins
+            .get(&node_id)
+            .map(|(name, index)| (name.as_str(), *index))
+    }
+
+    /// Convert a NodeId to the ElementId it is mapped from (if any)
+    pub fn node_to_element_id(&self, node_id: NodeId) -> Option<ElementId> {
+        self.element_ids.get(&node_id).copied()
+    }
+
+    /// Iterate over every ElementId and the NodeId it is mapped to, in ElementId order. ElementIds which aren't
+    /// currently mapped (such as those of removed nodes) are skipped.
+    pub fn element_mappings(&self) -> impl Iterator<Item = (ElementId, NodeId)> + '_ {
+        self.node_id_mapping
+            .iter()
+            .enumerate()
+            .filter_map(|(element_id, node_id)| Some((ElementId(element_id), (*node_id)?)))
+    }
+
+    /// The nodes which the state refers to and which must therefore outlive a garbage collection of the document
+    /// even if they are detached: registered templates and nodes mapped from an ElementId
+    pub fn retained_nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.templates
+            .values()
+            .flatten()
+            .copied()
+            .chain(self.node_id_mapping.iter().flatten().copied())
+    }
+
+    /// Register an observer which is notified of each mutation as it is applied, replacing any existing observer.
+    /// See [`DioxusDocument::set_mutation_observer`].
+    pub fn set_mutation_observer(&mut self, observer: Box<dyn MutationObserver>) {
+        self.observer = Some(observer);
+    }
+
+    /// Register a callback which is invoked whenever the attribute `name` is set (or removed) on any node.
+    /// See [`DioxusDocument::watch_attribute`].
+    pub fn watch_attribute(&mut self, name: String, callback: AttributeCallback) {
+        self.attribute_watchers
+            .0
+            .entry(name)
+            .or_default()
+            .push(callback);
+    }
+
+    /// Count the templates and ElementId mappings held by the state, e.g. to check that long-running apps don't
+    /// leak ElementIds. See also `Document::stats`.
+    pub fn stats(&self) -> DioxusStateStats {
+        DioxusStateStats {
+            templates: self.templates.len(),
+            node_id_mapping_len: self.node_id_mapping.len(),
+            mapped_element_ids: self.element_mappings().count(),
+        }
+    }
+
+    /// Check that every ElementId is mapped to a live node of `doc`, that no node is mapped from more than one
+    /// ElementId, that the NodeId -> ElementId mapping agrees and that every registered template's nodes are still
+    /// alive, returning every violation found.
+    /// This walks every mapping, so it is intended for debug builds and tests.
+    pub fn debug_check_invariants(&self, doc: &Document) -> Vec<MappingViolation> {
+        let mut violations = Vec::new();
+
+        let mut mapped_from: FxHashMap<NodeId, ElementId> = FxHashMap::default();
+        for (element_id, node_id) in self.element_mappings() {
+            if doc.tree().get(node_id).is_none() {
+                violations.push(MappingViolation::MissingNode {
+                    element_id,
+                    node_id,
+                });
+            }
+            if let Some(&other_id) = mapped_from.get(&node_id) {
+                violations.push(MappingViolation::DuplicateMapping {
+                    node_id,
+                    element_ids: (other_id, element_id),
+                });
+            } else {
+                mapped_from.insert(node_id, element_id);
+            }
+        }
+        for (&node_id, &element_id) in self.element_ids.iter() {
+            if self.try_element_to_node_id(element_id) != Some(node_id) {
+                violations.push(MappingViolation::StaleReverseMapping {
+                    node_id,
+                    element_id,
+                });
+            }
+        }
+
+        for (template, node_ids) in self.templates.iter() {
+            for &node_id in node_ids {
+                if doc.tree().get(node_id).is_none() {
+                    violations.push(MappingViolation::MissingTemplateNode {
+                        template: template.clone(),
+                        node_id,
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+
+    /// Map (or unmap, if `node_id` is `None`) an ElementId, keeping the NodeId -> ElementId mapping in sync
+    fn map_element_id(&mut self, element_id: ElementId, node_id: Option<NodeId>) {
+        if self.node_id_mapping.len() <= element_id.0 {
+            self.node_id_mapping.resize(element_id.0 + 1, None);
+        }
+        let previous = std::mem::replace(&mut self.node_id_mapping[element_id.0], node_id);
+        if let Some(previous) = previous {
+            if self.element_ids.get(&previous) == Some(&element_id) {
+                self.element_ids.remove(&previous);
+            }
+        }
+        if let Some(node_id) = node_id {
+            self.element_ids.insert(node_id, element_id);
+        }
+    }
+
     // /// Create a mutation writer for the RealDom
     // pub fn create_mutation_writer<'a>(&'a mut self, doc: &'a mut Document) -> MutationWriter<'a> {
     //     MutationWriter { doc, state: self }
@@ -227,22 +482,109 @@ impl DioxusState {
 }
 
 impl MutationWriter<'_> {
-    /// Update an ElementId -> NodeId mapping
-    fn set_id_mapping(&mut self, node_id: NodeId, element_id: ElementId) {
-        let element_id: usize = element_id.0;
+    /// Replace the node `target` (and its descendants) with a new instance of root `index` of the registered
+    /// template `template_name`, returning the id of the new node. If `target` is mapped from an ElementId then
+    /// that ElementId is remapped to the new node.
+    ///
+    /// Returns `None` (leaving the document unchanged) if no such template root is registered.
+    pub fn replace_subtree_with_template(
+        &mut self,
+        target: NodeId,
+        template_name: &str,
+        index: usize,
+    ) -> Option<NodeId> {
+        let template_node_id = *self
+            .state
+            .templates
+            .get(template_name)
+            .and_then(|template| template.get(index))?;
+        let clone_id = self.doc.deep_clone_node(template_node_id);
+        self.set_template_origin(clone_id, Some((template_name, index)));
+        self.request_autofocus_in_subtree(clone_id);
+        self.notify(|observer| observer.node_created(clone_id));
 
-        // Ensure node_id_mapping is large enough to contain element_id
-        if self.state.node_id_mapping.len() <= element_id {
-            self.state.node_id_mapping.resize(element_id + 1, None);
+        let element_id = self.state.node_to_element_id(target);
+        self.doc.insert_before(target, &[clone_id]);
+        self.remove_subtree(target);
+
+        // Remap directly rather than with `set_id_mapping`, as the previously mapped node has already been removed
+        if let Some(element_id) = element_id {
+            self.state.map_element_id(element_id, Some(clone_id));
+        }
+
+        Some(clone_id)
+    }
+
+    /// Remove a node (and its descendants) from the document, unmapping any ElementIds mapped to them
+    fn remove_subtree(&mut self, node_id: NodeId) {
+        self.set_template_origin(node_id, None);
+        self.unmap_subtree(node_id);
+        self.doc.remove_node(node_id);
+        self.notify(|observer| observer.node_removed(node_id));
+    }
+
+    /// Clear the ElementId mappings of a node and its descendants, as Dioxus doesn't remove descendants one by one
+    fn unmap_subtree(&mut self, node_id: NodeId) {
+        let mut stack = vec![node_id];
+        while let Some(id) = stack.pop() {
+            if let Some(&element_id) = self.state.element_ids.get(&id) {
+                self.state.map_element_id(element_id, None);
+            }
+            if let Some(node) = self.doc.get_node(id) {
+                stack.extend(node.children.iter().copied());
+            }
+        }
+    }
+
+    /// Set (or clear) the template origin of a node and its descendants
+    fn set_template_origin(&mut self, node_id: NodeId, origin: Option<(&str, usize)>) {
+        match origin {
+            Some((name, index)) => {
+                self.state
+                    .template_origins
+                    .insert(node_id, (name.to_string(), index));
+            }
+            None => {
+                self.state.template_origins.remove(&node_id);
+            }
         }
+
+        let Some(node) = self.doc.get_node(node_id) else {
+            return;
+        };
+        for child_id in node.children.clone() {
+            self.set_template_origin(child_id, origin);
+        }
+    }
+
+    /// Notify the mutation observer (if any)
+    fn notify(&mut self, callback: impl FnOnce(&mut dyn MutationObserver)) {
+        if let Some(observer) = self.state.observer.as_deref_mut() {
+            callback(observer);
+        }
+    }
+
+    /// Update an ElementId -> NodeId mapping
+    fn set_id_mapping(&mut self, node_id: NodeId, element_id: ElementId) {
         // If element_id is already mapping to a node, remove that node from the document
-        else if let Some(mapped_node_id) = self.state.node_id_mapping[element_id] {
+        if let Some(mapped_node_id) = self.state.try_element_to_node_id(element_id) {
             // todo: we should mark these as needing garbage collection?
-            self.doc.remove_node(mapped_node_id);
+            self.remove_subtree(mapped_node_id);
         }
 
         // Set the new mapping
-        self.state.node_id_mapping[element_id] = Some(node_id);
+        self.state.map_element_id(element_id, Some(node_id));
+    }
+
+    /// Request autofocus for the last node in the subtree rooted at `node_id` with an `autofocus` attribute
+    fn request_autofocus_in_subtree(&mut self, node_id: NodeId) {
+        let node = self.doc.get_node(node_id).unwrap();
+        if node.attr(local_name!("autofocus")).is_some() {
+            self.doc.request_autofocus(node_id);
+        }
+        for child_id in node.children.clone() {
+            self.request_autofocus_in_subtree(child_id);
+        }
     }
 
     /// Find a child in the document by child index path
@@ -266,6 +608,9 @@ impl WriteMutations for MutationWriter<'_> {
             .iter()
             .map(|root| create_template_node(self.doc, root))
             .collect();
+        for (index, &root_id) in template_root_ids.iter().enumerate() {
+            self.set_template_origin(root_id, Some((template.name, index)));
+        }
 
         #[cfg(feature = "tracing")]
         tracing::info!(
@@ -285,9 +630,15 @@ impl WriteMutations for MutationWriter<'_> {
 
         let children = self.state.stack.split_off(self.state.stack.len() - m);
         let parent = self.state.element_to_node_id(id);
+        self.doc.record_child_count(parent);
+        self.doc.invalidate_layout(parent);
         for child in children {
-            self.doc.get_node_mut(parent).unwrap().children.push(child);
-            self.doc.get_node_mut(child).unwrap().parent = Some(parent);
+            let siblings = &mut self.doc.get_node_mut(parent).unwrap().children;
+            let child_idx = siblings.len();
+            siblings.push(child);
+            let child_node = self.doc.get_node_mut(child).unwrap();
+            child_node.parent = Some(parent);
+            child_node.child_idx = child_idx;
         }
     }
 
@@ -306,6 +657,7 @@ impl WriteMutations for MutationWriter<'_> {
         let node_id = self.doc.create_node(NodeData::Comment);
         self.set_id_mapping(node_id, id);
         self.state.stack.push(node_id);
+        self.notify(|observer| observer.node_created(node_id));
     }
 
     fn create_text_node(&mut self, value: &str, id: ElementId) {
@@ -315,6 +667,7 @@ impl WriteMutations for MutationWriter<'_> {
         let node_id = self.doc.create_text_node(value);
         self.set_id_mapping(node_id, id);
         self.state.stack.push(node_id);
+        self.notify(|observer| observer.node_created(node_id));
     }
 
     fn hydrate_text_node(&mut self, path: &'static [u8], value: &str, id: ElementId) {
@@ -342,6 +695,8 @@ impl WriteMutations for MutationWriter<'_> {
         }
 
         let parent = node.parent;
+        self.doc.invalidate_layout(node_id);
+        self.notify(|observer| observer.text_set(node_id, value));
 
         if let Some(parent) = parent {
             // if the text is the child of a style element, we want to put the style into the stylesheet cache
@@ -360,8 +715,11 @@ impl WriteMutations for MutationWriter<'_> {
 
         let template_node_id = self.state.templates[name][index];
         let clone_id = self.doc.deep_clone_node(template_node_id);
+        self.set_template_origin(clone_id, Some((name, index)));
+        self.request_autofocus_in_subtree(clone_id);
         self.set_id_mapping(clone_id, id);
         self.state.stack.push(clone_id);
+        self.notify(|observer| observer.node_created(clone_id));
     }
 
     fn replace_node_with(&mut self, id: ElementId, m: usize) {
@@ -371,7 +729,7 @@ impl WriteMutations for MutationWriter<'_> {
         let new_nodes = self.state.stack.split_off(self.state.stack.len() - m);
         let anchor_node_id = self.state.element_to_node_id(id);
         self.doc.insert_before(anchor_node_id, &new_nodes);
-        self.doc.remove_node(anchor_node_id);
+        self.remove_subtree(anchor_node_id);
     }
 
     fn replace_placeholder_with_nodes(&mut self, path: &'static [u8], m: usize) {
@@ -381,7 +739,7 @@ impl WriteMutations for MutationWriter<'_> {
         let new_nodes = self.state.stack.split_off(self.state.stack.len() - m);
         let anchor_node_id = self.load_child(path);
         self.doc.insert_before(anchor_node_id, &new_nodes);
-        self.doc.remove_node(anchor_node_id);
+        self.remove_subtree(anchor_node_id);
     }
 
     fn insert_nodes_after(&mut self, id: ElementId, m: usize) {
@@ -423,6 +781,16 @@ impl WriteMutations for MutationWriter<'_> {
     ) {
         let node_id = self.state.element_to_node_id(id);
         let node = self.doc.get_node_mut(node_id).unwrap();
+
+        // Capture the previous value for watchers before it is overwritten
+        let old_value = self
+            .state
+            .attribute_watchers
+            .0
+            .contains_key(name)
+            .then(|| node.attr(Atom::from(name)).map(str::to_string))
+            .flatten();
+
         if let NodeData::Element(ref mut element) = node.raw_dom_data {
             // FIXME: support non-text attributes
             if let AttributeValue::Text(val) = value {
@@ -453,6 +821,36 @@ impl WriteMutations for MutationWriter<'_> {
                 // FIXME: check namespace
                 element.attrs.retain(|attr| attr.name.local != *name);
             }
+
+            self.doc.invalidate_layout(node_id);
+
+            // Re-parse inline styles so that the element is restyled with the new declarations
+            if name == "style" && ns.is_none() {
+                match value {
+                    AttributeValue::Text(val) => self.doc.set_style_attribute(node_id, val),
+                    AttributeValue::None => self.doc.remove_style_attribute(node_id),
+                    _ => {}
+                }
+            }
+
+            if name == "autofocus" && !matches!(value, AttributeValue::None) {
+                self.doc.request_autofocus(node_id);
+            }
+
+            match value {
+                AttributeValue::Text(val) => self
+                    .notify(|observer| observer.attribute_set(node_id, name, Some(val.as_str()))),
+                AttributeValue::None => {
+                    self.notify(|observer| observer.attribute_set(node_id, name, None))
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(watchers) = self.state.attribute_watchers.0.get_mut(name) {
+            for watcher in watchers {
+                watcher(node_id, old_value.as_deref(), value);
+            }
         }
     }
 
@@ -471,8 +869,11 @@ impl WriteMutations for MutationWriter<'_> {
         let changed = text.content != value;
         text.content = value.to_string();
         let contents = text.content.clone();
+        let parent = node.parent;
+        self.doc.invalidate_layout(node_id);
+        self.notify(|observer| observer.text_set(node_id, value));
 
-        if let Some(parent) = node.parent {
+        if let Some(parent) = parent {
             // if the text is the child of a style element, we want to put the style into the stylesheet cache
             let parent = self.doc.get_node(parent).unwrap();
             if let NodeData::Element(ref element) = parent.raw_dom_data {
@@ -503,15 +904,20 @@ impl WriteMutations for MutationWriter<'_> {
             _id,
         );
 
-        // let node_id = self.state.element_to_node_id(id);
-        // let mut node = self.rdom.get_mut(node_id).unwrap();
-        // node.add_event_listener(name);
+        let node_id = self.state.element_to_node_id(_id);
+        self.doc
+            .get_node_mut(node_id)
+            .unwrap()
+            .add_event_listener(_name);
+        self.notify(|observer| observer.listener_added(node_id, _name));
     }
 
-    fn remove_event_listener(&mut self, _name: &'static str, _id: ElementId) {
-        // let node_id = self.state.element_to_node_id(id);
-        // let mut node = self.rdom.get_mut(node_id).unwrap();
-        // node.remove_event_listener(name);
+    fn remove_event_listener(&mut self, name: &'static str, id: ElementId) {
+        let node_id = self.state.element_to_node_id(id);
+        self.doc
+            .get_node_mut(node_id)
+            .unwrap()
+            .remove_event_listener(name);
     }
 
     fn remove_node(&mut self, id: ElementId) {
@@ -519,7 +925,7 @@ impl WriteMutations for MutationWriter<'_> {
         tracing::info!("remove_node id:{}", id.0);
 
         let node_id = self.state.element_to_node_id(id);
-        self.doc.remove_node(node_id);
+        self.remove_subtree(node_id);
     }
 
     fn push_root(&mut self, id: ElementId) {
@@ -586,3 +992,477 @@ fn create_template_node(doc: &mut Document, node: &TemplateNode) -> NodeId {
         TemplateNode::DynamicText { .. } => doc.create_text_node(""),
     }
 }
+
+#[test]
+fn element_mappings_skip_removed_elements_and_their_descendants() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+
+    let (mut doc, mut state) = new_document();
+    let root_id = doc.root_node().id;
+    let mutations = MutationBuilder::new()
+        .create_element("div", 1)
+        .create_element("p", 2)
+        .create_text("Hello", 3)
+        .append_children(2, 1)
+        .create_placeholder(4)
+        .append_children(1, 2)
+        .append_children(0, 1)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    let div = state.element_to_node_id(ElementId(1));
+    let p = state.element_to_node_id(ElementId(2));
+    let text = state.element_to_node_id(ElementId(3));
+    let placeholder = state.element_to_node_id(ElementId(4));
+    assert_eq!(doc.get_node(div).unwrap().children, vec![p, placeholder]);
+    assert_eq!(
+        state.element_mappings().collect::<Vec<_>>(),
+        vec![
+            (ElementId(0), root_id),
+            (ElementId(1), div),
+            (ElementId(2), p),
+            (ElementId(3), text),
+            (ElementId(4), placeholder),
+        ]
+    );
+
+    // Removing the paragraph also unmaps its text, which Dioxus doesn't remove separately
+    let mutations = MutationBuilder::new().remove(2).build();
+    write_mutations(&mut doc, &mut state, &mutations);
+    assert_eq!(
+        state.element_mappings().collect::<Vec<_>>(),
+        vec![
+            (ElementId(0), root_id),
+            (ElementId(1), div),
+            (ElementId(4), placeholder),
+        ]
+    );
+    assert_eq!(state.stats().mapped_element_ids, 3);
+}
+
+#[test]
+fn attribute_watchers_receive_the_previous_and_new_values() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+    use std::cell::RefCell;
+
+    let (mut doc, mut state) = new_document();
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let recorded = changes.clone();
+    state.watch_attribute(
+        "color".to_string(),
+        Box::new(move |node_id, old, new| {
+            let new = match new {
+                AttributeValue::Text(text) => Some(text.clone()),
+                _ => None,
+            };
+            recorded
+                .borrow_mut()
+                .push((node_id, old.map(str::to_string), new));
+        }),
+    );
+
+    let mutations = MutationBuilder::new()
+        .create_element("font", 1)
+        .append_children(0, 1)
+        .set_attribute("color", "red", 1)
+        .set_attribute("face", "serif", 1)
+        .set_attribute("color", "blue", 1)
+        .remove_attribute("color", 1)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    let font = state.element_to_node_id(ElementId(1));
+    let red = || Some("red".to_string());
+    let blue = || Some("blue".to_string());
+    assert_eq!(
+        *changes.borrow(),
+        vec![
+            (font, None, red()),
+            (font, red(), blue()),
+            (font, blue(), None)
+        ]
+    );
+}
+
+#[test]
+fn mutation_observers_are_notified_in_order() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+    use std::cell::RefCell;
+
+    #[derive(Debug, PartialEq)]
+    enum Callback {
+        NodeCreated(NodeId),
+        NodeRemoved(NodeId),
+        AttributeSet(NodeId, String, Option<String>),
+        TextSet(NodeId, String),
+        ListenerAdded(NodeId, String),
+    }
+
+    struct Recorder(Rc<RefCell<Vec<Callback>>>);
+
+    impl MutationObserver for Recorder {
+        fn node_created(&mut self, node_id: NodeId) {
+            self.0.borrow_mut().push(Callback::NodeCreated(node_id));
+        }
+        fn node_removed(&mut self, node_id: NodeId) {
+            self.0.borrow_mut().push(Callback::NodeRemoved(node_id));
+        }
+        fn attribute_set(&mut self, node_id: NodeId, name: &str, value: Option<&str>) {
+            let callback =
+                Callback::AttributeSet(node_id, name.to_string(), value.map(str::to_string));
+            self.0.borrow_mut().push(callback);
+        }
+        fn text_set(&mut self, node_id: NodeId, text: &str) {
+            let callback = Callback::TextSet(node_id, text.to_string());
+            self.0.borrow_mut().push(callback);
+        }
+        fn listener_added(&mut self, node_id: NodeId, name: &str) {
+            let callback = Callback::ListenerAdded(node_id, name.to_string());
+            self.0.borrow_mut().push(callback);
+        }
+    }
+
+    let (mut doc, mut state) = new_document();
+    let callbacks = Rc::new(RefCell::new(Vec::new()));
+    state.set_mutation_observer(Box::new(Recorder(callbacks.clone())));
+
+    let mutations = MutationBuilder::new()
+        .create_element("button", 1)
+        .create_text("Ok", 2)
+        .append_children(1, 1)
+        .append_children(0, 1)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+    let button = state.element_to_node_id(ElementId(1));
+    let text = state.element_to_node_id(ElementId(2));
+
+    let mutations = MutationBuilder::new()
+        .set_attribute("class", "primary", 1)
+        .set_text("Cancel", 2)
+        .new_event_listener("click", 1)
+        .remove_attribute("class", 1)
+        .remove(1)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    let attribute = |name: &str, value: Option<&str>| {
+        Callback::AttributeSet(button, name.to_string(), value.map(str::to_string))
+    };
+    assert_eq!(
+        *callbacks.borrow(),
+        vec![
+            Callback::NodeCreated(button),
+            Callback::NodeCreated(text),
+            attribute("class", Some("primary")),
+            Callback::TextSet(text, "Cancel".to_string()),
+            // Listeners are stored as placeholder attributes
+            attribute("click", Some("<rust func>")),
+            attribute("data-dioxus-id", Some("1")),
+            Callback::ListenerAdded(button, "click".to_string()),
+            attribute("class", None),
+            Callback::NodeRemoved(button),
+        ]
+    );
+}
+
+#[test]
+fn focused_element_maps_the_focussed_node_to_its_element_id() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+
+    let (inner, vdom_state) = new_document();
+    let mut doc = DioxusDocument {
+        vdom: VirtualDom::new(|| None),
+        vdom_state,
+        inner,
+    };
+    let mutations = MutationBuilder::new()
+        .create_element("input", 1)
+        .create_element("input", 2)
+        .append_children(0, 2)
+        .build();
+    write_mutations(&mut doc.inner, &mut doc.vdom_state, &mutations);
+
+    assert_eq!(doc.focused_element(), None);
+    assert_eq!(doc.inner.get_focus_node_id(), None);
+
+    let second = doc.vdom_state.element_to_node_id(ElementId(2));
+    doc.inner.set_focus_to(second);
+    assert_eq!(doc.focused_element(), Some(ElementId(2)));
+    assert_eq!(doc.inner.get_focus_node_id(), Some(second));
+
+    // Once the focussed node is removed, it no longer maps to an ElementId
+    let mutations = MutationBuilder::new().remove(2).build();
+    write_mutations(&mut doc.inner, &mut doc.vdom_state, &mutations);
+    assert_eq!(doc.focused_element(), None);
+}
+
+#[test]
+fn replace_subtree_with_template_swaps_a_node_for_a_template_instance() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+    use blitz_dom::assert_dom_snapshot;
+
+    const CARD: Template = Template {
+        name: "card",
+        roots: &[TemplateNode::Element {
+            tag: "section",
+            namespace: None,
+            attrs: &[TemplateAttribute::Static {
+                name: "class",
+                value: "card",
+                namespace: None,
+            }],
+            children: &[TemplateNode::Text { text: "Title" }],
+        }],
+        node_paths: &[],
+        attr_paths: &[],
+    };
+
+    let (mut doc, mut state) = new_document();
+    let mutations = MutationBuilder::new()
+        .create_element("div", 1)
+        .create_element("p", 2)
+        .create_text("Hello", 3)
+        .append_children(2, 1)
+        .create_element("hr", 4)
+        .append_children(1, 2)
+        .append_children(0, 1)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+    let mut writer = MutationWriter {
+        doc: &mut doc,
+        state: &mut state,
+    };
+    writer.register_template(CARD);
+
+    let div = writer.state.element_to_node_id(ElementId(1));
+    let p = writer.state.element_to_node_id(ElementId(2));
+    let hr = writer.state.element_to_node_id(ElementId(4));
+    assert_eq!(writer.replace_subtree_with_template(p, "card", 1), None);
+    assert_eq!(writer.replace_subtree_with_template(p, "list", 0), None);
+    let card = writer.replace_subtree_with_template(p, "card", 0).unwrap();
+
+    // The new node takes the place (and the ElementId) of the old one, whose descendants are unmapped
+    assert_eq!(doc.get_node(div).unwrap().children, vec![card, hr]);
+    assert_eq!(doc.get_node(card).unwrap().parent, Some(div));
+    assert_eq!(state.element_to_node_id(ElementId(2)), card);
+    assert_eq!(state.try_element_to_node_id(ElementId(3)), None);
+    assert_dom_snapshot!(
+        doc,
+        r#"
+            <div>
+              <section class="card">
+                "Title"
+              <hr>
+        "#
+    );
+}
+
+#[test]
+fn template_origins_are_recorded_for_templates_and_their_clones() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+
+    const ITEMS: Template = Template {
+        name: "items",
+        roots: &[
+            TemplateNode::Element {
+                tag: "li",
+                namespace: None,
+                attrs: &[],
+                children: &[TemplateNode::Text { text: "Item" }],
+            },
+            TemplateNode::Element {
+                tag: "hr",
+                namespace: None,
+                attrs: &[],
+                children: &[],
+            },
+        ],
+        node_paths: &[],
+        attr_paths: &[],
+    };
+
+    let (mut doc, mut state) = new_document();
+    let mut writer = MutationWriter {
+        doc: &mut doc,
+        state: &mut state,
+    };
+    writer.register_template(ITEMS);
+    writer.load_template("items", 0, ElementId(1));
+    writer.create_text_node("Plain", ElementId(2));
+    writer.append_children(ElementId(0), 2);
+
+    // Template nodes report the template they were registered by
+    let prototypes = state.templates["items"].clone();
+    let li_prototype = prototypes[0];
+    assert_eq!(state.template_origin(li_prototype), Some(("items", 0)));
+    let text_prototype = doc.get_node(li_prototype).unwrap().children[0];
+    assert_eq!(state.template_origin(text_prototype), Some(("items", 0)));
+    assert_eq!(state.template_origin(prototypes[1]), Some(("items", 1)));
+
+    // Clones (and their descendants) report the template root they were loaded from, and other nodes have no
+    // origin
+    let li = state.element_to_node_id(ElementId(1));
+    let text = doc.get_node(li).unwrap().children[0];
+    let plain = state.element_to_node_id(ElementId(2));
+    assert_ne!(li, li_prototype);
+    assert_eq!(state.template_origin(li), Some(("items", 0)));
+    assert_eq!(state.template_origin(text), Some(("items", 0)));
+    assert_eq!(state.template_origin(plain), None);
+
+    let mutations = MutationBuilder::new().remove(1).build();
+    write_mutations(&mut doc, &mut state, &mutations);
+    assert_eq!(state.template_origin(li), None);
+    assert_eq!(state.template_origin(text), None);
+}
+
+#[test]
+fn every_watcher_of_an_attribute_is_called_when_it_is_set() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+    use std::cell::RefCell;
+
+    let (inner, vdom_state) = new_document();
+    let mut doc = DioxusDocument {
+        vdom: VirtualDom::new(|| None),
+        vdom_state,
+        inner,
+    };
+    let first = Rc::new(RefCell::new(Vec::new()));
+    let second = Rc::new(RefCell::new(Vec::new()));
+    for calls in [first.clone(), second.clone()] {
+        doc.watch_attribute(
+            "value".to_string(),
+            Box::new(move |node_id, _, value| {
+                if let AttributeValue::Text(value) = value {
+                    calls.borrow_mut().push((node_id, value.clone()));
+                }
+            }),
+        );
+    }
+
+    let mutations = MutationBuilder::new()
+        .create_element("input", 1)
+        .create_element("input", 2)
+        .append_children(0, 2)
+        .set_attribute("value", "a", 1)
+        .set_attribute("placeholder", "Name", 2)
+        .set_attribute("value", "b", 2)
+        .set_attribute("value", "c", 1)
+        .build();
+    write_mutations(&mut doc.inner, &mut doc.vdom_state, &mutations);
+
+    let input_1 = doc.vdom_state.element_to_node_id(ElementId(1));
+    let input_2 = doc.vdom_state.element_to_node_id(ElementId(2));
+    let expected = vec![
+        (input_1, "a".to_string()),
+        (input_2, "b".to_string()),
+        (input_1, "c".to_string()),
+    ];
+    assert_eq!(*first.borrow(), expected);
+    assert_eq!(*second.borrow(), expected);
+}
+
+#[test]
+fn stats_drop_when_a_subtree_is_removed() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+
+    let (mut doc, mut state) = new_document();
+    let mutations = MutationBuilder::new()
+        .create_element("div", 1)
+        .create_element("p", 2)
+        .create_text("Hello", 3)
+        .append_children(2, 1)
+        .create_placeholder(4)
+        .append_children(1, 2)
+        .append_children(0, 1)
+        .new_event_listener("click", 2)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    let before = doc.stats();
+    assert_eq!(
+        state.stats(),
+        DioxusStateStats {
+            templates: 2,
+            node_id_mapping_len: 5,
+            mapped_element_ids: 5,
+        }
+    );
+    assert_eq!(before.listeners, 1);
+
+    let mutations = MutationBuilder::new().remove(1).build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    // Only the root stays mapped. Templates are kept, and the mapping isn't shrunk as ElementIds are reused.
+    assert_eq!(
+        state.stats(),
+        DioxusStateStats {
+            templates: 2,
+            node_id_mapping_len: 5,
+            mapped_element_ids: 1,
+        }
+    );
+    let after = doc.stats();
+    assert_eq!(before.nodes - after.nodes, 4);
+    assert_eq!(before.elements - after.elements, 2);
+    assert_eq!(before.text_nodes - after.text_nodes, 1);
+    assert_eq!(before.placeholders - after.placeholders, 1);
+    assert_eq!(after.listeners, 0);
+}
+
+#[test]
+fn debug_check_invariants_flags_stale_and_duplicate_mappings() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+
+    let (mut doc, mut state) = new_document();
+    let mutations = MutationBuilder::new()
+        .create_element("div", 1)
+        .create_element("p", 2)
+        .append_children(1, 1)
+        .append_children(0, 1)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+    assert_eq!(state.debug_check_invariants(&doc), vec![]);
+
+    // Map a third ElementId to the div and remove the paragraph without unmapping it
+    let div = state.element_to_node_id(ElementId(1));
+    let p = state.element_to_node_id(ElementId(2));
+    state.node_id_mapping.push(Some(div));
+    doc.remove_node(p);
+
+    assert_eq!(
+        state.debug_check_invariants(&doc),
+        vec![
+            MappingViolation::MissingNode {
+                element_id: ElementId(2),
+                node_id: p,
+            },
+            MappingViolation::DuplicateMapping {
+                node_id: div,
+                element_ids: (ElementId(1), ElementId(3)),
+            },
+        ]
+    );
+}
+
+#[test]
+fn removing_a_subtree_only_unmaps_its_own_element_ids() {
+    use crate::testing::{new_document, write_mutations, MutationBuilder};
+
+    let (mut doc, mut state) = new_document();
+    let mutations = MutationBuilder::new()
+        .create_element("div", 1)
+        .create_element("p", 2)
+        .append_children(1, 1)
+        .create_element("span", 3)
+        .append_children(0, 2)
+        .remove(1)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    let span = state.element_to_node_id(ElementId(3));
+    assert_eq!(state.try_element_to_node_id(ElementId(1)), None);
+    assert_eq!(state.try_element_to_node_id(ElementId(2)), None);
+    assert_eq!(state.element_ids.get(&span), Some(&ElementId(3)));
+    assert_eq!(state.element_ids.len(), 2);
+    assert_eq!(state.debug_check_invariants(&doc), vec![]);
+}
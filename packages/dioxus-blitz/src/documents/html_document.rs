@@ -49,6 +49,10 @@ impl HtmlDocument {
         // Parse HTML string into document
         DocumentHtmlParser::parse_into_doc(&mut dom, html);
 
+        if let Some(node_id) = dom.take_autofocus_request() {
+            dom.set_focus_to(node_id);
+        }
+
         HtmlDocument { inner: dom }
     }
 }
@@ -2,5 +2,7 @@ mod dioxus_document;
 mod event_handler;
 mod html_document;
 
-pub(crate) use dioxus_document::DioxusDocument;
+pub use dioxus_document::{
+    AttributeCallback, DioxusDocument, DioxusState, MutationObserver, MutationWriter,
+};
 pub(crate) use html_document::HtmlDocument;
@@ -8,10 +8,14 @@
 //!  - `hot-reload`: Enables hot-reloading of Dioxus RSX.
 //!  - `menu`: Enables the [`muda`] menubar.
 //!  - `tracing`: Enables tracing support.
+//!  - `testing` (not enabled by default): Enables the `testing` helpers for building documents from Dioxus
+//!    mutations in the tests of other crates.
 
 mod application;
 mod documents;
 mod stylo_to_winit;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod waker;
 mod window;
 
@@ -27,9 +31,12 @@ use url::Url;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 use crate::application::Application;
-use crate::documents::{DioxusDocument, HtmlDocument};
+use crate::documents::HtmlDocument;
 use crate::window::View;
 
+pub use crate::documents::{
+    AttributeCallback, DioxusDocument, DioxusState, MutationObserver, MutationWriter,
+};
 pub use crate::waker::BlitzEvent;
 pub use crate::window::WindowConfig;
 
@@ -0,0 +1,304 @@
+//! Helpers for constructing documents from Dioxus mutations in tests, without a `VirtualDom`
+
+use std::sync::{Mutex, OnceLock};
+
+use blitz_dom::{Document, Viewport};
+use dioxus::dioxus_core::{AttributeValue, ElementId, Template, TemplateNode, WriteMutations};
+use rustc_hash::FxHashMap;
+
+use crate::{DioxusState, MutationWriter};
+
+/// A single DOM mutation, modelled on the mutations a Dioxus `VirtualDom` emits.
+///
+/// Mutations operate on a stack of nodes: nodes are pushed when they are created and popped when they are
+/// inserted into the tree. Nodes are referred to by element ids chosen by the caller, where element id `0` is the
+/// root node of the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mutation {
+    /// Create an element with no attributes or children. Applied by loading a single-element template.
+    CreateElement {
+        tag: &'static str,
+        id: usize,
+    },
+    CreateTextNode {
+        value: String,
+        id: usize,
+    },
+    CreatePlaceholder {
+        id: usize,
+    },
+    /// Pop `m` nodes from the stack and append them to the children of `id`
+    AppendChildren {
+        id: usize,
+        m: usize,
+    },
+    /// Pop `m` nodes from the stack and insert them before `id`
+    InsertNodesBefore {
+        id: usize,
+        m: usize,
+    },
+    /// Pop `m` nodes from the stack and insert them after `id`
+    InsertNodesAfter {
+        id: usize,
+        m: usize,
+    },
+    /// Pop `m` nodes from the stack and replace `id` with them
+    ReplaceWith {
+        id: usize,
+        m: usize,
+    },
+    /// Set an attribute, or remove it if `value` is `None`
+    SetAttribute {
+        name: &'static str,
+        value: Option<String>,
+        id: usize,
+    },
+    SetText {
+        value: String,
+        id: usize,
+    },
+    NewEventListener {
+        name: &'static str,
+        id: usize,
+    },
+    Remove {
+        id: usize,
+    },
+}
+
+/// Builds a batch of [`Mutation`]s for constructing DOM states in tests without a Dioxus component.
+///
+/// ```ignore
+/// let (mut doc, mut state) = new_document();
+/// let mutations = MutationBuilder::new()
+///     .create_element("div", 1)
+///     .create_text("Hello", 2)
+///     .append_children(1, 1)
+///     .append_children(0, 1)
+///     .build();
+/// write_mutations(&mut doc, &mut state, &mutations);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MutationBuilder {
+    mutations: Vec<Mutation>,
+}
+
+impl MutationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create_element(mut self, tag: &'static str, id: usize) -> Self {
+        self.mutations.push(Mutation::CreateElement { tag, id });
+        self
+    }
+
+    pub fn create_text(mut self, value: &str, id: usize) -> Self {
+        let value = value.to_string();
+        self.mutations.push(Mutation::CreateTextNode { value, id });
+        self
+    }
+
+    pub fn create_placeholder(mut self, id: usize) -> Self {
+        self.mutations.push(Mutation::CreatePlaceholder { id });
+        self
+    }
+
+    pub fn append_children(mut self, id: usize, m: usize) -> Self {
+        self.mutations.push(Mutation::AppendChildren { id, m });
+        self
+    }
+
+    pub fn insert_nodes_before(mut self, id: usize, m: usize) -> Self {
+        self.mutations.push(Mutation::InsertNodesBefore { id, m });
+        self
+    }
+
+    pub fn insert_nodes_after(mut self, id: usize, m: usize) -> Self {
+        self.mutations.push(Mutation::InsertNodesAfter { id, m });
+        self
+    }
+
+    pub fn replace_with(mut self, id: usize, m: usize) -> Self {
+        self.mutations.push(Mutation::ReplaceWith { id, m });
+        self
+    }
+
+    pub fn set_attribute(mut self, name: &'static str, value: &str, id: usize) -> Self {
+        let value = Some(value.to_string());
+        self.mutations
+            .push(Mutation::SetAttribute { name, value, id });
+        self
+    }
+
+    pub fn remove_attribute(mut self, name: &'static str, id: usize) -> Self {
+        self.mutations.push(Mutation::SetAttribute {
+            name,
+            value: None,
+            id,
+        });
+        self
+    }
+
+    pub fn set_text(mut self, value: &str, id: usize) -> Self {
+        let value = value.to_string();
+        self.mutations.push(Mutation::SetText { value, id });
+        self
+    }
+
+    pub fn new_event_listener(mut self, name: &'static str, id: usize) -> Self {
+        self.mutations.push(Mutation::NewEventListener { name, id });
+        self
+    }
+
+    pub fn remove(mut self, id: usize) -> Self {
+        self.mutations.push(Mutation::Remove { id });
+        self
+    }
+
+    pub fn build(self) -> Vec<Mutation> {
+        self.mutations
+    }
+}
+
+/// Apply a batch of mutations through a [`WriteMutations`] implementation (usually a
+/// [`MutationWriter`](crate::MutationWriter)), exactly as a `VirtualDom` would. Apply subsequent batches through a
+/// writer over the same `DioxusState` so that they can refer to element ids assigned by earlier ones.
+pub fn apply_mutations(writer: &mut impl WriteMutations, mutations: &[Mutation]) {
+    let mut template_names: FxHashMap<&'static str, &'static str> = FxHashMap::default();
+    for mutation in mutations {
+        match mutation {
+            Mutation::CreateElement { tag, id } => {
+                let name = *template_names.entry(*tag).or_insert_with(|| {
+                    let template = element_template(tag);
+                    let name = template.name;
+                    writer.register_template(template);
+                    name
+                });
+                writer.load_template(name, 0, ElementId(*id));
+            }
+            Mutation::CreateTextNode { value, id } => {
+                writer.create_text_node(value, ElementId(*id))
+            }
+            Mutation::CreatePlaceholder { id } => writer.create_placeholder(ElementId(*id)),
+            Mutation::AppendChildren { id, m } => writer.append_children(ElementId(*id), *m),
+            Mutation::InsertNodesBefore { id, m } => writer.insert_nodes_before(ElementId(*id), *m),
+            Mutation::InsertNodesAfter { id, m } => writer.insert_nodes_after(ElementId(*id), *m),
+            Mutation::ReplaceWith { id, m } => writer.replace_node_with(ElementId(*id), *m),
+            Mutation::SetAttribute { name, value, id } => {
+                let value = match value {
+                    Some(value) => AttributeValue::Text(value.clone()),
+                    None => AttributeValue::None,
+                };
+                writer.set_attribute(name, None, &value, ElementId(*id));
+            }
+            Mutation::SetText { value, id } => writer.set_node_text(value, ElementId(*id)),
+            Mutation::NewEventListener { name, id } => {
+                writer.create_event_listener(name, ElementId(*id))
+            }
+            Mutation::Remove { id } => writer.remove_node(ElementId(*id)),
+        }
+    }
+}
+
+/// An empty 800x600 document, along with the `DioxusState` for applying mutations to it
+pub fn new_document() -> (Document, DioxusState) {
+    let mut doc = Document::new(Viewport::new(800, 600, 1.0));
+    let state = DioxusState::create(&mut doc);
+    (doc, state)
+}
+
+/// Apply a batch of mutations to a document through a [`MutationWriter`] over `state` (see [`apply_mutations`])
+pub fn write_mutations(doc: &mut Document, state: &mut DioxusState, mutations: &[Mutation]) {
+    apply_mutations(&mut MutationWriter { doc, state }, mutations);
+}
+
+/// A template with a single `tag` element as its root. Templates must be `'static`, so each tag's template is
+/// leaked the first time it is needed and then shared by every batch of mutations.
+fn element_template(tag: &'static str) -> Template {
+    static TEMPLATES: OnceLock<Mutex<FxHashMap<&'static str, Template>>> = OnceLock::new();
+    let mut templates = TEMPLATES.get_or_init(Default::default).lock().unwrap();
+    *templates.entry(tag).or_insert_with(|| {
+        let roots: &'static [TemplateNode] = Box::leak(Box::new([TemplateNode::Element {
+            tag,
+            namespace: None,
+            attrs: &[],
+            children: &[],
+        }]));
+        Template {
+            name: Box::leak(format!("blitz-testing:{tag}").into_boxed_str()),
+            roots,
+            node_paths: &[],
+            attr_paths: &[],
+        }
+    })
+}
+
+#[test]
+fn mutation_builder_constructs_a_tree() {
+    use blitz_dom::{assert_dom_snapshot, local_name};
+
+    let (mut doc, mut state) = new_document();
+    let mutations = MutationBuilder::new()
+        .create_element("html", 1)
+        .create_element("body", 2)
+        .create_element("ul", 3)
+        .set_attribute("id", "list", 3)
+        .create_element("li", 4)
+        .create_text("Second", 5)
+        .append_children(4, 1)
+        .append_children(3, 1)
+        .append_children(2, 1)
+        .append_children(1, 1)
+        .append_children(0, 1)
+        .create_element("li", 6)
+        .create_text("First", 7)
+        .append_children(6, 1)
+        .insert_nodes_before(4, 1)
+        .create_placeholder(8)
+        .insert_nodes_after(4, 1)
+        .set_text("Last", 5)
+        .set_attribute("class", "selected", 6)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    assert_dom_snapshot!(
+        doc,
+        r#"
+            <html>
+              <body>
+                <ul id="list">
+                  <li class="selected">
+                    "First"
+                  <li>
+                    "Last"
+                  <!-- -->
+        "#
+    );
+    let list = state.element_to_node_id(ElementId(3));
+    let placeholder = state.element_to_node_id(ElementId(8));
+    assert_eq!(doc.get_node(list).unwrap().children[2], placeholder);
+
+    let mutations = MutationBuilder::new()
+        .create_text("Replaced", 9)
+        .replace_with(8, 1)
+        .remove(6)
+        .remove_attribute("id", 3)
+        .build();
+    write_mutations(&mut doc, &mut state, &mutations);
+
+    assert_eq!(state.try_element_to_node_id(ElementId(6)), None);
+    assert_eq!(state.try_element_to_node_id(ElementId(8)), None);
+    let list = doc.get_node(list).unwrap();
+    assert_eq!(list.attr(local_name!("id")), None);
+    assert_eq!(list.children.len(), 2);
+}
+
+#[test]
+fn element_templates_are_only_leaked_once_per_tag() {
+    let first = element_template("div");
+    let second = element_template("div");
+    assert!(std::ptr::eq(first.name, second.name));
+    assert!(std::ptr::eq(first.roots, second.roots));
+    assert_ne!(element_template("span").name, first.name);
+}
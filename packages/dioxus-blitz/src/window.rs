@@ -11,6 +11,7 @@ use wgpu::rwh::HasWindowHandle;
 
 use std::sync::Arc;
 use std::task::Waker;
+use std::time::Instant;
 use winit::dpi::LogicalSize;
 use winit::event::{ElementState, MouseButton};
 use winit::event_loop::{ActiveEventLoop, EventLoopProxy};
@@ -57,6 +58,8 @@ pub(crate) struct View<Doc: DocumentLike> {
     pub devtools: Devtools,
     mouse_pos: (f32, f32),
     dom_mouse_pos: (f32, f32),
+    /// The start of the clock used to sample CSS transitions
+    animation_epoch: Instant,
 
     #[cfg(feature = "accessibility")]
     /// Accessibility adapter for `accesskit`.
@@ -96,6 +99,7 @@ impl<Doc: DocumentLike> View<Doc> {
             devtools: Default::default(),
             mouse_pos: Default::default(),
             dom_mouse_pos: Default::default(),
+            animation_epoch: Instant::now(),
 
             #[cfg(feature = "accessibility")]
             accessibility: AccessibilityState::new(&winit_window, proxy.clone()),
@@ -159,9 +163,17 @@ impl<Doc: DocumentLike> View<Doc> {
     }
 
     pub fn redraw(&mut self) {
+        let animation_time = self.animation_epoch.elapsed().as_secs_f64();
+        self.dom.as_mut().set_animation_time(animation_time);
+        self.dom.as_mut().flush_scroll();
         self.dom.as_mut().resolve();
         self.renderer
             .render(self.dom.as_ref(), self.viewport.scale_f64(), self.devtools);
+
+        // Keep rendering frames until transitions finish
+        if self.dom.as_ref().has_active_animations() {
+            self.request_redraw();
+        }
     }
 
     pub fn window_id(&self) -> WindowId {
@@ -241,10 +253,10 @@ impl<Doc: DocumentLike> View<Doc> {
             BlitzWindowEvent::Accessibility(accessibility_event) => {
                 match &*accessibility_event {
                     accesskit_winit::WindowEvent::InitialTreeRequested => {
-                        self.accessibility.build_tree(self.dom.as_ref());
+                        self.accessibility.build_initial_tree(self.dom.as_ref());
                     }
                     accesskit_winit::WindowEvent::AccessibilityDeactivated => {
-                        // TODO
+                        self.accessibility.deactivate();
                     }
                     accesskit_winit::WindowEvent::ActionRequested(_req) => {
                         // TODO
@@ -270,8 +282,13 @@ impl<Doc: DocumentLike> View<Doc> {
             WindowEvent::Moved(_) => {}
             WindowEvent::Occluded(_) => {},
             WindowEvent::Resized(physical_size) => {
-                self.viewport.window_size = (physical_size.width, physical_size.height);
-                self.kick_viewport();
+                let (width, height) = (physical_size.width, physical_size.height);
+                self.viewport.window_size = (width, height);
+                // Resizes are coalesced by the document until the next redraw
+                if width > 0 && height > 0 {
+                    self.dom.as_mut().resize_viewport(width, height);
+                }
+                self.kick_renderer_viewport();
             }
             WindowEvent::ScaleFactorChanged {
                 // scale_factor,
@@ -391,14 +408,19 @@ impl<Doc: DocumentLike> View<Doc> {
                 }
             }
             WindowEvent::MouseWheel { delta, .. } => {
-                match delta {
-                    winit::event::MouseScrollDelta::LineDelta(_, y) => {
-                        self.dom.as_mut().scroll_by(y as f64 * 20.0)
-                    }
-                    winit::event::MouseScrollDelta::PixelDelta(offsets) => {
-                        self.dom.as_mut().scroll_by(offsets.y)
+                let (dx, dy) = match delta {
+                    winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                        (x as f64 * 20.0, y as f64 * 20.0)
                     }
+                    winit::event::MouseScrollDelta::PixelDelta(offsets) => (offsets.x, offsets.y),
                 };
+                // Invert scrolling on macos
+                #[cfg(target_os = "macos")]
+                let (dx, dy) = (-dx, -dy);
+
+                // Wheel events can arrive many times per frame, so they are applied together when redrawing
+                let (x, y) = self.dom_mouse_pos;
+                self.dom.as_mut().queue_scroll(x, y, dx, dy);
                 self.request_redraw();
             }
 
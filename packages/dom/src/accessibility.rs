@@ -0,0 +1,257 @@
+//! Computation of accessible names for assistive technologies
+//!
+//! This is a simplified version of the [accessible name computation](https://www.w3.org/TR/accname-1.2/):
+//! hidden content, CSS generated content and embedded controls are not taken into account.
+
+use std::collections::{HashMap, HashSet};
+
+use html5ever::local_name;
+
+use crate::{Document, NodeData};
+
+/// Elements whose accessible name can be computed from their content
+const NAME_FROM_CONTENT_ELEMENTS: &[&str] = &[
+    "a",
+    "button",
+    "caption",
+    "figcaption",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "label",
+    "legend",
+    "li",
+    "option",
+    "summary",
+    "td",
+    "th",
+];
+
+/// The `<label>` elements of a document which refer to a form control with their `for` attribute, indexed by the
+/// id they refer to. Build one with [`Document::label_index`] to compute the names of many nodes without searching
+/// the whole document for each control's label.
+#[derive(Debug, Clone, Default)]
+pub struct LabelIndex {
+    labels: HashMap<String, usize>,
+}
+
+impl LabelIndex {
+    /// The first `<label>` in the document whose `for` attribute is `id`
+    pub fn label_for(&self, id: &str) -> Option<usize> {
+        self.labels.get(id).copied()
+    }
+}
+
+impl Document {
+    /// Index the document's `<label for="...">` elements. The index isn't updated as the document changes.
+    pub fn label_index(&self) -> LabelIndex {
+        let mut labels = HashMap::new();
+        for (label_id, label) in self.nodes.iter() {
+            if !label
+                .raw_dom_data
+                .is_element_with_tag_name(&local_name!("label"))
+            {
+                continue;
+            }
+            if let Some(id) = label.attr(local_name!("for")) {
+                labels.entry(id.to_string()).or_insert(label_id);
+            }
+        }
+        LabelIndex { labels }
+    }
+
+    /// The accessible name of a node, as announced by a screen reader. In order of priority this is taken from:
+    ///
+    ///  - the content of the elements referenced by `aria-labelledby`
+    ///  - `aria-label`
+    ///  - the `<label>` associated with a form control, or the `alt` text of an image
+    ///  - the node's text content (only for elements such as buttons, links and headings)
+    ///  - `title`
+    ///
+    /// Whitespace is collapsed. Returns an empty string if the node has no accessible name.
+    pub fn accessible_name(&self, node_id: usize) -> String {
+        self.accessible_name_with_labels(node_id, &self.label_index())
+    }
+
+    /// The accessible name of a node (see [`accessible_name`](Self::accessible_name)), using an index of the
+    /// document's labels which is shared between calls
+    pub fn accessible_name_with_labels(&self, node_id: usize, labels: &LabelIndex) -> String {
+        let mut visited = HashSet::new();
+        let name = self.compute_accessible_name(node_id, labels, &mut visited, false);
+        collapse_whitespace(&name)
+    }
+
+    /// `from_content` is true when computing the name of a node which is part of another node's name (because it
+    /// is referenced by `aria-labelledby` or is a descendant of a node named from its content).
+    /// `visited` holds nodes whose names are being computed, which guards against circular references.
+    fn compute_accessible_name(
+        &self,
+        node_id: usize,
+        labels: &LabelIndex,
+        visited: &mut HashSet<usize>,
+        from_content: bool,
+    ) -> String {
+        let node = &self.nodes[node_id];
+        let element = match &node.raw_dom_data {
+            NodeData::Text(text) => return text.content.clone(),
+            NodeData::Element(element) | NodeData::AnonymousBlock(element) => element,
+            _ => return String::new(),
+        };
+        if !visited.insert(node_id) {
+            return String::new();
+        }
+        if from_content && element.attr(local_name!("aria-hidden")) == Some("true") {
+            return String::new();
+        }
+
+        // References are not followed from within another reference (so labels can't chain)
+        if !from_content {
+            if let Some(ids) = element.attr(local_name!("aria-labelledby")) {
+                let name = ids
+                    .split_ascii_whitespace()
+                    .filter_map(|id| self.nodes_to_id.get(id))
+                    .map(|&id| self.compute_accessible_name(id, labels, visited, true))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if !name.trim().is_empty() {
+                    return name;
+                }
+            }
+        }
+
+        if let Some(label) = element.attr(local_name!("aria-label")) {
+            if !label.trim().is_empty() {
+                return label.to_string();
+            }
+        }
+
+        match element.name.local {
+            local_name!("input") | local_name!("select") | local_name!("textarea") => {
+                if let Some(label_id) = self.associated_label(node_id, labels) {
+                    let name = self.compute_accessible_name(label_id, labels, visited, true);
+                    if !name.trim().is_empty() {
+                        return name;
+                    }
+                }
+                let is_button = matches!(
+                    element.attr(local_name!("type")),
+                    Some("button" | "submit" | "reset")
+                );
+                if is_button {
+                    if let Some(value) = element.attr(local_name!("value")) {
+                        return value.to_string();
+                    }
+                }
+            }
+            local_name!("img") => {
+                if let Some(alt) = element.attr(local_name!("alt")) {
+                    return alt.to_string();
+                }
+            }
+            _ => {}
+        }
+
+        if from_content || NAME_FROM_CONTENT_ELEMENTS.contains(&&*element.name.local) {
+            let name: String = node
+                .children
+                .iter()
+                .map(|&child_id| self.compute_accessible_name(child_id, labels, visited, true))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !name.trim().is_empty() {
+                return name;
+            }
+        }
+
+        element
+            .attr(local_name!("title"))
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// The `<label>` associated with a form control: either a label whose `for` attribute is the control's id,
+    /// or the nearest `<label>` ancestor of the control
+    fn associated_label(&self, node_id: usize, labels: &LabelIndex) -> Option<usize> {
+        if let Some(label_id) = self.nodes[node_id]
+            .attr(local_name!("id"))
+            .and_then(|id| labels.label_for(id))
+        {
+            return Some(label_id);
+        }
+
+        let mut ancestor = self.nodes[node_id].parent;
+        while let Some(id) = ancestor {
+            if self.nodes[id]
+                .raw_dom_data
+                .is_element_with_tag_name(&local_name!("label"))
+            {
+                return Some(id);
+            }
+            ancestor = self.nodes[id].parent;
+        }
+        None
+    }
+}
+
+/// Collapse runs of whitespace to a single space and trim the ends
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn accessible_names_follow_aria_precedence() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <button id="labelled" aria-label="Close dialog">X</button>
+        <button id="content">  Save
+            <span>changes</span> </button>
+        <span id="first">Billing</span><span id="second">address</span>
+        <div id="referenced" role="region" aria-labelledby="first second" aria-label="Ignored"></div>
+        <label for="name">Your name</label><input id="name">
+        <label>Email <input id="email"></label>
+        <div id="cycle-a" aria-labelledby="cycle-b">A</div>
+        <div id="cycle-b" aria-labelledby="cycle-a">B</div>
+        <div id="titled" title="Tooltip"></div>
+        <div id="unnamed">Not a name</div>
+    </body></html>"#;
+    let doc = parse(html);
+    let name = |id: &str| doc.accessible_name(by_id(&doc, id));
+
+    assert_eq!(name("labelled"), "Close dialog");
+    assert_eq!(name("content"), "Save changes");
+    assert_eq!(name("referenced"), "Billing address");
+    assert_eq!(name("name"), "Your name");
+    // The input itself has no text, so the label's name is just its text
+    assert_eq!(name("email"), "Email");
+    // Referenced nodes are named from their content, without following their own references
+    assert_eq!(name("cycle-a"), "B");
+    assert_eq!(name("titled"), "Tooltip");
+    assert_eq!(name("unnamed"), "");
+}
+
+#[test]
+fn label_index_maps_controls_to_the_first_label_for_them() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <label id="first" for="name">Name</label><label for="name">Full name</label><input id="name">
+        <div for="email">Not a label</div><input id="email">
+    </body></html>"#;
+    let doc = parse(html);
+    let labels = doc.label_index();
+
+    assert_eq!(labels.label_for("name"), Some(by_id(&doc, "first")));
+    assert_eq!(labels.label_for("email"), None);
+    assert_eq!(
+        doc.accessible_name_with_labels(by_id(&doc, "name"), &labels),
+        "Name"
+    );
+    assert_eq!(
+        doc.accessible_name_with_labels(by_id(&doc, "email"), &labels),
+        ""
+    );
+}
@@ -0,0 +1,270 @@
+//! Export of the document as an [AccessKit](https://accesskit.dev) tree, for platform screen reader support
+//!
+//! Each node of the document which is exposed to assistive technologies becomes an AccessKit node with the same
+//! id. Hidden subtrees (`display: none` or `aria-hidden="true"`), inert subtrees, comments and whitespace-only text
+//! are omitted.
+
+use std::collections::HashMap;
+
+use accesskit::{NodeBuilder, NodeId, Rect, Role, Tree, TreeUpdate};
+use html5ever::local_name;
+
+use crate::accessibility::LabelIndex;
+use crate::{Document, NodeData};
+
+/// Converts a document into AccessKit tree updates. The exporter remembers the nodes it has exported, so that
+/// each update only contains the nodes which have changed since the previous one.
+#[derive(Default)]
+pub struct AccessibilityExporter {
+    exported: HashMap<usize, accesskit::Node>,
+}
+
+impl AccessibilityExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget the nodes which have been exported, so that the next update describes the whole tree again (e.g.
+    /// because the platform adapter was deactivated and has been reactivated)
+    pub fn reset(&mut self) {
+        self.exported.clear();
+    }
+
+    /// Build an update containing the nodes which are new or have changed since the last update (every node for
+    /// the first update, which also describes the tree). The focused node is always included as the update's focus.
+    ///
+    /// This assumes styles are resolved and layout is complete.
+    pub fn update(&mut self, doc: &Document) -> TreeUpdate {
+        let mut nodes = HashMap::new();
+        let labels = doc.label_index();
+        build_node(doc, doc.root_node().id, &labels, &mut nodes);
+
+        let changed = nodes
+            .iter()
+            .filter(|&(node_id, node)| self.exported.get(node_id) != Some(node))
+            .map(|(&node_id, node)| (NodeId(node_id as u64), node.clone()))
+            .collect();
+        let tree = self
+            .exported
+            .is_empty()
+            .then(|| Tree::new(NodeId(doc.root_node().id as u64)));
+        self.exported = nodes;
+
+        let focus = doc
+            .focus_node_id
+            .filter(|node_id| self.exported.contains_key(node_id))
+            .unwrap_or(doc.root_node().id);
+        TreeUpdate {
+            nodes: changed,
+            tree,
+            focus: NodeId(focus as u64),
+        }
+    }
+}
+
+/// Build the AccessKit node for `node_id` and its exposed descendants, adding them to `nodes`. Returns whether
+/// the node is exposed.
+fn build_node(
+    doc: &Document,
+    node_id: usize,
+    labels: &LabelIndex,
+    nodes: &mut HashMap<usize, accesskit::Node>,
+) -> bool {
+    let node = &doc.nodes[node_id];
+    if node.is_inert() {
+        return false;
+    }
+    let mut builder = match &node.raw_dom_data {
+        NodeData::Document => NodeBuilder::new(Role::Document),
+        NodeData::Text(text) => {
+            if text.content.trim().is_empty() {
+                return false;
+            }
+            let mut builder = NodeBuilder::new(Role::StaticText);
+            builder.set_name(text.content.as_str());
+            builder
+        }
+        NodeData::Element(element) => {
+            if matches!(node.style.display, taffy::Display::None)
+                || element.attr(local_name!("aria-hidden")) == Some("true")
+            {
+                return false;
+            }
+            let mut builder = NodeBuilder::new(role(doc, node_id));
+            builder.set_html_tag(&*element.name.local);
+            let name = doc.accessible_name_with_labels(node_id, labels);
+            if !name.is_empty() {
+                builder.set_name(name);
+            }
+            if let Some(level) = heading_level(&element.name.local) {
+                builder.set_level(level);
+            }
+            builder
+        }
+        _ => return false,
+    };
+
+    let bounds = doc.scrolled_border_box(node_id);
+    builder.set_bounds(Rect::new(bounds.x0, bounds.y0, bounds.x1, bounds.y1));
+
+    let children: Vec<NodeId> = node
+        .children
+        .iter()
+        .copied()
+        .filter(|&child_id| build_node(doc, child_id, labels, nodes))
+        .map(|child_id| NodeId(child_id as u64))
+        .collect();
+    builder.set_children(children);
+
+    nodes.insert(node_id, builder.build());
+    true
+}
+
+/// The role of an element: taken from its `role` attribute if it is one of the roles we know of, and otherwise
+/// from its tag (and for inputs, its type)
+fn role(doc: &Document, node_id: usize) -> Role {
+    let node = &doc.nodes[node_id];
+    let explicit_role = node.attr(local_name!("role")).and_then(|role| {
+        Some(match role.trim() {
+            "article" => Role::Article,
+            "banner" => Role::Banner,
+            "button" => Role::Button,
+            "checkbox" => Role::CheckBox,
+            "complementary" => Role::Complementary,
+            "contentinfo" => Role::ContentInfo,
+            "form" => Role::Form,
+            "heading" => Role::Heading,
+            "img" => Role::Image,
+            "link" => Role::Link,
+            "list" => Role::List,
+            "listitem" => Role::ListItem,
+            "main" => Role::Main,
+            "navigation" => Role::Navigation,
+            "radio" => Role::RadioButton,
+            "region" => Role::Region,
+            "textbox" => Role::TextInput,
+            _ => return None,
+        })
+    });
+    if let Some(role) = explicit_role {
+        return role;
+    }
+
+    let Some(element) = node.raw_dom_data.downcast_element() else {
+        return Role::Unknown;
+    };
+    match element.name.local {
+        local_name!("a") if element.attr(local_name!("href")).is_some() => Role::Link,
+        local_name!("article") => Role::Article,
+        local_name!("button") => Role::Button,
+        local_name!("footer") => Role::ContentInfo,
+        local_name!("form") => Role::Form,
+        local_name!("h1")
+        | local_name!("h2")
+        | local_name!("h3")
+        | local_name!("h4")
+        | local_name!("h5")
+        | local_name!("h6") => Role::Heading,
+        local_name!("header") => Role::Banner,
+        local_name!("img") => Role::Image,
+        local_name!("input") => match element.attr(local_name!("type")) {
+            Some("button" | "submit" | "reset") => Role::Button,
+            Some("checkbox") => Role::CheckBox,
+            Some("number") => Role::NumberInput,
+            Some("radio") => Role::RadioButton,
+            _ => Role::TextInput,
+        },
+        local_name!("li") => Role::ListItem,
+        local_name!("main") => Role::Main,
+        local_name!("nav") => Role::Navigation,
+        local_name!("ol") | local_name!("ul") => Role::List,
+        local_name!("p") => Role::Paragraph,
+        local_name!("section") => Role::Section,
+        local_name!("table") => Role::Table,
+        local_name!("textarea") => Role::MultilineTextInput,
+        _ => Role::GenericContainer,
+    }
+}
+
+fn heading_level(tag: &str) -> Option<usize> {
+    match tag {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+#[test]
+fn exported_nodes_have_roles_and_names() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <h2 id="title">Settings</h2>
+        <button id="save" aria-label="Save settings">Save</button>
+        <div id="hidden" aria-hidden="true"><button>Hidden</button></div>
+        <nav id="nav"><a id="home" href="/">Home</a></nav>
+        <label for="volume">Volume</label><input id="volume">
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let id = |name: &str| NodeId(by_id(&doc, name) as u64);
+
+    let mut exporter = AccessibilityExporter::new();
+    let update = exporter.update(&doc);
+    assert_eq!(update.tree, Some(Tree::new(NodeId(0))));
+    assert_eq!(update.focus, NodeId(0));
+    let node = |node_id: NodeId| {
+        update
+            .nodes
+            .iter()
+            .find(|(id, _)| *id == node_id)
+            .map(|(_, node)| node)
+    };
+
+    let title = node(id("title")).unwrap();
+    assert_eq!(title.role(), Role::Heading);
+    assert_eq!(title.name(), Some("Settings"));
+    assert_eq!(title.level(), Some(2));
+    let save = node(id("save")).unwrap();
+    assert_eq!(save.role(), Role::Button);
+    assert_eq!(save.name(), Some("Save settings"));
+    assert_eq!(node(id("nav")).unwrap().role(), Role::Navigation);
+    assert_eq!(node(id("home")).unwrap().role(), Role::Link);
+    assert_eq!(node(id("home")).unwrap().name(), Some("Home"));
+    assert_eq!(node(id("volume")).unwrap().name(), Some("Volume"));
+    assert!(node(id("hidden")).is_none());
+
+    // Only changed nodes are exported by later updates
+    let save_id = by_id(&doc, "save");
+    doc.set_focus_to(save_id);
+    doc.resolve();
+    let update = exporter.update(&doc);
+    assert!(update.tree.is_none());
+    assert!(update.nodes.is_empty());
+    assert_eq!(update.focus, NodeId(save_id as u64));
+}
+
+#[test]
+fn inert_subtrees_are_not_exported() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="dialog" inert><button id="inside">Hidden</button></div>
+        <button id="outside">Shown</button>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let id = |name: &str| NodeId(by_id(&doc, name) as u64);
+    let (dialog, inside, outside) = (id("dialog"), id("inside"), id("outside"));
+
+    let mut exporter = AccessibilityExporter::new();
+    let update = exporter.update(&doc);
+    let exported = |node_id: NodeId| update.nodes.iter().any(|(id, _)| *id == node_id);
+    assert!(exported(outside));
+    assert!(!exported(dialog));
+    assert!(!exported(inside));
+}
@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
 use parley::layout::PositionedLayoutItem;
+use style::properties::ComputedValues;
+use style_traits::ToCss;
 
-use crate::Document;
+use crate::{Document, Node, NodeData};
 
 impl Document {
     pub fn debug_log_node(&self, node_id: usize) {
@@ -77,3 +81,253 @@ impl Document {
         // taffy::print_tree(&self.dom, node_id.into());
     }
 }
+
+/// A broken structural invariant of the document tree, as reported by [`Document::debug_check_invariants`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// A node lists a child which does not exist in the document
+    MissingChild { parent: usize, child: usize },
+    /// A node lists a child whose parent is some other node (or no node)
+    ChildHasWrongParent {
+        parent: usize,
+        child: usize,
+        child_parent: Option<usize>,
+    },
+    /// A node's parent does not exist in the document
+    MissingParent { node: usize, parent: usize },
+    /// A node's parent does not list the node as one of its children
+    NotAChildOfParent { node: usize, parent: usize },
+    /// A node's `child_idx` does not match its position in its parent's children
+    WrongChildIndex {
+        node: usize,
+        child_idx: usize,
+        actual_idx: usize,
+    },
+    /// The focussed node does not exist in the document
+    MissingFocusNode(usize),
+    /// The hovered node does not exist in the document
+    MissingHoverNode(usize),
+}
+
+impl Document {
+    /// Check that the tree's parent/child links are consistent and that the focussed and hovered nodes exist.
+    ///
+    /// This walks every node in the document so it is intended for debugging and tests rather than for use
+    /// on every frame.
+    pub fn debug_check_invariants(&self) -> Vec<InvariantViolation> {
+        let mut violations = Vec::new();
+
+        for (id, node) in self.nodes.iter() {
+            for &child_id in &node.children {
+                match self.nodes.get(child_id) {
+                    None => violations.push(InvariantViolation::MissingChild {
+                        parent: id,
+                        child: child_id,
+                    }),
+                    Some(child) if child.parent != Some(id) => {
+                        violations.push(InvariantViolation::ChildHasWrongParent {
+                            parent: id,
+                            child: child_id,
+                            child_parent: child.parent,
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            let Some(parent_id) = node.parent else {
+                continue;
+            };
+            let Some(parent) = self.nodes.get(parent_id) else {
+                violations.push(InvariantViolation::MissingParent {
+                    node: id,
+                    parent: parent_id,
+                });
+                continue;
+            };
+            match parent.children.iter().position(|&child_id| child_id == id) {
+                None => violations.push(InvariantViolation::NotAChildOfParent {
+                    node: id,
+                    parent: parent_id,
+                }),
+                Some(actual_idx) if actual_idx != node.child_idx => {
+                    violations.push(InvariantViolation::WrongChildIndex {
+                        node: id,
+                        child_idx: node.child_idx,
+                        actual_idx,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        if let Some(focus_id) = self.focus_node_id {
+            if !self.nodes.contains(focus_id) {
+                violations.push(InvariantViolation::MissingFocusNode(focus_id));
+            }
+        }
+        if let Some(hover_id) = self.hover_node_id {
+            if !self.nodes.contains(hover_id) {
+                violations.push(InvariantViolation::MissingHoverNode(hover_id));
+            }
+        }
+
+        violations
+    }
+}
+
+/// Serializes one resolved value of a node for [`Document::computed_styles`]
+type ComputedStyleSerializer = fn(&Node, &ComputedValues) -> String;
+
+/// The values reported by [`Document::computed_styles`], keyed by name. Style values are serialized as CSS and
+/// layout values (the node's border box relative to its parent) in CSS pixels.
+const COMPUTED_STYLE_SERIALIZERS: &[(&str, ComputedStyleSerializer)] = &[
+    ("background-color", |_, style| {
+        style.get_background().background_color.to_css_string()
+    }),
+    ("color", |_, style| {
+        style.get_inherited_text().color.to_css_string()
+    }),
+    ("display", |_, style| style.clone_display().to_css_string()),
+    ("font-family", |_, style| {
+        style.clone_font_family().to_css_string()
+    }),
+    ("font-size", |_, style| {
+        style.get_font().font_size.computed_size().to_css_string()
+    }),
+    ("font-weight", |_, style| {
+        style.clone_font_weight().to_css_string()
+    }),
+    ("layout-height", |node, _| {
+        format!("{}px", node.final_layout.size.height)
+    }),
+    ("layout-width", |node, _| {
+        format!("{}px", node.final_layout.size.width)
+    }),
+    ("layout-x", |node, _| {
+        format!("{}px", node.final_layout.location.x)
+    }),
+    ("layout-y", |node, _| {
+        format!("{}px", node.final_layout.location.y)
+    }),
+    ("opacity", |_, style| {
+        style.get_effects().opacity.to_string()
+    }),
+    ("position", |_, style| {
+        style.clone_position().to_css_string()
+    }),
+    ("visibility", |_, style| {
+        style.clone_visibility().to_css_string()
+    }),
+];
+
+impl Document {
+    /// The resolved style and layout values of a node as a sorted map from name to serialized value, for display
+    /// in a devtools "computed styles" panel. Returns an empty map for nodes which haven't been styled.
+    pub fn computed_styles(&self, node_id: usize) -> BTreeMap<String, String> {
+        let Some(node) = self.get_node(node_id) else {
+            return BTreeMap::new();
+        };
+        let Some(style) = node.primary_styles() else {
+            return BTreeMap::new();
+        };
+
+        COMPUTED_STYLE_SERIALIZERS
+            .iter()
+            .map(|(name, serialize)| (name.to_string(), serialize(node, &style)))
+            .collect()
+    }
+}
+
+/// Counts of the nodes and listeners in a document (see [`Document::stats`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DomStats {
+    /// The total number of nodes, including the document node and anonymous blocks
+    pub nodes: usize,
+    pub elements: usize,
+    pub text_nodes: usize,
+    /// Comment nodes, which are used as placeholders for content which isn't currently rendered
+    pub placeholders: usize,
+    /// The total number of event listeners registered on all nodes
+    pub listeners: usize,
+}
+
+impl Document {
+    /// Count the nodes and listeners in the document, e.g. to check that long-running tests don't leak nodes
+    pub fn stats(&self) -> DomStats {
+        let mut stats = DomStats::default();
+        for (_, node) in self.nodes.iter() {
+            stats.nodes += 1;
+            stats.listeners += node.listeners.len();
+            match node.raw_dom_data {
+                NodeData::Element(_) => stats.elements += 1,
+                NodeData::Text(_) => stats.text_nodes += 1,
+                NodeData::Comment => stats.placeholders += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+#[test]
+fn computed_styles_contain_resolved_values() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="box" style="color: red; font-size: 20px; width: 100px; height: 50px"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let styles = doc.computed_styles(by_id(&doc, "box"));
+    assert_eq!(styles["color"], "rgb(255, 0, 0)");
+    assert_eq!(styles["font-size"], "20px");
+    assert_eq!(styles["display"], "block");
+    assert_eq!(styles["layout-width"], "100px");
+    assert_eq!(styles["layout-height"], "50px");
+}
+
+#[test]
+fn detects_broken_parent_links() {
+    use crate::testing::parse;
+
+    let html = "<!DOCTYPE html><html><body><div><p>hello</p><p>world</p></div></body></html>";
+    let mut doc = parse(html);
+    assert_eq!(doc.debug_check_invariants(), Vec::new());
+
+    // Detach the body from the html element without removing it from the html element's children
+    let body_id = doc.root_element().children.last().copied().unwrap();
+    let html_id = doc.nodes[body_id].parent.take().unwrap();
+
+    assert_eq!(
+        doc.debug_check_invariants(),
+        vec![InvariantViolation::ChildHasWrongParent {
+            parent: html_id,
+            child: body_id,
+            child_parent: None,
+        }]
+    );
+}
+
+#[test]
+fn stats_count_nodes_by_kind() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="list"><p id="first">hello</p><!-- placeholder --><p>world</p></div></body></html>"#;
+    let mut doc = parse(html);
+    doc.nodes[by_id(&doc, "first")].add_event_listener("click");
+    doc.nodes[by_id(&doc, "first")].add_event_listener("input");
+
+    let before = doc.stats();
+    assert_eq!(before.nodes, doc.nodes.len());
+    assert_eq!(before.listeners, 2);
+
+    doc.remove_node(by_id(&doc, "list"));
+    let after = doc.stats();
+    assert_eq!(before.nodes - after.nodes, 6);
+    assert_eq!(before.elements - after.elements, 3);
+    assert_eq!(before.text_nodes - after.text_nodes, 2);
+    assert_eq!(before.placeholders - after.placeholders, 1);
+    assert_eq!(after.listeners, 0);
+}
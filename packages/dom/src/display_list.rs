@@ -0,0 +1,1533 @@
+//! Flattening of the styled and laid-out tree into a list of paint operations
+//!
+//! A display list decouples painting from the DOM: a renderer can paint the document by iterating over
+//! the list in order without needing to understand the tree, styles or layout.
+
+use std::collections::HashSet;
+
+use html5ever::local_name;
+use parley::layout::PositionedLayoutItem;
+use peniko::kurbo::{Affine, Point, Rect, RoundedRectRadii, Size, Vec2};
+use peniko::Color;
+use style::computed_values::object_fit::T as ObjectFit;
+use style::properties::generated::longhands::position::computed_value::T as Position;
+use style::properties::generated::longhands::visibility::computed_value::T as Visibility;
+use style::properties::ComputedValues;
+use style::values::computed::{Length, Overflow};
+use style::values::specified::{BorderStyle, OutlineStyle};
+
+use crate::util::{resolve_color, ToPenikoColor};
+use crate::{Document, Node, PseudoContent};
+
+/// A single paint operation. Rects are in CSS pixels relative to the top-left corner of the document (before the
+/// transforms of any enclosing layers are applied).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayItem {
+    /// Paint subsequent items into a layer until the matching `PopLayer`. The layer is composited into its parent
+    /// with `transform` (which maps points in the layer to points in its parent) applied, at `opacity`.
+    PushLayer {
+        node_id: usize,
+        transform: Affine,
+        opacity: f32,
+    },
+    /// Composite the most recently pushed layer into its parent
+    PopLayer,
+    /// Clip subsequent items to `rect` until the matching `PopClip`
+    PushClip { node_id: usize, rect: Rect },
+    /// Remove the most recently pushed clip
+    PopClip,
+    /// Draw one of the box shadows of an element whose border box is `rect` (see [`box_shadows`]). Outset shadows
+    /// are listed before the element's background and inset shadows after it.
+    BoxShadow {
+        node_id: usize,
+        rect: Rect,
+        shadow: BoxShadow,
+    },
+    /// Fill `rect` (an element's border box) with a solid background colour
+    Rect {
+        node_id: usize,
+        rect: Rect,
+        color: Color,
+    },
+    /// Fill `rect` (an element's border box) with the background image (e.g. a gradient) at `index` in the
+    /// element's `background-image` list
+    BackgroundImage {
+        node_id: usize,
+        rect: Rect,
+        index: usize,
+    },
+    /// Stroke the border of an element whose border box is `rect`.
+    /// Widths and colours are in top, right, bottom, left order.
+    Border {
+        node_id: usize,
+        rect: Rect,
+        widths: [f64; 4],
+        colors: [Color; 4],
+    },
+    /// Marks the end of the items which paint an element's own box (its shadows, background and border), so that
+    /// every painted element is listed even if its box isn't visible. `rect` is the element's border box.
+    Element { node_id: usize, rect: Rect },
+    /// Draw the replaced content (an image or SVG) of an element whose content box is `rect`
+    Replaced { node_id: usize, rect: Rect },
+    /// Draw the text of an inline root or text input whose content box is `rect`
+    Text { node_id: usize, rect: Rect },
+    /// Stroke the outline of an element whose border box is `rect` (see [`Outline`])
+    Outline {
+        node_id: usize,
+        rect: Rect,
+        outline: Outline,
+    },
+    /// Generated content of an element within an inline root (see [`Node::generated_content`]). The text is drawn
+    /// as part of the inline root's `Text` item: `Before` content is listed just before that item and `After`
+    /// content just after it, each in tree order.
+    GeneratedText {
+        node_id: usize,
+        pseudo: PseudoContent,
+        text: String,
+    },
+}
+
+/// A clip applied by an ancestor of a node: the ancestor's padding box (in CSS pixels relative to the top-left
+/// corner of the document) with the ancestor's inner border radii
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipRect {
+    pub node_id: usize,
+    pub rect: Rect,
+    pub radii: RoundedRectRadii,
+}
+
+impl Document {
+    /// The clips which apply when painting a node: one for each ancestor which clips its content (with
+    /// `overflow` other than `visible`), outermost first. Painting the node on its own (e.g. for a partial redraw)
+    /// requires intersecting all of them. Scroll offsets of ancestors are taken into account.
+    pub fn clip_stack(&self, node_id: usize) -> Vec<ClipRect> {
+        let mut ancestors = Vec::new();
+        let mut ancestor = self.nodes[node_id].parent;
+        while let Some(ancestor_id) = ancestor {
+            ancestors.push(ancestor_id);
+            ancestor = self.nodes[ancestor_id].parent;
+        }
+
+        let mut clips = Vec::new();
+        let mut origin = Point::ZERO;
+        for &ancestor_id in ancestors.iter().rev() {
+            let node = &self.nodes[ancestor_id];
+            let layout = node.final_layout;
+            origin += Vec2::new(layout.location.x as f64, layout.location.y as f64);
+
+            if let Some(style) = node.primary_styles() {
+                if !matches!(style.get_box().overflow_x, Overflow::Visible) {
+                    let border_box = Rect::from_origin_size(
+                        origin,
+                        (layout.size.width as f64, layout.size.height as f64),
+                    );
+                    let rect = Rect::new(
+                        border_box.x0 + layout.border.left as f64,
+                        border_box.y0 + layout.border.top as f64,
+                        border_box.x1 - layout.border.right as f64,
+                        border_box.y1 - layout.border.bottom as f64,
+                    );
+
+                    // The padding box's corners are rounded by the border radius less the width of the border
+                    // TODO: elliptical radii
+                    let border = style.get_border();
+                    let width = Length::new(layout.size.width);
+                    let inner_radius = |radius: &style::values::computed::BorderCornerRadius,
+                                        border_width: f32| {
+                        (radius.0.width.0.resolve(width).px() - border_width).max(0.0) as f64
+                    };
+                    let radii = RoundedRectRadii::new(
+                        inner_radius(&border.border_top_left_radius, layout.border.left),
+                        inner_radius(&border.border_top_right_radius, layout.border.right),
+                        inner_radius(&border.border_bottom_right_radius, layout.border.right),
+                        inner_radius(&border.border_bottom_left_radius, layout.border.left),
+                    );
+
+                    clips.push(ClipRect {
+                        node_id: ancestor_id,
+                        rect,
+                        radii,
+                    });
+                }
+            }
+
+            // Descendants are painted relative to the scrolled content
+            origin -= node.scroll_offset;
+        }
+        clips
+    }
+
+    /// Whether any part of a node's border box is visible within `viewport` (in CSS pixels relative to the top-left
+    /// corner of the document). Scroll offsets of ancestors are taken into account, and the parts of the node
+    /// clipped by its ancestors (see [`clip_stack`](Self::clip_stack)) aren't visible.
+    ///
+    /// TODO: rounded clip corners and transforms
+    pub fn is_in_viewport(&self, node_id: usize, viewport: Rect) -> bool {
+        let visible = self.clip_stack(node_id).iter().fold(
+            self.scrolled_border_box(node_id).intersect(viewport),
+            |visible, clip| visible.intersect(clip.rect),
+        );
+        visible.width() > 0.0 && visible.height() > 0.0
+    }
+
+    /// A node's border box in CSS pixels relative to the top-left corner of the document, taking the scroll
+    /// offsets of its ancestors into account
+    pub(crate) fn scrolled_border_box(&self, node_id: usize) -> Rect {
+        let node = &self.nodes[node_id];
+        let layout = node.final_layout;
+        let mut origin = Point::new(layout.location.x as f64, layout.location.y as f64);
+        let mut ancestor = node.parent;
+        while let Some(ancestor_id) = ancestor {
+            let ancestor_node = &self.nodes[ancestor_id];
+            let location = ancestor_node.final_layout.location;
+            origin += Vec2::new(location.x as f64, location.y as f64) - ancestor_node.scroll_offset;
+            ancestor = ancestor_node.parent;
+        }
+
+        Rect::from_origin_size(
+            origin,
+            (layout.size.width as f64, layout.size.height as f64),
+        )
+    }
+
+    /// Flatten the document into a list of paint operations in paint order. Elements which are transformed or
+    /// partially transparent are painted into layers.
+    ///
+    /// This assumes styles are resolved and layout is complete.
+    pub fn build_display_list(&self) -> Vec<DisplayItem> {
+        let mut items = Vec::new();
+        if let Some(root) = self.try_root_element() {
+            self.push_display_items(&mut items, root.id, Point::ZERO);
+        }
+        items
+    }
+
+    fn push_display_items(&self, items: &mut Vec<DisplayItem>, node_id: usize, location: Point) {
+        let node = &self.nodes[node_id];
+        if !is_painted(node) {
+            return;
+        }
+        let Some(style) = node.primary_styles() else {
+            return;
+        };
+
+        // Fixed elements are positioned relative to the viewport rather than their parent, and don't scroll
+        let location = match style.get_box().position {
+            Position::Fixed => Point::new(0.0, -self.scroll_offset),
+            _ => location,
+        };
+        let layout = node.final_layout;
+        let origin = location
+            + Vec2::new(layout.location.x as f64, layout.location.y as f64)
+            + self.sticky_offset(node_id);
+        let border_box = Rect::from_origin_size(
+            origin,
+            (layout.size.width as f64, layout.size.height as f64),
+        );
+        let widths = [
+            layout.border.top as f64,
+            layout.border.right as f64,
+            layout.border.bottom as f64,
+            layout.border.left as f64,
+        ];
+        let padding_box = Rect::new(
+            border_box.x0 + widths[3],
+            border_box.y0 + widths[0],
+            border_box.x1 - widths[1],
+            border_box.y1 - widths[2],
+        );
+        let content_box = Rect::new(
+            padding_box.x0 + layout.padding.left as f64,
+            padding_box.y0 + layout.padding.top as f64,
+            padding_box.x1 - layout.padding.right as f64,
+            padding_box.y1 - layout.padding.bottom as f64,
+        );
+
+        // The element's transform is applied about the top-left corner of its border box
+        let local_transform = node.local_transform();
+        let opacity = style.get_effects().opacity;
+        let is_layer = local_transform != Affine::IDENTITY || opacity < 1.0;
+        if is_layer {
+            items.push(DisplayItem::PushLayer {
+                node_id,
+                transform: Affine::translate(origin.to_vec2())
+                    * local_transform
+                    * Affine::translate(-origin.to_vec2()),
+                opacity,
+            });
+        }
+
+        let shadows = box_shadows(&style);
+        for &shadow in shadows.iter().filter(|shadow| !shadow.inset) {
+            items.push(DisplayItem::BoxShadow {
+                node_id,
+                rect: border_box,
+                shadow,
+            });
+        }
+
+        for layer in background_layers(&style) {
+            items.push(match layer {
+                BackgroundLayer::Color(color) => DisplayItem::Rect {
+                    node_id,
+                    rect: border_box,
+                    color,
+                },
+                BackgroundLayer::Gradient(index) | BackgroundLayer::Image(index) => {
+                    DisplayItem::BackgroundImage {
+                        node_id,
+                        rect: border_box,
+                        index,
+                    }
+                }
+            });
+        }
+
+        for &shadow in shadows.iter().filter(|shadow| shadow.inset) {
+            items.push(DisplayItem::BoxShadow {
+                node_id,
+                rect: border_box,
+                shadow,
+            });
+        }
+
+        if widths.iter().any(|&width| width > 0.0) {
+            let border = style.get_border();
+            items.push(DisplayItem::Border {
+                node_id,
+                rect: border_box,
+                widths,
+                colors: [
+                    resolve_color(&style, &border.border_top_color).as_peniko(),
+                    resolve_color(&style, &border.border_right_color).as_peniko(),
+                    resolve_color(&style, &border.border_bottom_color).as_peniko(),
+                    resolve_color(&style, &border.border_left_color).as_peniko(),
+                ],
+            });
+        }
+
+        items.push(DisplayItem::Element {
+            node_id,
+            rect: border_box,
+        });
+
+        let element = node.element_data();
+        if element
+            .is_some_and(|element| element.image_data().is_some() || element.svg_data().is_some())
+        {
+            items.push(DisplayItem::Replaced {
+                node_id,
+                rect: content_box,
+            });
+        }
+
+        // TODO: account for overflow_x vs overflow_y
+        let should_clip = !matches!(style.get_box().overflow_x, Overflow::Visible);
+        if should_clip {
+            items.push(DisplayItem::PushClip {
+                node_id,
+                rect: padding_box,
+            });
+        }
+
+        if element.is_some_and(|element| element.text_input_data().is_some()) {
+            items.push(DisplayItem::Text {
+                node_id,
+                rect: content_box,
+            });
+        } else if node.is_inline_root {
+            let inline_layout = node
+                .raw_dom_data
+                .downcast_element()
+                .and_then(|element| element.inline_layout_data());
+            let inline_boxes: HashSet<usize> = inline_layout
+                .map(|inline_layout| inline_layout.layout.inline_boxes())
+                .unwrap_or_default()
+                .iter()
+                .map(|ibox| ibox.id as usize)
+                .collect();
+
+            self.push_generated_text(items, node_id, PseudoContent::Before, &inline_boxes);
+            items.push(DisplayItem::Text {
+                node_id,
+                rect: content_box,
+            });
+            self.push_generated_text(items, node_id, PseudoContent::After, &inline_boxes);
+        }
+        if !self.is_content_skipped(node_id) {
+            // Inline boxes are positioned relative to the content box of their inline root, and other children
+            // relative to the (scrolled) border box of their parent
+            let child_location = if node.is_inline_root {
+                content_box.origin()
+            } else {
+                border_box.origin() - node.scroll_offset
+            };
+            for child_id in painted_children(node) {
+                self.push_display_items(items, child_id, child_location);
+            }
+        }
+
+        if should_clip {
+            items.push(DisplayItem::PopClip);
+        }
+
+        // Outlines are painted over the element's content, and aren't clipped by it
+        if let Some(outline) = outline(node) {
+            items.push(DisplayItem::Outline {
+                node_id,
+                rect: border_box,
+                outline,
+            });
+        }
+
+        if is_layer {
+            items.push(DisplayItem::PopLayer);
+        }
+    }
+
+    /// Where replaced content of `content_size` (such as an image's natural size) is placed according to the node's
+    /// `object-fit`, in CSS pixels relative to the node's border box. The content is centered within the node's
+    /// content box, and with `cover` (or `none`) it may overflow the content box, in which case it should be
+    /// clipped to it.
+    ///
+    /// TODO: `object-position`
+    pub fn object_fit_rect(&self, node_id: usize, content_size: Size) -> Rect {
+        let node = &self.nodes[node_id];
+        let layout = node.final_layout;
+        let content_box = Rect::new(
+            (layout.border.left + layout.padding.left) as f64,
+            (layout.border.top + layout.padding.top) as f64,
+            (layout.size.width - layout.border.right - layout.padding.right) as f64,
+            (layout.size.height - layout.border.bottom - layout.padding.bottom) as f64,
+        );
+        if content_size.width <= 0.0 || content_size.height <= 0.0 {
+            return content_box;
+        }
+
+        let contain_scale = f64::min(
+            content_box.width() / content_size.width,
+            content_box.height() / content_size.height,
+        );
+        let cover_scale = f64::max(
+            content_box.width() / content_size.width,
+            content_box.height() / content_size.height,
+        );
+        let size = match node.object_fit() {
+            ObjectFit::Fill => return content_box,
+            ObjectFit::Contain => content_size * contain_scale,
+            ObjectFit::Cover => content_size * cover_scale,
+            ObjectFit::None => content_size,
+            ObjectFit::ScaleDown => content_size * contain_scale.min(1.0),
+        };
+        Rect::from_center_size(content_box.center(), size)
+    }
+
+    /// The region which needs to be repainted since the last render (see [`mark_painted`](Self::mark_painted)): the
+    /// bounding rect of the border boxes of every node which changed or moved (both where it was last painted and
+    /// where it is now), and of nodes which are no longer painted. Returns `None` if nothing needs repainting.
+    ///
+    /// Nodes moved by layout as a side effect of other changes (such as the siblings of a node which grew) are
+    /// included, as each painted node's border box is compared with where it was last painted.
+    ///
+    /// This assumes styles are resolved and layout is complete.
+    pub fn dirty_rect(&self) -> Option<Rect> {
+        let order = self.paint_order();
+        let painted: HashSet<usize> = order.iter().copied().collect();
+
+        let mut dirty: Option<Rect> = None;
+        let mut include = |rect: Rect| {
+            dirty = Some(dirty.map_or(rect, |dirty| dirty.union(rect)));
+        };
+        for &node_id in &order {
+            let rect = self.scrolled_border_box(node_id);
+            let old_rect = self.painted_rects.get(&node_id).copied();
+            if old_rect != Some(rect) || self.changed.contains(&node_id) {
+                include(rect);
+                old_rect.map(&mut include);
+            }
+        }
+        // Nodes which were painted last time but no longer are (because they were removed or hidden)
+        for (node_id, &rect) in &self.painted_rects {
+            if !painted.contains(node_id) {
+                include(rect);
+            }
+        }
+        dirty
+    }
+
+    /// Record that the document has been rendered: the current border box of each painted node is remembered
+    /// (for computing [`dirty_rect`](Self::dirty_rect)) and `changed` is cleared.
+    pub fn mark_painted(&mut self) {
+        self.painted_rects = self
+            .paint_order()
+            .into_iter()
+            .map(|node_id| (node_id, self.scrolled_border_box(node_id)))
+            .collect();
+        self.changed.clear();
+    }
+
+    /// The position of a node in the order in which nodes are painted by the display list, or `None` if the node
+    /// isn't painted (e.g. because it or one of its ancestors is hidden). A node is painted over the nodes with
+    /// lower paint indexes.
+    ///
+    /// The paint order is computed by the first call after each layout pass, and reused until the next one.
+    pub fn paint_index(&self, node_id: usize) -> Option<usize> {
+        let paint_indexes = self.paint_indexes.get_or_init(|| {
+            let order = self.paint_order();
+            order
+                .into_iter()
+                .enumerate()
+                .map(|(index, node_id)| (node_id, index))
+                .collect()
+        });
+        paint_indexes.get(&node_id).copied()
+    }
+
+    /// The painted nodes, in paint order
+    fn paint_order(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        if let Some(root) = self.try_root_element() {
+            self.collect_paint_order(root.id, &mut order);
+        }
+        order
+    }
+
+    fn collect_paint_order(&self, node_id: usize, order: &mut Vec<usize>) {
+        let node = &self.nodes[node_id];
+        if !is_painted(node) {
+            return;
+        }
+        order.push(node_id);
+        if self.is_content_skipped(node_id) {
+            return;
+        }
+        for child_id in painted_children(node) {
+            self.collect_paint_order(child_id, order);
+        }
+    }
+
+    /// Push the generated content of a node within an inline context and of its descendants, in tree order.
+    /// Inline boxes have their own inline contexts, so they are skipped.
+    fn push_generated_text(
+        &self,
+        items: &mut Vec<DisplayItem>,
+        node_id: usize,
+        pseudo: PseudoContent,
+        inline_boxes: &HashSet<usize>,
+    ) {
+        let node = &self.nodes[node_id];
+        if let Some(text) = node.generated_content(pseudo) {
+            items.push(DisplayItem::GeneratedText {
+                node_id,
+                pseudo,
+                text: text.to_string(),
+            });
+        }
+        for &child_id in &node.children {
+            if !inline_boxes.contains(&child_id) {
+                self.push_generated_text(items, child_id, pseudo, inline_boxes);
+            }
+        }
+    }
+}
+
+/// Whether a node is painted: only elements (and anonymous blocks) which are displayed, visible, not fully
+/// transparent and not collapsed by their transform (e.g. by `scale(0)`) are painted
+fn is_painted(node: &Node) -> bool {
+    let Some(element) = node.raw_dom_data.downcast_element() else {
+        return false;
+    };
+    if matches!(node.style.display, taffy::Display::None) {
+        return false;
+    }
+
+    // Elements with a `hidden` attribute and hidden inputs are checked here rather than with the style engine for
+    // performance reasons
+    if matches!(element.attr(local_name!("hidden")), Some("true" | ""))
+        || (element.name.local == local_name!("input")
+            && element.attr(local_name!("type")) == Some("hidden"))
+    {
+        return false;
+    }
+    node.primary_styles().is_some_and(|style| {
+        style.get_inherited_box().visibility == Visibility::Visible
+            && style.get_effects().opacity != 0.0
+    }) && node.local_transform().determinant() != 0.0
+}
+
+/// The children of a node in the order they are painted: the inline boxes of an inline root in line order, or
+/// otherwise its layout children in z-index order
+fn painted_children(node: &Node) -> Vec<usize> {
+    if !node.is_inline_root {
+        return paint_order(node);
+    }
+    let Some(inline_layout) = node
+        .raw_dom_data
+        .downcast_element()
+        .and_then(|element| element.inline_layout_data())
+    else {
+        return Vec::new();
+    };
+    inline_layout
+        .layout
+        .lines()
+        .flat_map(|line| {
+            line.items()
+                .filter_map(|item| match item {
+                    PositionedLayoutItem::InlineBox(ibox) => Some(ibox.id as usize),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The layout children of a node sorted by z-index (children with equal z-indexes remain in document order)
+fn paint_order(node: &Node) -> Vec<usize> {
+    let mut children = node.layout_children.borrow().clone().unwrap_or_default();
+    children.sort_by_key(|&child_id| {
+        node.with(child_id)
+            .primary_styles()
+            .map(|style| style.get_position().z_index.integer_or(0))
+            .unwrap_or(0)
+    });
+    children
+}
+
+/// The width (in CSS pixels) and colour of the ring drawn for `outline-style: auto`
+pub const FOCUS_RING_WIDTH: f64 = 2.0;
+pub const FOCUS_RING_COLOR: Color = Color::rgb8(0, 95, 204);
+
+/// An element's outline, resolved from its `outline-*` properties. Unlike a border, an outline is drawn outside the
+/// border box and doesn't take up any space in the layout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    /// Width in CSS pixels
+    pub width: f64,
+    /// Gap between the border box and the outline in CSS pixels. Negative offsets draw the outline inside the border
+    /// box.
+    pub offset: f64,
+    pub color: Color,
+    /// TODO: only solid outlines are drawn, other styles are drawn as solid
+    pub style: BorderStyle,
+}
+
+impl Outline {
+    /// The area enclosed by the outer edge of the outline around a border box
+    pub fn outer_rect(&self, border_box: Rect) -> Rect {
+        let distance = self.offset + self.width;
+        border_box.inflate(distance, distance)
+    }
+}
+
+/// The outline drawn around an element, if any. `outline-style: auto` draws the default focus ring (offset by
+/// `outline-offset`), which the UA stylesheet gives focussed elements. Stylo can't match `:focus-visible`, so that
+/// rule selects `:focus` instead and the ring is left out around elements focussed with a pointer.
+pub fn outline(node: &Node) -> Option<Outline> {
+    let style = node.primary_styles()?;
+    let outline = style.get_outline();
+    let offset = outline.outline_offset.px() as f64;
+    let focus_ring = |offset| Outline {
+        width: FOCUS_RING_WIDTH,
+        offset,
+        color: FOCUS_RING_COLOR,
+        style: BorderStyle::Solid,
+    };
+
+    match outline.outline_style {
+        OutlineStyle::Auto => {
+            (!node.is_focussed() || node.is_focus_visible()).then(|| focus_ring(offset))
+        }
+        OutlineStyle::BorderStyle(BorderStyle::None | BorderStyle::Hidden) => None,
+        OutlineStyle::BorderStyle(border_style) => {
+            let width = outline.outline_width.to_f64_px();
+            (width > 0.0).then(|| Outline {
+                width,
+                offset,
+                color: resolve_color(&style, &outline.outline_color).as_peniko(),
+                style: border_style,
+            })
+        }
+    }
+}
+
+/// A layer of an element's background
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundLayer {
+    /// The background colour, which is always the bottom layer
+    Color(Color),
+    /// A gradient, with its index in the element's `background-image` list
+    Gradient(usize),
+    /// An image referenced by url, with its index in the element's `background-image` list
+    Image(usize),
+}
+
+/// The layers of an element's background in paint order (back to front). CSS lists background images top layer
+/// first, so they are reversed, and the background colour is painted beneath all of them. Transparent colours and
+/// `none` images are omitted.
+pub fn background_layers(style: &ComputedValues) -> Vec<BackgroundLayer> {
+    use style::values::generics::image::GenericImage;
+
+    let background = style.get_background();
+    let mut layers = Vec::new();
+    let color = resolve_color(style, &background.background_color).as_peniko();
+    if color.a > 0 {
+        layers.push(BackgroundLayer::Color(color));
+    }
+    for (index, image) in background.background_image.0.iter().enumerate().rev() {
+        match image {
+            GenericImage::None => {}
+            GenericImage::Gradient(_) => layers.push(BackgroundLayer::Gradient(index)),
+            _ => layers.push(BackgroundLayer::Image(index)),
+        }
+    }
+    layers
+}
+
+/// A resolved background layer. Colours are `[r, g, b, a]` in sRGB, with every channel in the range 0-1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    Solid([f32; 4]),
+    LinearGradient {
+        /// Direction of the gradient line in degrees, where 0 points upwards and angles increase clockwise.
+        /// `to <side>` and `to <corner>` directions are resolved against the element's padding box.
+        angle: f32,
+        stops: Vec<GradientStop>,
+    },
+}
+
+/// A colour stop of a gradient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientStop {
+    pub color: [f32; 4],
+    /// Position along the gradient line, where 0 is the start and 1 is the end. Stops without a position are
+    /// spaced evenly between their neighbours (see [`resolve_stop_positions`]).
+    pub position: Option<f32>,
+    /// Position of the interpolation hint between the previous stop and this one (if any), like `position`
+    pub hint: Option<f32>,
+}
+
+/// The positions of a gradient's colour stops, filling in missing positions as CSS does: the first and last stops
+/// default to the start and end of the gradient line, stops are never positioned before an earlier stop, and runs
+/// of stops without positions are spaced evenly between their neighbours.
+pub fn resolve_stop_positions(stops: &[GradientStop]) -> Vec<f32> {
+    let last = stops.len().saturating_sub(1);
+    let mut positions: Vec<Option<f32>> = stops
+        .iter()
+        .enumerate()
+        .map(|(index, stop)| match stop.position {
+            None if index == 0 => Some(0.0),
+            None if index == last => Some(1.0),
+            position => position,
+        })
+        .collect();
+
+    let mut max = f32::MIN;
+    for position in positions.iter_mut().flatten() {
+        max = max.max(*position);
+        *position = max;
+    }
+
+    // Runs of missing positions lie between two stops with positions, as the first and last stops have them
+    let mut start = 0;
+    while let Some(run_start) = (start..positions.len()).find(|&index| positions[index].is_none()) {
+        let run_end = (run_start..positions.len())
+            .find(|&index| positions[index].is_some())
+            .unwrap_or(last);
+        let before = positions[run_start - 1].unwrap_or(0.0);
+        let after = positions[run_end].unwrap_or(1.0);
+        let step = (after - before) / (run_end - run_start + 1) as f32;
+        for (offset, position) in positions[run_start..run_end].iter_mut().enumerate() {
+            *position = Some(before + step * (offset + 1) as f32);
+        }
+        start = run_end;
+    }
+
+    positions.into_iter().flatten().collect()
+}
+
+/// The solid and linear gradient layers of an element's background in paint order (back to front), like
+/// [`background_layers`]. Other kinds of image are skipped.
+pub fn backgrounds(node: &Node) -> Vec<Background> {
+    let Some(style) = node.primary_styles() else {
+        return Vec::new();
+    };
+    background_layers(&style)
+        .into_iter()
+        .filter_map(|layer| match layer {
+            BackgroundLayer::Color(_) => Some(Background::Solid(
+                *resolve_color(&style, &style.get_background().background_color)
+                    .to_color_space(style::color::ColorSpace::Srgb)
+                    .raw_components(),
+            )),
+            BackgroundLayer::Gradient(index) => background_image(node, index),
+            BackgroundLayer::Image(_) => None,
+        })
+        .collect()
+}
+
+/// Resolve the `index`th image of an element's `background-image` list (see [`BackgroundLayer`]). Only linear
+/// gradients are resolved: other kinds of image give `None`.
+pub fn background_image(node: &Node, index: usize) -> Option<Background> {
+    use style::values::computed::LineDirection;
+    use style::values::generics::image::{GenericGradient, GenericGradientItem, GenericImage};
+    use style::values::specified::position::{HorizontalPositionKeyword, VerticalPositionKeyword};
+
+    let style = node.primary_styles()?;
+    let rgba = |color: &style::values::computed::Color| {
+        *resolve_color(&style, color)
+            .to_color_space(style::color::ColorSpace::Srgb)
+            .raw_components()
+    };
+    let layout = &node.final_layout;
+    let width = layout.size.width - layout.border.left - layout.border.right;
+    let height = layout.size.height - layout.border.top - layout.border.bottom;
+
+    let GenericImage::Gradient(gradient) = style.get_background().background_image.0.get(index)?
+    else {
+        return None;
+    };
+    let GenericGradient::Linear {
+        direction, items, ..
+    } = &**gradient
+    else {
+        return None;
+    };
+
+    let angle = match direction {
+        LineDirection::Angle(angle) => angle.degrees(),
+        LineDirection::Horizontal(HorizontalPositionKeyword::Right) => 90.0,
+        LineDirection::Horizontal(HorizontalPositionKeyword::Left) => 270.0,
+        LineDirection::Vertical(VerticalPositionKeyword::Top) => 0.0,
+        LineDirection::Vertical(VerticalPositionKeyword::Bottom) => 180.0,
+        // The gradient line for corners is perpendicular to the diagonal joining the two neighbouring corners
+        LineDirection::Corner(horizontal, vertical) => {
+            let x = match horizontal {
+                HorizontalPositionKeyword::Right => height,
+                HorizontalPositionKeyword::Left => -height,
+            };
+            let y = match vertical {
+                VerticalPositionKeyword::Top => width,
+                VerticalPositionKeyword::Bottom => -width,
+            };
+            x.atan2(y).to_degrees().rem_euclid(360.0)
+        }
+    };
+
+    // Length stop and hint positions are resolved against the length of the gradient line
+    let radians = angle.to_radians();
+    let line_length = Length::new((width * radians.sin()).abs() + (height * radians.cos()).abs());
+    let resolve_position = |position: &style::values::computed::LengthPercentage| {
+        (line_length.px() > 0.0).then(|| position.resolve(line_length).px() / line_length.px())
+    };
+
+    let mut stops = Vec::new();
+    let mut hint = None;
+    for item in items.iter() {
+        let (color, position) = match item {
+            GenericGradientItem::SimpleColorStop(color) => (rgba(color), None),
+            GenericGradientItem::ComplexColorStop { color, position } => {
+                (rgba(color), resolve_position(position))
+            }
+            GenericGradientItem::InterpolationHint(position) => {
+                hint = resolve_position(position);
+                continue;
+            }
+        };
+        stops.push(GradientStop {
+            color,
+            position,
+            hint: hint.take(),
+        });
+    }
+
+    Some(Background::LinearGradient { angle, stops })
+}
+
+/// One shadow from an element's `box-shadow` list, in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoxShadow {
+    pub offset: Vec2,
+    /// The CSS blur radius, which is twice the standard deviation of the gaussian blur
+    pub blur: f64,
+    /// Distance the shadow is grown by (or shrunk by, if negative) before it is blurred
+    pub spread: f64,
+    pub color: Color,
+    /// Inset shadows are drawn inside the padding box, above the background
+    pub inset: bool,
+}
+
+/// The shadows in an element's `box-shadow` list in paint order (back to front). CSS lists the top shadow first, so
+/// they are reversed. Transparent shadows are omitted.
+pub fn box_shadows(style: &ComputedValues) -> Vec<BoxShadow> {
+    style
+        .get_effects()
+        .box_shadow
+        .0
+        .iter()
+        .rev()
+        .map(|shadow| BoxShadow {
+            offset: Vec2::new(
+                shadow.base.horizontal.px() as f64,
+                shadow.base.vertical.px() as f64,
+            ),
+            blur: shadow.base.blur.0.px() as f64,
+            spread: shadow.spread.px() as f64,
+            color: resolve_color(style, &shadow.base.color).as_peniko(),
+            inset: shadow.inset,
+        })
+        .filter(|shadow| shadow.color.a > 0)
+        .collect()
+}
+
+/// A resolved `filter` function. Amounts are multipliers, where 1 leaves the element unchanged (except for
+/// `Grayscale`, `Invert` and `Sepia`, where 0 leaves it unchanged).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterEffect {
+    /// Gaussian blur with a standard deviation in CSS pixels
+    Blur(f32),
+    Brightness(f32),
+    Contrast(f32),
+    Grayscale(f32),
+    /// Hue rotation in degrees
+    HueRotate(f32),
+    Invert(f32),
+    Opacity(f32),
+    Saturate(f32),
+    Sepia(f32),
+}
+
+/// The filter effects applied to an element, in the order they are applied. Functions which aren't supported
+/// (`drop-shadow()` and `url()` filters) are skipped.
+pub fn filter_effects(style: &ComputedValues) -> Vec<FilterEffect> {
+    use style::values::generics::effects::GenericFilter;
+
+    style
+        .get_effects()
+        .filter
+        .0
+        .iter()
+        .filter_map(|filter| match filter {
+            GenericFilter::Blur(radius) => Some(FilterEffect::Blur(radius.0.px())),
+            GenericFilter::Brightness(amount) => Some(FilterEffect::Brightness(amount.0)),
+            GenericFilter::Contrast(amount) => Some(FilterEffect::Contrast(amount.0)),
+            GenericFilter::Grayscale(amount) => Some(FilterEffect::Grayscale(amount.0)),
+            GenericFilter::HueRotate(angle) => Some(FilterEffect::HueRotate(angle.degrees())),
+            GenericFilter::Invert(amount) => Some(FilterEffect::Invert(amount.0)),
+            GenericFilter::Opacity(amount) => Some(FilterEffect::Opacity(amount.0)),
+            GenericFilter::Saturate(amount) => Some(FilterEffect::Saturate(amount.0)),
+            GenericFilter::Sepia(amount) => Some(FilterEffect::Sepia(amount.0)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn display_list_contains_backgrounds_and_clips_in_paint_order() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="outer" style="overflow: hidden; width: 100px; height: 100px; background-color: red">
+            <div id="front" style="position: relative; z-index: 1; width: 10px; height: 10px; background-color: blue"></div>
+            <div id="back" style="width: 10px; height: 10px; border: 2px solid green"></div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let items: Vec<(&str, usize)> = doc
+        .build_display_list()
+        .iter()
+        .filter_map(|item| match *item {
+            DisplayItem::PushClip { node_id, .. } => Some(("push_clip", node_id)),
+            DisplayItem::PopClip => Some(("pop_clip", 0)),
+            DisplayItem::Rect { node_id, .. } => Some(("rect", node_id)),
+            DisplayItem::BackgroundImage { node_id, .. } => Some(("image", node_id)),
+            DisplayItem::Border { node_id, .. } => Some(("border", node_id)),
+            DisplayItem::PushLayer { .. }
+            | DisplayItem::PopLayer
+            | DisplayItem::BoxShadow { .. }
+            | DisplayItem::Element { .. }
+            | DisplayItem::Replaced { .. }
+            | DisplayItem::Text { .. }
+            | DisplayItem::GeneratedText { .. }
+            | DisplayItem::Outline { .. } => None,
+        })
+        .collect();
+
+    assert_eq!(
+        items,
+        vec![
+            ("rect", by_id(&doc, "outer")),
+            ("push_clip", by_id(&doc, "outer")),
+            ("border", by_id(&doc, "back")),
+            ("rect", by_id(&doc, "front")),
+            ("pop_clip", 0),
+        ]
+    );
+}
+
+#[test]
+fn clip_stack_lists_clipping_ancestors_outermost_first() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="outer" style="overflow: hidden; width: 200px; height: 200px; border: 2px solid black; border-radius: 10px">
+            <div style="padding: 10px">
+                <div id="inner" style="overflow: hidden; width: 50px; height: 50px">
+                    <div id="target" style="height: 100px"></div>
+                </div>
+            </div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    assert_eq!(
+        doc.clip_stack(by_id(&doc, "target")),
+        vec![
+            ClipRect {
+                node_id: by_id(&doc, "outer"),
+                rect: Rect::new(2.0, 2.0, 202.0, 202.0),
+                radii: RoundedRectRadii::from_single_radius(8.0),
+            },
+            ClipRect {
+                node_id: by_id(&doc, "inner"),
+                rect: Rect::new(12.0, 12.0, 62.0, 62.0),
+                radii: RoundedRectRadii::from_single_radius(0.0),
+            },
+        ]
+    );
+    assert!(doc.clip_stack(by_id(&doc, "outer")).is_empty());
+}
+
+#[test]
+fn current_color_resolves_to_inherited_text_color() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="color: red">
+        <div id="target" style="border: 2px solid; border-color: currentColor"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let target = by_id(&doc, "target");
+
+    let colors = doc
+        .build_display_list()
+        .into_iter()
+        .find_map(|item| match item {
+            DisplayItem::Border {
+                node_id, colors, ..
+            } if node_id == target => Some(colors),
+            _ => None,
+        })
+        .expect("target should have a border");
+    assert_eq!(colors, [Color::rgb8(255, 0, 0); 4]);
+}
+
+#[test]
+fn generated_content_is_listed_around_the_text_in_paint_order() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <p id="link" before-content="→ " after-content=" ←">Next <span id="icon" before-content="★"></span></p>
+        <div id="empty" before-content="•"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let items: Vec<(&str, usize, String)> = doc
+        .build_display_list()
+        .into_iter()
+        .filter_map(|item| match item {
+            // Skip the anonymous blocks wrapping whitespace between the elements
+            DisplayItem::Text { node_id, .. }
+                if [by_id(&doc, "link"), by_id(&doc, "empty")].contains(&node_id) =>
+            {
+                Some(("text", node_id, String::new()))
+            }
+            DisplayItem::GeneratedText {
+                node_id,
+                pseudo,
+                text,
+            } => {
+                let kind = match pseudo {
+                    PseudoContent::Before => "before",
+                    PseudoContent::After => "after",
+                };
+                Some((kind, node_id, text))
+            }
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        items,
+        vec![
+            ("before", by_id(&doc, "link"), "→ ".to_string()),
+            ("before", by_id(&doc, "icon"), "★".to_string()),
+            ("text", by_id(&doc, "link"), String::new()),
+            ("after", by_id(&doc, "link"), " ←".to_string()),
+            ("before", by_id(&doc, "empty"), "•".to_string()),
+            ("text", by_id(&doc, "empty"), String::new()),
+        ]
+    );
+
+    // The generated content is laid out but not added to the tree
+    let text = |name: &str| {
+        doc.nodes[by_id(&doc, name)]
+            .element_data()
+            .and_then(|element| element.inline_layout_data())
+            .map(|layout| layout.text.clone())
+            .unwrap()
+    };
+    assert!(text("link").starts_with("→ Next"));
+    assert!(text("link").ends_with(" ←"));
+    assert_eq!(text("empty"), "•");
+    assert_eq!(doc.nodes[by_id(&doc, "empty")].children.len(), 0);
+}
+
+#[test]
+fn nodes_scrolled_out_of_their_container_are_not_in_the_viewport() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="list" style="overflow: auto; width: 100px; height: 100px">
+            <div id="first" style="height: 100px"></div>
+            <div id="second" style="height: 100px"></div>
+            <div id="third" style="height: 100px"></div>
+        </div>
+        <div id="below" style="margin-top: 1000px; height: 10px"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let viewport = Rect::new(0.0, 0.0, 800.0, 600.0);
+
+    assert!(doc.is_in_viewport(by_id(&doc, "first"), viewport));
+    // The third item is within the viewport, but clipped by the list
+    assert!(!doc.is_in_viewport(by_id(&doc, "third"), viewport));
+    assert!(!doc.is_in_viewport(by_id(&doc, "below"), viewport));
+    assert!(doc.is_in_viewport(by_id(&doc, "below"), viewport + Vec2::new(0.0, 1000.0)));
+
+    // Scrolling the list by one and a half items hides the first and half of the second
+    doc.nodes[by_id(&doc, "list")].scroll_offset = Vec2::new(0.0, 150.0);
+    assert!(!doc.is_in_viewport(by_id(&doc, "first"), viewport));
+    assert!(doc.is_in_viewport(by_id(&doc, "second"), viewport));
+    assert!(doc.is_in_viewport(by_id(&doc, "third"), viewport));
+}
+
+#[test]
+fn background_layers_are_painted_back_to_front() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="two" style="height: 10px; background: linear-gradient(red, blue), rgb(0, 128, 0)"></div>
+        <div id="three" style="height: 10px; background-image: url(top.png), linear-gradient(red, blue)"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let layers =
+        |name: &str| background_layers(&doc.nodes[by_id(&doc, name)].primary_styles().unwrap());
+
+    // The colour is the bottom layer, beneath the gradient
+    assert_eq!(
+        layers("two"),
+        vec![
+            BackgroundLayer::Color(Color::rgb8(0, 128, 0)),
+            BackgroundLayer::Gradient(0)
+        ]
+    );
+    // The first listed image is the top layer, and a transparent background colour isn't a layer
+    assert_eq!(
+        layers("three"),
+        vec![BackgroundLayer::Gradient(1), BackgroundLayer::Image(0)]
+    );
+
+    let items: Vec<DisplayItem> =
+        doc.build_display_list()
+            .into_iter()
+            .filter(|item| match item {
+                DisplayItem::Rect { node_id, .. }
+                | DisplayItem::BackgroundImage { node_id, .. } => *node_id == by_id(&doc, "two"),
+                _ => false,
+            })
+            .collect();
+    assert!(matches!(
+        items.as_slice(),
+        [
+            DisplayItem::Rect { .. },
+            DisplayItem::BackgroundImage { index: 0, .. }
+        ]
+    ));
+}
+
+#[test]
+fn paint_indexes_follow_z_index_order() {
+    use crate::testing::{by_id, parse};
+    use html5ever::{ns, QualName};
+    use style::invalidation::element::restyle_hints::RestyleHint;
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="top" style="position: relative; z-index: 2; height: 10px; background-color: red"></div>
+        <div id="middle" style="position: relative; z-index: 1; height: 10px; background-color: green"></div>
+        <div id="bottom" style="height: 10px; background-color: blue"></div>
+        <div id="hidden" style="visibility: hidden; height: 10px"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let index = |name: &str| doc.paint_index(by_id(&doc, name));
+
+    assert_eq!(doc.paint_index(doc.root_element().id), Some(0));
+    // Siblings are painted in z-index order, whatever their order in the document
+    assert!(index("bottom").unwrap() < index("middle").unwrap());
+    assert!(index("middle").unwrap() < index("top").unwrap());
+    assert_eq!(index("hidden"), None);
+
+    // Paint indexes match the order in which the backgrounds are drawn
+    let drawn: Vec<usize> = doc
+        .build_display_list()
+        .into_iter()
+        .filter_map(|item| match item {
+            DisplayItem::Rect { node_id, .. } => Some(node_id),
+            _ => None,
+        })
+        .collect();
+    let mut by_index = drawn.clone();
+    by_index.sort_by_key(|&node_id| doc.paint_index(node_id));
+    assert_eq!(drawn, by_index);
+    assert_eq!(drawn.len(), 3);
+
+    // The cached paint order is recomputed after the next layout pass
+    let bottom = by_id(&doc, "bottom");
+    doc.snapshot_node_and(bottom, |node| {
+        let element = node.raw_dom_data.downcast_element_mut().unwrap();
+        element.set_attribute(
+            QualName::new(None, ns!(), local_name!("style")),
+            "position: relative; z-index: 3; height: 10px".to_string(),
+        );
+        node.flush_style_attribute();
+        node.set_restyle_hint(RestyleHint::restyle_subtree());
+    });
+    doc.resolve();
+    assert!(doc.paint_index(bottom) > doc.paint_index(by_id(&doc, "top")));
+}
+
+#[test]
+fn filter_functions_are_resolved_in_order() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="overlay" style="filter: blur(4px) brightness(0.5)"></div>
+        <div id="shadowed" style="filter: drop-shadow(2px 2px 2px black) grayscale(1) hue-rotate(90deg)"></div>
+        <div id="none"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let effects =
+        |name: &str| filter_effects(&doc.nodes[by_id(&doc, name)].primary_styles().unwrap());
+
+    assert_eq!(
+        effects("overlay"),
+        vec![FilterEffect::Blur(4.0), FilterEffect::Brightness(0.5)]
+    );
+    // Unsupported functions are skipped
+    assert_eq!(
+        effects("shadowed"),
+        vec![FilterEffect::Grayscale(1.0), FilterEffect::HueRotate(90.0)]
+    );
+    assert!(effects("none").is_empty());
+}
+
+#[test]
+fn outlines_are_drawn_outside_the_border_box_without_affecting_layout() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="outlined" style="width: 50px; height: 20px; outline: 3px solid red; outline-offset: 2px"></div>
+        <div id="plain" style="width: 50px; height: 20px"></div>
+        <button id="focussed" style="width: 50px; height: 20px">Ok</button>
+        <button id="unringed" style="width: 50px; height: 20px; outline: none">Ok</button>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let outlined = &doc.nodes[by_id(&doc, "outlined")];
+    assert_eq!(
+        outline(outlined),
+        Some(Outline {
+            width: 3.0,
+            offset: 2.0,
+            color: Color::rgb8(255, 0, 0),
+            style: BorderStyle::Solid,
+        })
+    );
+    assert_eq!(outlined.final_layout.size.width, 50.0);
+    assert_eq!(outlined.final_layout.size.height, 20.0);
+    assert_eq!(outlined.final_layout.border.top, 0.0);
+    // The next element is laid out directly after the border box
+    assert_eq!(
+        doc.nodes[by_id(&doc, "plain")].final_layout.location.y,
+        20.0
+    );
+    assert_eq!(
+        outline(outlined)
+            .unwrap()
+            .outer_rect(Rect::new(0.0, 0.0, 50.0, 20.0)),
+        Rect::new(-5.0, -5.0, 55.0, 25.0)
+    );
+    assert_eq!(outline(&doc.nodes[by_id(&doc, "plain")]), None);
+    assert!(doc.build_display_list().contains(&DisplayItem::Outline {
+        node_id: by_id(&doc, "outlined"),
+        rect: Rect::new(0.0, 0.0, 50.0, 20.0),
+        outline: outline(outlined).unwrap(),
+    }));
+
+    // The UA stylesheet's focus outline draws the default focus ring around elements focussed with the keyboard,
+    // but not with a pointer, and can be removed with `outline: none`
+    doc.add_stylesheet(":focus { outline: auto }");
+    assert_eq!(outline(&doc.nodes[by_id(&doc, "focussed")]), None);
+    doc.nodes[by_id(&doc, "focussed")].focus(true);
+    doc.resolve();
+    let ring = outline(&doc.nodes[by_id(&doc, "focussed")]).unwrap();
+    assert_eq!(ring.color, FOCUS_RING_COLOR);
+    assert_eq!(ring.width, FOCUS_RING_WIDTH);
+    doc.nodes[by_id(&doc, "focussed")].focus(false);
+    doc.resolve();
+    assert_eq!(outline(&doc.nodes[by_id(&doc, "focussed")]), None);
+    doc.nodes[by_id(&doc, "unringed")].focus(true);
+    doc.resolve();
+    assert_eq!(outline(&doc.nodes[by_id(&doc, "unringed")]), None);
+}
+
+#[test]
+fn dirty_rect_covers_old_and_new_positions_of_moved_nodes() {
+    use crate::testing::{by_id, parse};
+
+    // The body has a fixed height, so that it doesn't grow along with its content
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0; height: 100px">
+        <div id="box" style="width: 50px; height: 20px"></div>
+        <div id="other" style="width: 50px; height: 20px"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    doc.mark_painted();
+    assert_eq!(doc.dirty_rect(), None);
+
+    let box_id = by_id(&doc, "box");
+    doc.set_style_attribute(box_id, "width: 50px; height: 20px; margin-left: 100px");
+    doc.resolve();
+    let dirty = doc.dirty_rect().unwrap();
+    for rect in [
+        Rect::new(0.0, 0.0, 50.0, 20.0),
+        Rect::new(100.0, 0.0, 150.0, 20.0),
+    ] {
+        assert_eq!(dirty.union(rect), dirty);
+    }
+    // The unchanged node isn't repainted
+    let other = doc.scrolled_border_box(by_id(&doc, "other"));
+    assert!(dirty.y1 <= other.y0);
+
+    doc.mark_painted();
+    assert_eq!(doc.dirty_rect(), None);
+
+    // Growing the first node moves its sibling down, so both of the sibling's positions are repainted too
+    doc.set_style_attribute(box_id, "width: 50px; height: 40px; margin-left: 100px");
+    doc.resolve();
+    assert_eq!(doc.dirty_rect(), Some(Rect::new(0.0, 0.0, 150.0, 60.0)));
+}
+
+#[test]
+fn object_fit_places_content_within_the_content_box() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="contain" style="width: 100px; height: 200px; object-fit: contain"></div>
+        <div id="cover" style="width: 100px; height: 200px; object-fit: cover"></div>
+        <div id="padded" style="width: 100px; height: 200px; padding: 10px; object-fit: contain"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let fit = |name: &str| doc.object_fit_rect(by_id(&doc, name), Size::new(400.0, 200.0));
+
+    // A wide image is letterboxed in a tall box
+    assert_eq!(fit("contain"), Rect::new(0.0, 75.0, 100.0, 125.0));
+    assert_eq!(fit("padded"), Rect::new(10.0, 85.0, 110.0, 135.0));
+    // ...or cropped at its sides to fill it
+    assert_eq!(fit("cover"), Rect::new(-150.0, 0.0, 250.0, 200.0));
+}
+
+#[test]
+fn box_shadow_lists_are_resolved_back_to_front() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="color: blue">
+        <div id="single" style="box-shadow: 2px 4px 6px 1px red"></div>
+        <div id="list" style="box-shadow: inset 0 0 4px currentColor, -3px 0 black, 1px 1px transparent"></div>
+        <div id="none"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let shadows = |name: &str| box_shadows(&doc.nodes[by_id(&doc, name)].primary_styles().unwrap());
+
+    assert_eq!(
+        shadows("single"),
+        vec![BoxShadow {
+            offset: Vec2::new(2.0, 4.0),
+            blur: 6.0,
+            spread: 1.0,
+            color: Color::rgb8(255, 0, 0),
+            inset: false,
+        }]
+    );
+    // The first shadow in the list is painted last, and transparent shadows are skipped
+    assert_eq!(
+        shadows("list"),
+        vec![
+            BoxShadow {
+                offset: Vec2::new(-3.0, 0.0),
+                blur: 0.0,
+                spread: 0.0,
+                color: Color::rgb8(0, 0, 0),
+                inset: false,
+            },
+            BoxShadow {
+                offset: Vec2::ZERO,
+                blur: 4.0,
+                spread: 0.0,
+                color: Color::rgb8(0, 0, 255),
+                inset: true,
+            },
+        ]
+    );
+    assert!(shadows("none").is_empty());
+}
+
+#[test]
+fn linear_gradient_backgrounds_resolve_their_angle_and_stops() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="sides" style="background: linear-gradient(to right, red, blue)"></div>
+        <div id="angled" style="width: 100px; height: 100px; background: lime linear-gradient(45deg, red 25%, rgb(0 0 255 / 0.5) 50px)"></div>
+        <div id="solid" style="background-color: red"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let backgrounds = |name: &str| backgrounds(&doc.nodes[by_id(&doc, name)]);
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    assert_eq!(
+        backgrounds("sides"),
+        vec![Background::LinearGradient {
+            angle: 90.0,
+            stops: vec![
+                GradientStop {
+                    color: RED,
+                    position: None,
+                    hint: None,
+                },
+                GradientStop {
+                    color: [0.0, 0.0, 1.0, 1.0],
+                    position: None,
+                    hint: None,
+                },
+            ],
+        }]
+    );
+
+    // The colour is painted beneath the gradient
+    let angled = backgrounds("angled");
+    assert_eq!(angled[0], Background::Solid([0.0, 1.0, 0.0, 1.0]));
+    let Background::LinearGradient { angle, stops } = &angled[1] else {
+        panic!("expected a linear gradient, got {:?}", angled[1]);
+    };
+    assert_eq!(*angle, 45.0);
+    assert_eq!(stops[0].color, RED);
+    assert_eq!(stops[0].position, Some(0.25));
+    assert_eq!(stops[1].color, [0.0, 0.0, 1.0, 0.5]);
+    // The gradient line of a 100px square at 45deg is 100 * sqrt(2) long
+    let position = stops[1].position.unwrap();
+    assert!((position - 50.0 / (100.0 * 2f32.sqrt())).abs() < 1e-4);
+
+    assert_eq!(backgrounds("solid"), vec![Background::Solid(RED)]);
+}
+
+#[test]
+fn gradient_stop_positions_are_filled_in_like_css() {
+    let stops = |positions: &[Option<f32>]| {
+        let stops: Vec<GradientStop> = positions
+            .iter()
+            .map(|&position| GradientStop {
+                color: [0.0, 0.0, 0.0, 1.0],
+                position,
+                hint: None,
+            })
+            .collect();
+        resolve_stop_positions(&stops)
+    };
+
+    assert_eq!(stops(&[None, None]), vec![0.0, 1.0]);
+    assert_eq!(
+        stops(&[None, None, None, None, None]),
+        vec![0.0, 0.25, 0.5, 0.75, 1.0]
+    );
+    assert_eq!(
+        stops(&[Some(0.25), None, Some(0.75), None]),
+        vec![0.25, 0.5, 0.75, 1.0]
+    );
+    // Stops can't be positioned before an earlier stop
+    assert_eq!(stops(&[Some(0.5), Some(0.25), None]), vec![0.5, 0.5, 1.0]);
+    assert_eq!(stops(&[]), Vec::<f32>::new());
+}
+
+#[test]
+fn transformed_and_translucent_elements_are_painted_into_layers() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="moved" style="transform: translate(10px, 20px); height: 10px; background-color: red"></div>
+        <div id="faded" style="opacity: 0.5; height: 10px; background-color: blue">
+            <div id="inner" style="height: 5px; background-color: green"></div>
+        </div>
+        <div id="collapsed" style="transform: scale(0); height: 10px; background-color: red"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let items: Vec<DisplayItem> = doc
+        .build_display_list()
+        .into_iter()
+        .filter(|item| {
+            matches!(
+                item,
+                DisplayItem::PushLayer { .. } | DisplayItem::PopLayer | DisplayItem::Rect { .. }
+            )
+        })
+        .collect();
+
+    let rect = |name: &str, y: f64, height: f64, color: Color| DisplayItem::Rect {
+        node_id: by_id(&doc, name),
+        rect: Rect::new(0.0, y, 800.0, y + height),
+        color,
+    };
+    assert_eq!(
+        items,
+        vec![
+            DisplayItem::PushLayer {
+                node_id: by_id(&doc, "moved"),
+                transform: Affine::translate((10.0, 20.0)),
+                opacity: 1.0,
+            },
+            rect("moved", 0.0, 10.0, Color::rgb8(255, 0, 0)),
+            DisplayItem::PopLayer,
+            DisplayItem::PushLayer {
+                node_id: by_id(&doc, "faded"),
+                transform: Affine::IDENTITY,
+                opacity: 0.5,
+            },
+            rect("faded", 10.0, 10.0, Color::rgb8(0, 0, 255)),
+            rect("inner", 10.0, 5.0, Color::rgb8(0, 128, 0)),
+            DisplayItem::PopLayer,
+        ]
+    );
+}
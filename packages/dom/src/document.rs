@@ -1,20 +1,27 @@
-use crate::events::{EventData, HitResult, RendererEvent};
+use crate::events::{DispatchedEvent, EventData, HitResult, KeyRepeatState, RendererEvent};
+use crate::layout::IntrinsicSizes;
 use crate::node::TextBrush;
-use crate::{Node, NodeData, TextNodeData, Viewport};
+use crate::{ElementNodeData, Node, NodeData, TextNodeData, Viewport};
 use app_units::Au;
+use html5ever::{local_name, namespace_url, ns, LocalName, QualName};
 use peniko::kurbo;
 // use quadtree_rs::Quadtree;
 use parley::editor::{PointerButton, TextEvent};
 use selectors::{matching::QuirksMode, Element};
 use slab::Slab;
 use std::any::Any;
+use std::cell::OnceCell;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use style::animation::{AnimationSetKey, AnimationState, DocumentAnimationSet};
+use style::invalidation::element::restyle_hints::RestyleHint;
 use style::selector_parser::ServoElementSnapshot;
 use style::servo::media_queries::FontMetricsProvider;
 use style::servo_arc::Arc as ServoArc;
 use style::values::computed::ui::CursorKind;
 use style::{
-    dom::{TDocument, TNode},
+    dom::{OpaqueNode, TDocument, TNode},
     media_queries::{Device, MediaList},
     selector_parser::SnapshotMap,
     shared_lock::{SharedRwLock, StylesheetGuards},
@@ -22,7 +29,14 @@ use style::{
     stylist::Stylist,
 };
 use taffy::AvailableSpace;
+use unicode_segmentation::UnicodeSegmentation;
 use url::Url;
+use winit::event::Ime;
+use winit::keyboard::KeyCode;
+
+/// The maximum number of times `flush_layout` lays out again after scrollbars or content relevance change, so that
+/// layouts which never settle (e.g. content which only overflows without a scrollbar) still terminate
+const MAX_EXTRA_LAYOUT_PASSES: usize = 4;
 
 // TODO: implement a proper font metrics provider
 #[derive(Debug, Clone)]
@@ -84,6 +98,11 @@ pub struct Document {
     // caching for the stylist
     pub(crate) snapshots: SnapshotMap,
 
+    /// CSS transitions which are in flight, keyed by node. These are created and retargeted by stylo during restyles.
+    pub(crate) animations: DocumentAnimationSet,
+    /// The time (in seconds) at which transitions are sampled (see `set_animation_time`)
+    pub(crate) animation_time: f64,
+
     pub(crate) nodes_to_id: HashMap<String, usize>,
 
     /// Base url for resolving linked resources (stylesheets, images, fonts, etc)
@@ -94,6 +113,8 @@ pub struct Document {
 
     // Viewport details such as the dimensions, HiDPI scale, and zoom factor,
     pub(crate) viewport: Viewport,
+    /// A resize requested by `resize_viewport` which will be applied by the next `resolve`
+    pub(crate) pending_viewport: Option<Viewport>,
 
     pub(crate) stylesheets: HashMap<String, DocumentStyleSheet>,
 
@@ -106,6 +127,52 @@ pub struct Document {
     pub(crate) hover_node_id: Option<usize>,
     /// The node which is currently focussed (if any)
     pub(crate) focus_node_id: Option<usize>,
+    /// The root of the subtree to which tab navigation is confined (if any)
+    pub(crate) focus_trap_root: Option<usize>,
+    /// Whether focus was last moved with the keyboard (rather than by clicking), see `focus_visible`
+    pub(crate) focus_visible: bool,
+    /// The most recently inserted node with an `autofocus` attribute which has not yet been focussed
+    pub(crate) autofocus_request: Option<usize>,
+    /// The selected text (if any)
+    pub(crate) text_selection: Option<TextSelection>,
+    /// Text cut or copied from a text input which has not yet been written to the system clipboard
+    pub(crate) clipboard_text: Option<String>,
+
+    /// Wheel deltas which have been queued but not yet applied (see `queue_scroll`)
+    pub(crate) pending_scroll: Option<PendingScroll>,
+
+    /// Keys which are currently held, for generating auto-repeat events
+    pub(crate) key_repeat: KeyRepeatState,
+
+    /// Arbitrary data attached to nodes by integrations (see `set_user_data`)
+    pub(crate) user_data: HashMap<usize, Box<dyn Any + Send>>,
+
+    /// Names of user-defined events (such as "longpress") which can be dispatched with `dispatch_synthetic`
+    pub(crate) synthetic_events: HashSet<String>,
+
+    /// The original child counts of nodes whose children have changed since `take_child_count_changes` was last called
+    pub(crate) child_count_changes: HashMap<usize, usize>,
+
+    /// Whether layout is out of date with respect to styles
+    pub(crate) layout_dirty: bool,
+    /// Nodes whose own layout inputs (content, children or styles) have changed since the last layout pass
+    pub(crate) layout_invalidations: HashSet<usize>,
+    /// Nodes which were laid out (rather than reusing a cached layout) in the last layout pass
+    pub(crate) relayout_nodes: HashSet<usize>,
+    /// Elements with `content-visibility: auto` which were in the viewport as of the last layout pass, so their
+    /// content is laid out and painted
+    pub(crate) relevant_content: HashSet<usize>,
+    /// The border boxes of the painted nodes as of the last render (see `mark_painted`)
+    pub(crate) painted_rects: HashMap<usize, kurbo::Rect>,
+    /// The position of each painted node in paint order, computed on demand after each layout pass (see
+    /// `paint_index`)
+    pub(crate) paint_indexes: OnceCell<HashMap<usize, usize>>,
+    /// The thickness (in CSS pixels) of the space reserved for scrollbars
+    pub(crate) scrollbar_width: f32,
+    /// Whether `resolve` should defer relayout until `flush_layout` is called
+    pub(crate) defer_layout: bool,
+    /// Whether `resolve` is suspended entirely (see `pause_reactivity`)
+    pub(crate) reactivity_paused: bool,
 
     // TODO: move to nodes
     pub scroll_offset: f64,
@@ -128,17 +195,19 @@ impl DocumentLike for Document {
                         .raw_dom_data
                         .downcast_element_mut()
                         .and_then(|el| el.text_input_data_mut());
-                    if text_input_data.is_some() {
-                        let x = hit.x as f64 * self.viewport.scale_f64();
+                    if let Some(input_data) = text_input_data {
+                        let x = hit.x as f64 * self.viewport.scale_f64()
+                            + input_data.scroll_offset as f64;
                         let y = hit.y as f64 * self.viewport.scale_f64();
-                        text_input_data.unwrap().editor.pointer_down(
+                        input_data.editor.pointer_down(
                             kurbo::Point { x, y },
                             mods,
                             PointerButton::Primary,
                         );
                         println!("Clicked {}", hit.node_id);
 
-                        self.set_focus_to(hit.node_id);
+                        self.scroll_text_input_to_cursor(hit.node_id);
+                        self.focus_by_pointer(hit.node_id);
                     }
                 }
             }
@@ -154,6 +223,7 @@ impl DocumentLike for Document {
                         input_data.editor.text_event(&text_event);
                         println!("Sent text event to {}", node_id);
                     }
+                    self.scroll_text_input_to_cursor(node_id);
                 }
             }
             EventData::Ime(ime_event) => {
@@ -168,6 +238,20 @@ impl DocumentLike for Document {
                         input_data.editor.text_event(&text_event);
                         println!("Sent ime event to {}", node_id);
                     }
+                    self.scroll_text_input_to_cursor(node_id);
+                }
+            }
+            EventData::Paste { text } => {
+                self.paste_text(&text);
+            }
+            EventData::Cut => {
+                if let Some(text) = self.cut_selection() {
+                    self.clipboard_text = Some(text);
+                }
+            }
+            EventData::Copy => {
+                if let Some(text) = self.copy_selection() {
+                    self.clipboard_text = Some(text);
                 }
             }
             EventData::Hover => {}
@@ -197,8 +281,11 @@ impl Document {
             nodes,
             stylist,
             snapshots,
+            animations: DocumentAnimationSet::default(),
+            animation_time: 0.0,
             nodes_to_id,
             viewport,
+            pending_viewport: None,
             base_url: None,
             // quadtree: Quadtree::new(20),
             stylesheets: HashMap::new(),
@@ -207,6 +294,25 @@ impl Document {
 
             hover_node_id: None,
             focus_node_id: None,
+            focus_trap_root: None,
+            focus_visible: true,
+            autofocus_request: None,
+            text_selection: None,
+            clipboard_text: None,
+            pending_scroll: None,
+            key_repeat: KeyRepeatState::default(),
+            user_data: HashMap::new(),
+            synthetic_events: HashSet::new(),
+            child_count_changes: HashMap::new(),
+            layout_dirty: true,
+            layout_invalidations: HashSet::new(),
+            relayout_nodes: HashSet::new(),
+            relevant_content: HashSet::new(),
+            painted_rects: HashMap::new(),
+            paint_indexes: OnceCell::new(),
+            scrollbar_width: 0.0,
+            defer_layout: false,
+            reactivity_paused: false,
             scroll_offset: 0.0,
             changed: HashSet::new(),
         };
@@ -243,14 +349,23 @@ impl Document {
             .or(self.try_root_element().map(|el| el.id))
     }
 
+    /// The node which currently has focus (if any). Unlike [`get_focussed_node_id`](Self::get_focussed_node_id),
+    /// this does not fall back to the root element when no node is focussed.
+    pub fn get_focus_node_id(&self) -> Option<usize> {
+        self.focus_node_id
+    }
+
+    /// The root of the tree: the `Document` node (with id 0), which is the parent of the root element
     pub fn root_node(&self) -> &Node {
         &self.nodes[0]
     }
 
+    /// The root element (`<html>`), or `None` if the document has no elements yet
     pub fn try_root_element(&self) -> Option<&Node> {
         TDocument::as_node(&self.root_node()).first_element_child()
     }
 
+    /// The root element (`<html>`). Panics if the document has no elements yet.
     pub fn root_element(&self) -> &Node {
         TDocument::as_node(&self.root_node())
             .first_element_child()
@@ -278,16 +393,61 @@ impl Document {
 
         // Mark the new node as changed.
         self.changed.insert(id);
+        self.invalidate_layout(id);
 
         id
     }
 
+    /// Reserve capacity for at least `additional` more nodes, avoiding repeated reallocation of the node storage
+    /// when a large number of nodes are about to be created (e.g. during an initial render)
+    pub fn reserve_nodes(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
     pub fn create_text_node(&mut self, text: &str) -> usize {
         let content = text.to_string();
         let data = NodeData::Text(TextNodeData::new(content));
         self.create_node(data)
     }
 
+    /// Turn a placeholder (comment) node into an empty element in place, keeping its id and position in the tree.
+    /// Only the node itself needs styling and only it and its ancestors need laying out again.
+    ///
+    /// Panics if the node is not a placeholder.
+    pub fn upgrade_placeholder_to_element(&mut self, node_id: usize, name: QualName) {
+        let node = &mut self.nodes[node_id];
+        assert!(
+            matches!(node.raw_dom_data, NodeData::Comment),
+            "node {node_id} is not a placeholder"
+        );
+        node.raw_dom_data = NodeData::Element(ElementNodeData::new(name, Vec::new()));
+        *node.stylo_element_data.borrow_mut() = Some(Default::default());
+        self.mark_upgraded_placeholder(node_id);
+    }
+
+    /// Turn a placeholder (comment) node into a text node in place, keeping its id and position in the tree.
+    /// Text nodes aren't styled, so only layout needs updating.
+    ///
+    /// Panics if the node is not a placeholder.
+    pub fn upgrade_placeholder_to_text(&mut self, node_id: usize, text: &str) {
+        let node = &mut self.nodes[node_id];
+        assert!(
+            matches!(node.raw_dom_data, NodeData::Comment),
+            "node {node_id} is not a placeholder"
+        );
+        node.raw_dom_data = NodeData::Text(TextNodeData::new(text.to_string()));
+        self.mark_upgraded_placeholder(node_id);
+    }
+
+    fn mark_upgraded_placeholder(&mut self, node_id: usize) {
+        self.changed.insert(node_id);
+        self.invalidate_layout(node_id);
+        // The parent's layout children change (e.g. the new node may need wrapping in an anonymous block)
+        if let Some(parent_id) = self.nodes[node_id].parent {
+            self.invalidate_layout(parent_id);
+        }
+    }
+
     pub fn deep_clone_node(&mut self, node_id: usize) -> usize {
         // Load existing node
         let node = &self.nodes[node_id];
@@ -319,10 +479,12 @@ impl Document {
         let node_child_idx = node.child_idx;
 
         let parent_id = node.parent.unwrap();
+        self.record_child_count(parent_id);
         let parent = &mut self.nodes[parent_id];
 
         // Mark the node's parent as changed.
         self.changed.insert(parent_id);
+        self.invalidate_layout(parent_id);
 
         let mut children = std::mem::take(&mut parent.children);
         children.splice(
@@ -347,6 +509,8 @@ impl Document {
         let node = &self.nodes[node_id];
         // let node_child_idx = node.child_idx;
         let parent_id = node.parent.unwrap();
+        self.record_child_count(parent_id);
+        self.invalidate_layout(parent_id);
         let parent = &mut self.nodes[parent_id];
 
         let mut children = std::mem::take(&mut parent.children);
@@ -367,17 +531,7 @@ impl Document {
     }
 
     pub fn remove_node(&mut self, node_id: usize) -> Option<Node> {
-        fn remove_node_ignoring_parent(doc: &mut Document, node_id: usize) -> Option<Node> {
-            let node = doc.nodes.try_remove(node_id);
-            if let Some(node) = &node {
-                for &child in &node.children {
-                    remove_node_ignoring_parent(doc, child);
-                }
-            }
-            node
-        }
-
-        let node = remove_node_ignoring_parent(self, node_id);
+        let node = self.remove_node_ignoring_parent(node_id);
 
         // Update child_idx values
         if let Some(Node {
@@ -386,6 +540,8 @@ impl Document {
             ..
         }) = node
         {
+            self.record_child_count(parent_id);
+            self.invalidate_layout(parent_id);
             let parent = &mut self.nodes[parent_id];
 
             let mut children = std::mem::take(&mut parent.children);
@@ -402,9 +558,507 @@ impl Document {
             self.nodes[parent_id].children = children;
         }
 
+        self.invalidate_focus();
+
+        node
+    }
+
+    /// Remove a node from its parent's children (if it has a parent), leaving it and its descendants in the slab
+    fn detach_node(&mut self, node_id: usize) {
+        let node = &mut self.nodes[node_id];
+        let Some(parent_id) = node.parent.take() else {
+            return;
+        };
+        let child_idx = node.child_idx;
+        self.record_child_count(parent_id);
+        self.invalidate_layout(parent_id);
+        self.changed.insert(parent_id);
+
+        let mut children = std::mem::take(&mut self.nodes[parent_id].children);
+        children.remove(child_idx);
+        for (child_idx, &child_id) in children.iter().enumerate().skip(child_idx) {
+            self.nodes[child_id].child_idx = child_idx;
+        }
+        self.nodes[parent_id].children = children;
+    }
+
+    /// Remove a node and its descendants from the slab without updating its parent's children
+    fn remove_node_ignoring_parent(&mut self, node_id: usize) -> Option<Node> {
+        let node = self.nodes.try_remove(node_id);
+        self.user_data.remove(&node_id);
+        self.animations
+            .sets
+            .write()
+            .remove(&AnimationSetKey::new_for_non_pseudo(OpaqueNode(node_id)));
+        if let Some(node) = &node {
+            for &child in &node.children {
+                self.remove_node_ignoring_parent(child);
+            }
+        }
         node
     }
 
+    /// Remove several nodes (and their descendants) at once, returning the total number of nodes removed.
+    ///
+    /// Unlike calling `remove_node` for each node, each affected parent's children are only updated once.
+    /// Ids which are descendants of other ids in the list (or which have already been removed) are skipped.
+    /// Id mappings referring to removed nodes are cleared.
+    pub fn remove_many(&mut self, node_ids: &[usize]) -> usize {
+        let requested: HashSet<usize> = node_ids.iter().copied().collect();
+
+        // Only remove the topmost nodes: their descendants are removed along with them
+        let roots: Vec<usize> = requested
+            .iter()
+            .copied()
+            .filter(|&id| self.nodes.contains(id))
+            .filter(|&id| {
+                let mut ancestor = self.nodes[id].parent;
+                while let Some(ancestor_id) = ancestor {
+                    if requested.contains(&ancestor_id) {
+                        return false;
+                    }
+                    ancestor = self.nodes[ancestor_id].parent;
+                }
+                true
+            })
+            .collect();
+
+        let mut parents = HashSet::new();
+        for &node_id in &roots {
+            if let Some(parent_id) = self.nodes[node_id].parent {
+                if parents.insert(parent_id) {
+                    self.record_child_count(parent_id);
+                    self.invalidate_layout(parent_id);
+                }
+            }
+        }
+
+        let count_before = self.nodes.len();
+        for &node_id in &roots {
+            self.remove_node_ignoring_parent(node_id);
+        }
+
+        for parent_id in parents {
+            let mut children = std::mem::take(&mut self.nodes[parent_id].children);
+            children.retain(|&child_id| self.nodes.contains(child_id));
+            for (child_idx, &child_id) in children.iter().enumerate() {
+                self.nodes[child_id].child_idx = child_idx;
+            }
+            self.nodes[parent_id].children = children;
+        }
+
+        self.nodes_to_id.retain(|_, id| self.nodes.contains(*id));
+        self.invalidate_focus();
+
+        count_before - self.nodes.len()
+    }
+
+    /// Update the children of `parent_id` to the keyed list `keyed` of (key, node) pairs, where each child's key is
+    /// stored in its `data-key` attribute.
+    ///
+    /// Existing children whose key appears in the list are kept (and the node given for that key is removed if it
+    /// is a different, detached node), so their state is preserved. The nodes given for new keys are inserted
+    /// and have their `data-key` set. Children with keys which are no longer in the list (and children without a
+    /// key) are removed. Of the kept children, as few as possible are moved: the children whose relative order is
+    /// unchanged (a longest increasing subsequence of their old positions) stay where they are.
+    ///
+    /// If `parent_id` or any of the given nodes has been removed from the document, nothing is changed.
+    pub fn reconcile_children_by_key(
+        &mut self,
+        parent_id: usize,
+        keyed: Vec<(String, usize)>,
+    ) -> KeyedChanges {
+        let Some(parent) = self.get_node(parent_id) else {
+            return KeyedChanges::default();
+        };
+        if keyed
+            .iter()
+            .any(|&(_, node_id)| self.get_node(node_id).is_none())
+        {
+            return KeyedChanges::default();
+        }
+
+        let data_key = LocalName::from("data-key");
+        let old_children = parent.children.clone();
+        let mut old_keyed: HashMap<String, (usize, usize)> = HashMap::new();
+        for (old_idx, &child_id) in old_children.iter().enumerate() {
+            if let Some(key) = self.nodes[child_id].attr(data_key.clone()) {
+                old_keyed.insert(key.to_string(), (old_idx, child_id));
+            }
+        }
+
+        let mut changes = KeyedChanges::default();
+        let mut new_children = Vec::with_capacity(keyed.len());
+        // The old positions of kept children, in their new order
+        let mut kept: Vec<(usize, usize)> = Vec::new();
+        for (key, node_id) in keyed {
+            match old_keyed.remove(&key) {
+                Some((old_idx, child_id)) => {
+                    if node_id != child_id && self.nodes[node_id].parent.is_none() {
+                        changes.discarded.push(node_id);
+                    }
+                    kept.push((old_idx, child_id));
+                    new_children.push(child_id);
+                }
+                None => {
+                    // Nodes from elsewhere in the tree are moved, so they must leave their old parent first
+                    if self.nodes[node_id].parent.is_some_and(|id| id != parent_id) {
+                        self.detach_node(node_id);
+                    }
+                    if let Some(element) = self.nodes[node_id].raw_dom_data.downcast_element_mut() {
+                        element.set_attribute(QualName::new(None, ns!(), data_key.clone()), key);
+                    }
+                    changes.inserted.push(node_id);
+                    new_children.push(node_id);
+                }
+            }
+        }
+
+        let old_positions: Vec<usize> = kept.iter().map(|&(old_idx, _)| old_idx).collect();
+        let stationary: HashSet<usize> = longest_increasing_subsequence(&old_positions)
+            .into_iter()
+            .collect();
+        changes.moved = kept
+            .iter()
+            .enumerate()
+            .filter(|(kept_idx, _)| !stationary.contains(kept_idx))
+            .map(|(_, &(_, child_id))| child_id)
+            .collect();
+        let new_set: HashSet<usize> = new_children.iter().copied().collect();
+        changes.removed = old_children
+            .iter()
+            .copied()
+            .filter(|child_id| !new_set.contains(child_id))
+            .collect();
+
+        if changes.inserted.is_empty() && changes.moved.is_empty() && changes.removed.is_empty() {
+            self.remove_many(&changes.discarded);
+            return changes;
+        }
+
+        self.record_child_count(parent_id);
+        self.invalidate_layout(parent_id);
+        self.changed.insert(parent_id);
+        for (child_idx, &child_id) in new_children.iter().enumerate() {
+            let child = &mut self.nodes[child_id];
+            child.parent = Some(parent_id);
+            child.child_idx = child_idx;
+        }
+        for &child_id in &changes.removed {
+            self.nodes[child_id].parent = None;
+        }
+        self.nodes[parent_id].children = new_children;
+
+        let garbage: Vec<usize> = changes
+            .removed
+            .iter()
+            .chain(&changes.discarded)
+            .copied()
+            .collect();
+        self.remove_many(&garbage);
+        changes
+    }
+
+    /// Bring focus, hover and selection state back in sync with the tree after structural changes: references to
+    /// nodes which have been removed (or are no longer attached to the document) are cleared. Tab navigation then
+    /// continues from the start of the document.
+    ///
+    /// This is called by `remove_node`, but integrations which restructure the tree through lower-level APIs
+    /// should call it themselves.
+    pub fn invalidate_focus(&mut self) {
+        let is_detached = |doc: &Document, id: Option<usize>| {
+            id.is_some_and(|id| {
+                !doc.nodes.contains(id) || (id != 0 && !doc.is_descendant_of(id, 0))
+            })
+        };
+        if is_detached(self, self.focus_node_id) {
+            self.focus_node_id = None;
+        }
+        if is_detached(self, self.hover_node_id) {
+            self.hover_node_id = None;
+        }
+        if is_detached(self, self.focus_trap_root) {
+            self.focus_trap_root = None;
+        }
+        if is_detached(self, self.text_selection.map(|selection| selection.node_id)) {
+            self.text_selection = None;
+        }
+    }
+
+    /// The index of a node among its parent's children, or `None` if the node has no parent. This is O(1): each
+    /// node caches its index, which the structural mutation methods keep up to date.
+    pub fn index_in_parent(&self, node_id: usize) -> Option<usize> {
+        let node = self.nodes.get(node_id)?;
+        let siblings = &self.nodes.get(node.parent?)?.children;
+        if siblings.get(node.child_idx) == Some(&node_id) {
+            Some(node.child_idx)
+        } else {
+            // The cached index is stale (the tree was modified through lower-level APIs)
+            siblings.iter().position(|&id| id == node_id)
+        }
+    }
+
+    /// The ids of the editable text fields whose content should be spellchecked (see [`Node::is_spellchecked`]),
+    /// in ascending order. This requires layout to have been resolved, as that is when editors are created.
+    pub fn spellcheck_regions(&self) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.is_spellchecked())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Remove every node which is not reachable from the root (through either the DOM tree or the layout tree),
+    /// returning the number of nodes removed. Id mappings, user data and hover/focus state referring to removed
+    /// nodes are cleared.
+    ///
+    /// This is a safety net for integrations which manipulate the tree through low-level APIs and may leave
+    /// detached nodes behind. Note that it also removes nodes which were created but have not yet been inserted,
+    /// so callers must not hold on to the ids of detached nodes they still need (such as templates) across a
+    /// collection. Use [`collect_garbage_with_roots`](Self::collect_garbage_with_roots) to keep such nodes.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.collect_garbage_with_roots(std::iter::empty())
+    }
+
+    /// Like [`collect_garbage`](Self::collect_garbage), but nodes reachable from any of `roots` (for example,
+    /// detached templates which an integration clones from) are kept as well as those reachable from the root.
+    pub fn collect_garbage_with_roots(&mut self, roots: impl IntoIterator<Item = usize>) -> usize {
+        let mut reachable = HashSet::new();
+        let mut stack = vec![0];
+        stack.extend(roots);
+        while let Some(node_id) = stack.pop() {
+            // Layout children may still refer to nodes which have been removed since the last layout
+            let Some(node) = self.nodes.get(node_id) else {
+                continue;
+            };
+            if !reachable.insert(node_id) {
+                continue;
+            }
+            stack.extend(node.children.iter().copied());
+            if let Some(layout_children) = node.layout_children.borrow().as_ref() {
+                stack.extend(layout_children.iter().copied());
+            }
+        }
+
+        let garbage: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|(id, _)| id)
+            .filter(|id| !reachable.contains(id))
+            .collect();
+
+        // Nodes are removed individually: the children of a stale anonymous block may still be reachable
+        for &node_id in &garbage {
+            self.nodes.remove(node_id);
+            self.user_data.remove(&node_id);
+            self.changed.remove(&node_id);
+            self.layout_invalidations.remove(&node_id);
+        }
+
+        let is_garbage = |id: &Option<usize>| id.is_some_and(|id| !reachable.contains(&id));
+        if is_garbage(&self.hover_node_id) {
+            self.hover_node_id = None;
+        }
+        if is_garbage(&self.focus_node_id) {
+            self.focus_node_id = None;
+        }
+        if is_garbage(&self.focus_trap_root) {
+            self.focus_trap_root = None;
+        }
+        if is_garbage(&self.autofocus_request) {
+            self.autofocus_request = None;
+        }
+        self.nodes_to_id.retain(|_, id| reachable.contains(id));
+
+        garbage.len()
+    }
+
+    /// Register a user-defined event name (e.g. "longpress") so that it can be dispatched with `dispatch_synthetic`
+    pub fn register_synthetic_event(&mut self, name: String) {
+        self.synthetic_events.insert(name);
+    }
+
+    /// Whether `name` has been registered with `register_synthetic_event`
+    pub fn is_synthetic_event(&self, name: &str) -> bool {
+        self.synthetic_events.contains(name)
+    }
+
+    /// Compute the path along which a registered synthetic event bubbles: the ids of the nodes from `target_id` up to the
+    /// root which have a listener for `name`, in the order in which their listeners should be called.
+    /// Returns an empty path if `name` has not been registered.
+    pub fn dispatch_synthetic(&self, target_id: usize, name: &str) -> Vec<usize> {
+        if !self.is_synthetic_event(name) {
+            return Vec::new();
+        }
+
+        let mut path = Vec::new();
+        let mut next_id = Some(target_id);
+        while let Some(node_id) = next_id {
+            let node = &self.nodes[node_id];
+            if node.has_event_listener(name) {
+                path.push(node_id);
+            }
+            next_id = node.parent;
+        }
+        path
+    }
+
+    /// Dispatch the event `name` to `target_id`, calling `listener` for each node with a listener for it: first the
+    /// capturing listeners from the root down to the target, then the other listeners from the target up to the
+    /// root. Listeners registered with `once` are removed after they are called, and `prevent_default` has no effect
+    /// in passive listeners.
+    ///
+    /// Returns whether the default action of the event was prevented.
+    pub fn dispatch_event(
+        &mut self,
+        target_id: usize,
+        name: &str,
+        mut listener: impl FnMut(&mut DispatchedEvent),
+    ) -> bool {
+        let mut ancestors = Vec::new();
+        let mut next_id = Some(target_id);
+        while let Some(node_id) = next_id {
+            ancestors.push(node_id);
+            next_id = self.nodes[node_id].parent;
+        }
+        let capture_path = ancestors.iter().rev().map(|&node_id| (node_id, true));
+        let bubble_path = ancestors.iter().map(|&node_id| (node_id, false));
+
+        let mut event = DispatchedEvent::new(target_id);
+        for (node_id, capture) in capture_path.chain(bubble_path) {
+            let node = &mut self.nodes[node_id];
+            let Some(index) = node
+                .listeners
+                .iter()
+                .position(|l| l.name == name && l.options.capture == capture)
+            else {
+                continue;
+            };
+            let options = node.listeners[index].options;
+            if options.once {
+                node.listeners.remove(index);
+            }
+
+            event.current_target = node_id;
+            event.passive = options.passive;
+            listener(&mut event);
+            if event.propagation_stopped {
+                break;
+            }
+        }
+        event.default_prevented()
+    }
+
+    /// The ids of the strict descendants of `root_id` which have a listener for `event`, sorted by depth
+    /// (shallowest first). Nodes at the same depth are in document order, so the result is deterministic.
+    /// Useful for delegating events within a component.
+    pub fn listeners_in_subtree(&self, root_id: usize, event: &str) -> Vec<usize> {
+        // Visit the subtree in document order (pre-order), recording the depth of each listener
+        let mut listeners: Vec<(usize, usize)> = Vec::new();
+        let mut stack: Vec<(usize, usize)> = self.nodes[root_id]
+            .children
+            .iter()
+            .rev()
+            .map(|&child_id| (child_id, 1))
+            .collect();
+        while let Some((node_id, depth)) = stack.pop() {
+            let node = &self.nodes[node_id];
+            if node.has_event_listener(event) {
+                listeners.push((depth, node_id));
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|&child_id| (child_id, depth + 1)),
+            );
+        }
+
+        // A stable sort keeps listeners at the same depth in document order
+        listeners.sort_by_key(|&(depth, _)| depth);
+        listeners.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Attach arbitrary data to a node (e.g. a cached GPU handle), replacing any data previously attached to it.
+    /// Unlike styles and layout the data is never recomputed; it is dropped when the node is removed.
+    pub fn set_user_data(&mut self, node_id: usize, data: Box<dyn Any + Send>) {
+        self.user_data.insert(node_id, data);
+    }
+
+    /// The data attached to a node with `set_user_data`, if there is any and it is of type `T`
+    pub fn user_data<T: 'static>(&self, node_id: usize) -> Option<&T> {
+        self.user_data.get(&node_id)?.downcast_ref()
+    }
+
+    /// Mutable access to the data attached to a node with `set_user_data`, if there is any and it is of type `T`
+    pub fn user_data_mut<T: 'static>(&mut self, node_id: usize) -> Option<&mut T> {
+        self.user_data.get_mut(&node_id)?.downcast_mut()
+    }
+
+    /// Detach and return the data attached to a node with `set_user_data`
+    pub fn take_user_data(&mut self, node_id: usize) -> Option<Box<dyn Any + Send>> {
+        self.user_data.remove(&node_id)
+    }
+
+    /// Configure the delay before a held key starts repeating and the interval between repeats (which is clamped to
+    /// at least [`MIN_KEY_REPEAT_INTERVAL`](crate::events::MIN_KEY_REPEAT_INTERVAL))
+    pub fn set_key_repeat_timing(&mut self, initial_delay: Duration, interval: Duration) {
+        self.key_repeat.initial_delay = initial_delay;
+        self.key_repeat.interval = interval.max(crate::events::MIN_KEY_REPEAT_INTERVAL);
+    }
+
+    /// Record that a key was pressed at `now`, starting its repeat timer.
+    /// Returns `false` if the key was already held (i.e. this is not an initial keydown).
+    pub fn key_down(&mut self, code: KeyCode, now: Instant) -> bool {
+        self.key_repeat.press(code, now)
+    }
+
+    /// Record that a key was released, stopping its repeats
+    pub fn key_up(&mut self, code: KeyCode) {
+        self.key_repeat.release(code);
+    }
+
+    /// The key repeats which are due at `now`, each paired with the focussed node to which it should be delivered.
+    /// Repeats which fall due while nothing is focussed are dropped.
+    pub fn tick_key_repeat(&mut self, now: Instant) -> Vec<(usize, KeyCode)> {
+        let repeats = self.key_repeat.tick(now);
+        let Some(focus_node_id) = self.focus_node_id else {
+            return Vec::new();
+        };
+        repeats
+            .into_iter()
+            .map(|code| (focus_node_id, code))
+            .collect()
+    }
+
+    /// The number of direct children of a node
+    pub fn child_count(&self, node_id: usize) -> usize {
+        self.nodes[node_id].children.len()
+    }
+
+    /// Record the child count of a node before its children are modified, so that the modification can be reported
+    /// by `take_child_count_changes`. Only the first call between calls to `take_child_count_changes` has an effect.
+    pub fn record_child_count(&mut self, node_id: usize) {
+        let count = self.child_count(node_id);
+        self.child_count_changes.entry(node_id).or_insert(count);
+    }
+
+    /// Take the ids of nodes whose number of direct children has changed since this method was last called
+    /// (in ascending order). Nodes whose children changed but whose count ended up unchanged are not included.
+    pub fn take_child_count_changes(&mut self) -> Vec<usize> {
+        let mut changed: Vec<usize> = std::mem::take(&mut self.child_count_changes)
+            .into_iter()
+            .filter(|&(node_id, count)| {
+                self.nodes
+                    .get(node_id)
+                    .is_some_and(|node| node.children.len() != count)
+            })
+            .map(|(node_id, _)| node_id)
+            .collect();
+        changed.sort_unstable();
+        changed
+    }
+
     pub fn resolve_url(&self, raw: &str) -> url::Url {
         match &self.base_url {
             Some(base_url) => base_url.join(raw).unwrap(),
@@ -497,73 +1151,569 @@ impl Document {
         cb(&mut self.nodes[node_id]);
     }
 
-    /// Restyle the tree and then relayout it
-    pub fn resolve(&mut self) {
-        if TDocument::as_node(&&self.nodes[0])
-            .first_element_child()
-            .is_none()
-        {
-            println!("No DOM - not resolving");
-            return;
+    /// Toggle a boolean attribute on an element, marking the element for restyling.
+    /// Returns whether the attribute is present after toggling.
+    pub fn toggle_attribute(&mut self, node_id: usize, name: QualName) -> bool {
+        if !self.nodes[node_id].is_element() {
+            return false;
         }
 
-        // we need to resolve stylist first since it will need to drive our layout bits
-        self.resolve_stylist();
+        self.snapshot_node(node_id);
+        let node = &mut self.nodes[node_id];
+        let is_present = node
+            .raw_dom_data
+            .downcast_element_mut()
+            .unwrap()
+            .toggle_attribute(name);
+        node.set_restyle_hint(RestyleHint::restyle_subtree());
+        self.changed.insert(node_id);
+        self.invalidate_layout(node_id);
 
-        // Fix up tree for layout (insert anonymous blocks as necessary, etc)
-        self.resolve_layout_children();
+        is_present
+    }
 
-        // Merge stylo into taffy
-        self.flush_styles_to_layout(vec![self.root_element().id]);
+    /// Replace an element's inline `style` attribute, re-parsing its declarations and marking the element for
+    /// restyling. Font-relative lengths (such as `em` margins) are re-resolved against the new computed font size.
+    pub fn set_style_attribute(&mut self, node_id: usize, value: &str) {
+        if !self.nodes[node_id].is_element() {
+            return;
+        }
 
-        // Next we resolve layout with the data resolved by stlist
-        self.resolve_layout();
+        self.snapshot_node(node_id);
+        let guard = self.guard.clone();
+        let node = &mut self.nodes[node_id];
+        let element = node.raw_dom_data.downcast_element_mut().unwrap();
+        element.set_attribute(
+            QualName::new(None, ns!(), local_name!("style")),
+            value.to_string(),
+        );
+        element.flush_style_attribute(&guard);
+        node.set_restyle_hint(RestyleHint::RESTYLE_STYLE_ATTRIBUTE);
+        self.changed.insert(node_id);
+        self.invalidate_layout(node_id);
     }
 
-    // Takes (x, y) co-ordinates (relative to the )
-    pub fn hit(&self, x: f32, y: f32) -> Option<HitResult> {
-        if TDocument::as_node(&&self.nodes[0])
-            .first_element_child()
-            .is_none()
-        {
-            println!("No DOM - not resolving");
-            return None;
+    /// Remove an element's inline `style` attribute along with its declarations, marking the element for restyling
+    pub fn remove_style_attribute(&mut self, node_id: usize) {
+        if !self.nodes[node_id].is_element() {
+            return;
         }
 
-        self.root_element().hit(x, y)
+        self.snapshot_node(node_id);
+        let guard = self.guard.clone();
+        let node = &mut self.nodes[node_id];
+        let element = node.raw_dom_data.downcast_element_mut().unwrap();
+        element
+            .attrs
+            .retain(|attr| attr.name.local != local_name!("style"));
+        element.flush_style_attribute(&guard);
+        node.set_restyle_hint(RestyleHint::RESTYLE_STYLE_ATTRIBUTE);
+        self.changed.insert(node_id);
+        self.invalidate_layout(node_id);
     }
 
-    pub fn next_node(&self, start: &Node, mut filter: impl FnMut(&Node) -> bool) -> Option<usize> {
-        let start_id = start.id;
-        let mut node = start;
-        let mut look_in_children = true;
-        loop {
-            // Next is first child
-            let next = if look_in_children && !node.children.is_empty() {
-                let node_id = node.children[0];
-                &self.nodes[node_id]
-            }
-            // Next is next sibling or parent
-            else if let Some(parent) = node.parent_node() {
-                let self_idx = parent
-                    .children
-                    .iter()
-                    .position(|id| *id == node.id)
-                    .unwrap();
-                // Next is next sibling
-                if let Some(sibling_id) = parent.children.get(self_idx + 1) {
-                    look_in_children = true;
-                    &self.nodes[*sibling_id]
-                }
-                // Next is parent
-                else {
-                    look_in_children = false;
-                    node = parent;
-                    continue;
+    /// Replace the content of a text node, marking it as changed and invalidating its layout. This is intended for
+    /// hot paths which update many text nodes per frame, so it does no other bookkeeping. Does nothing if the node
+    /// isn't a text node.
+    pub fn set_text_fast(&mut self, node_id: usize, text: impl Into<String>) {
+        let Some(NodeData::Text(data)) = self
+            .nodes
+            .get_mut(node_id)
+            .map(|node| &mut node.raw_dom_data)
+        else {
+            return;
+        };
+        data.content = text.into();
+        self.changed.insert(node_id);
+        self.invalidate_layout(node_id);
+    }
+
+    /// Delete the grapheme cluster (user-perceived character) before the caret in a text node, or the selected
+    /// text if the selection isn't collapsed. The caret is the document's text selection, which must be within the
+    /// node. Returns whether any text was deleted.
+    pub fn delete_backward_grapheme(&mut self, node_id: usize) -> bool {
+        self.delete_backward(node_id, |text| {
+            text.grapheme_indices(true)
+                .next_back()
+                .map_or(0, |(start, _)| start)
+        })
+    }
+
+    /// Delete the word before the caret in a text node (along with any whitespace between the word and the caret),
+    /// or the selected text if the selection isn't collapsed. Words are found with Unicode (UAX #29) word
+    /// segmentation. Returns whether any text was deleted.
+    pub fn delete_backward_word(&mut self, node_id: usize) -> bool {
+        self.delete_backward(node_id, |text| {
+            let mut boundary = text.len();
+            for (start, segment) in text.split_word_bound_indices().rev() {
+                boundary = start;
+                if !segment.chars().all(char::is_whitespace) {
+                    break;
                 }
             }
-            // Continue search from the root
-            else {
+            boundary
+        })
+    }
+
+    /// Delete the selected text of a text node or, if the selection is collapsed, the text between the caret and
+    /// the offset returned by `boundary` (given the text before the caret)
+    fn delete_backward(&mut self, node_id: usize, boundary: impl Fn(&str) -> usize) -> bool {
+        let Some(selection) = self.text_selection.filter(|s| s.node_id == node_id) else {
+            return false;
+        };
+        let Some(mut text) = self
+            .get_node(node_id)
+            .and_then(|node| node.text_data())
+            .map(|data| data.content.clone())
+        else {
+            return false;
+        };
+
+        let range = selection.range();
+        if range.end > text.len()
+            || !text.is_char_boundary(range.start)
+            || !text.is_char_boundary(range.end)
+        {
+            return false;
+        }
+        let range = if selection.is_collapsed() {
+            boundary(&text[..range.end])..range.end
+        } else {
+            range
+        };
+        if range.is_empty() {
+            return false;
+        }
+
+        text.replace_range(range.clone(), "");
+        self.set_text_fast(node_id, text);
+        self.set_text_selection(node_id, range.start, range.start);
+        true
+    }
+
+    /// The value of a form control. For text inputs this is the current text of the input's editor,
+    /// which may differ from the `value` attribute once the user has edited it.
+    pub fn input_value(&self, node_id: usize) -> Option<String> {
+        let element = self.get_node(node_id)?.element_data()?;
+        match element.text_input_data() {
+            Some(input_data) => Some(input_data.text()),
+            None => element.attr(local_name!("value")).map(str::to_string),
+        }
+    }
+
+    /// Set the value of a form control, updating both its `value` attribute and (for text inputs) the
+    /// text of its editor. The element is marked for restyling as selectors may depend on the value, and any text
+    /// selection within it is clamped to the new value.
+    pub fn set_input_value(&mut self, node_id: usize, value: &str) {
+        if !self.nodes[node_id].is_element() {
+            return;
+        }
+
+        self.snapshot_node_and(node_id, |node| {
+            let element = node.raw_dom_data.downcast_element_mut().unwrap();
+            element.set_attribute(
+                QualName::new(None, ns!(), local_name!("value")),
+                value.to_string(),
+            );
+            if let Some(input_data) = element.text_input_data_mut() {
+                input_data.set_text(value.to_string());
+            }
+            node.set_restyle_hint(RestyleHint::restyle_subtree());
+        });
+        self.changed.insert(node_id);
+        self.invalidate_layout(node_id);
+
+        // Clamp the selection (and so the caret) to the new text, moving offsets which now fall within a character
+        // back to its start
+        if let Some(selection) = self
+            .text_selection
+            .as_mut()
+            .filter(|selection| selection.node_id == node_id)
+        {
+            let clamp = |offset: usize| {
+                let mut offset = offset.min(value.len());
+                while !value.is_char_boundary(offset) {
+                    offset -= 1;
+                }
+                offset
+            };
+            selection.anchor = clamp(selection.anchor);
+            selection.focus = clamp(selection.focus);
+        }
+        self.scroll_text_input_to_cursor(node_id);
+    }
+
+    /// Restyle the tree and then relayout it
+    pub fn resolve(&mut self) {
+        if self.reactivity_paused {
+            return;
+        }
+
+        if let Some(viewport) = self.pending_viewport.take() {
+            self.set_viewport(viewport);
+        }
+
+        if TDocument::as_node(&&self.nodes[0])
+            .first_element_child()
+            .is_none()
+        {
+            println!("No DOM - not resolving");
+            return;
+        }
+
+        // we need to resolve stylist first since it will need to drive our layout bits
+        self.resolve_stylist();
+        self.update_inherited_state();
+        if self.blur_inert_focus() {
+            // Restyle the node which lost focus
+            self.resolve_stylist();
+        }
+
+        self.mark_layout_dirty();
+        if !self.defer_layout {
+            self.flush_layout();
+        }
+    }
+
+    /// Advance the clock at which transitions are sampled to `time` (in seconds), marking the nodes which are
+    /// transitioning to be restyled by the next `resolve`.
+    ///
+    /// Transitions are started by stylo when a restyle changes a property with a `transition`. If the property changes
+    /// again while its transition is in flight, the new transition starts from the property's current animated value
+    /// rather than the original start value (and reversing a transition shortens it to the time it had been running),
+    /// so retargeting a transition doesn't make the property jump.
+    pub fn set_animation_time(&mut self, time: f64) {
+        self.animation_time = time;
+
+        for (key, set) in self.animations.sets.write().iter_mut() {
+            for transition in set.transitions.iter_mut() {
+                if transition.state == AnimationState::Pending {
+                    transition.state = AnimationState::Running;
+                }
+                if transition.state == AnimationState::Running && transition.has_ended(time) {
+                    transition.state = AnimationState::Finished;
+                }
+            }
+
+            // Finished transitions are restyled once more so that their final value is applied and they are dropped
+            if let Some(node) = self.nodes.get(key.node.0) {
+                if let Some(data) = node.stylo_element_data.borrow_mut().as_mut() {
+                    data.hint.insert(RestyleHint::RESTYLE_CSS_TRANSITIONS);
+                }
+            }
+        }
+    }
+
+    /// Whether any transitions are in flight, in which case the document should be resolved again on the next frame
+    pub fn has_active_animations(&self) -> bool {
+        !self.animations.sets.read().is_empty()
+    }
+
+    /// Suspend restyling and relayout (e.g. during bulk edits). While paused `resolve` does nothing, but changes
+    /// continue to accumulate and are all resolved at once by `resume_reactivity`.
+    pub fn pause_reactivity(&mut self) {
+        self.reactivity_paused = true;
+    }
+
+    /// Resume restyling and relayout after `pause_reactivity`, resolving all changes made while paused
+    pub fn resume_reactivity(&mut self) {
+        if self.reactivity_paused {
+            self.reactivity_paused = false;
+            self.resolve();
+        }
+    }
+
+    pub fn is_reactivity_paused(&self) -> bool {
+        self.reactivity_paused
+    }
+
+    /// Defer relayout (e.g. while an element is being interactively resized) so that `resolve` only restyles the
+    /// tree. Layout changes are coalesced until `flush_layout` is called or deferral is turned off.
+    pub fn set_defer_layout(&mut self, defer_layout: bool) {
+        self.defer_layout = defer_layout;
+        if !defer_layout {
+            self.flush_layout();
+        }
+    }
+
+    /// Mark layout as being out of date so that it is recomputed by the next `flush_layout`
+    pub fn mark_layout_dirty(&mut self) {
+        self.layout_dirty = true;
+    }
+
+    /// Whether layout is out of date with respect to styles
+    pub fn needs_layout(&self) -> bool {
+        self.layout_dirty
+    }
+
+    /// Mark a node's own layout inputs (e.g. its text content or children) as having changed, so that the node and
+    /// its ancestors are laid out again by the next `flush_layout`. Style changes are detected automatically.
+    pub fn invalidate_layout(&mut self, node_id: usize) {
+        self.layout_invalidations.insert(node_id);
+        self.layout_dirty = true;
+    }
+
+    /// Invalidate the layout of every node (e.g. because the viewport's scale has changed)
+    pub fn invalidate_all_layout(&mut self) {
+        self.layout_invalidations = self.nodes.iter().map(|(id, _)| id).collect();
+        self.layout_dirty = true;
+    }
+
+    /// The number of nodes which were laid out (rather than reusing a cached layout) in the last layout pass
+    pub fn relayout_count(&self) -> usize {
+        self.relayout_nodes.len()
+    }
+
+    /// Relayout the tree if it has been marked dirty since it was last laid out.
+    /// Returns whether a layout pass was run.
+    ///
+    /// Layout is incremental: only invalidated nodes and their ancestors have their cached layouts discarded.
+    /// Other nodes (such as the siblings of an invalidated node) reuse their cached layout unless the space
+    /// available to them has changed, so invalidation stops propagating at nodes whose size is unaffected.
+    pub fn flush_layout(&mut self) -> bool {
+        // Styles and the nodes whose content is skipped may have changed along with layout
+        self.paint_indexes.take();
+
+        if !self.layout_dirty || self.try_root_element().is_none() {
+            return false;
+        }
+
+        // Work out which nodes need to be laid out again
+        self.invalidate_restyled_nodes();
+        self.propagate_layout_invalidations();
+
+        // Fix up tree for layout (insert anonymous blocks as necessary, etc)
+        self.resolve_layout_children();
+
+        // Merge stylo into taffy
+        self.flush_styles_to_layout(vec![self.root_element().id]);
+        self.clear_invalidated_layout_caches();
+
+        // Next we resolve layout with the data resolved by stlist
+        self.relayout_nodes.clear();
+        self.resolve_layout();
+
+        // Scrollbars appearing or disappearing change the space available to content, and content moving into or
+        // out of the viewport changes whether it is laid out, so lay out again until neither changes. A scrollbar
+        // in one axis can make content overflow in the other, so this can take more than one extra pass.
+        for _ in 0..MAX_EXTRA_LAYOUT_PASSES {
+            let scrollbars_changed = self.update_auto_scrollbars();
+            if !self.update_content_relevance() && !scrollbars_changed {
+                break;
+            }
+            self.propagate_layout_invalidations();
+            self.resolve_layout_children();
+            self.flush_styles_to_layout(vec![self.root_element().id]);
+            self.clear_invalidated_layout_caches();
+            self.resolve_layout();
+        }
+
+        self.layout_dirty = false;
+        true
+    }
+
+    /// Discard the cached layouts of invalidated nodes
+    fn clear_invalidated_layout_caches(&mut self) {
+        for node_id in std::mem::take(&mut self.layout_invalidations) {
+            if let Some(node) = self.nodes.get_mut(node_id) {
+                node.cache.clear();
+            }
+        }
+    }
+
+    /// Set the thickness (in CSS pixels) of the space reserved for scrollbars in scroll containers.
+    /// The default is zero (overlay scrollbars which don't take up space).
+    pub fn set_scrollbar_width(&mut self, width: f32) {
+        self.scrollbar_width = width;
+        self.invalidate_all_layout();
+    }
+
+    /// Work out which axes of `overflow: auto` scroll containers show a scrollbar (those whose content overflows),
+    /// invalidating the layout of containers where this has changed. Returns whether anything changed.
+    ///
+    /// Showing a scrollbar shrinks the space available in the other axis, which can make that axis overflow too, so
+    /// this must be repeated after laying out again.
+    fn update_auto_scrollbars(&mut self) -> bool {
+        use style::values::computed::Overflow;
+
+        if self.scrollbar_width == 0.0 {
+            return false;
+        }
+
+        let mut changed = Vec::new();
+        for (node_id, node) in self.nodes.iter() {
+            let Some(style) = node.primary_styles() else {
+                continue;
+            };
+            let box_styles = style.get_box();
+            let overflow = node.max_scroll_offset();
+            let scrollbars = taffy::Point {
+                x: box_styles.overflow_x == Overflow::Auto && overflow.x > 0.0,
+                y: box_styles.overflow_y == Overflow::Auto && overflow.y > 0.0,
+            };
+            if scrollbars != node.auto_scrollbars {
+                changed.push((node_id, scrollbars));
+            }
+        }
+
+        for &(node_id, scrollbars) in &changed {
+            self.nodes[node_id].auto_scrollbars = scrollbars;
+            self.invalidate_layout(node_id);
+        }
+        !changed.is_empty()
+    }
+
+    /// Whether the content of an element is skipped by layout and painting because the element has
+    /// `content-visibility: auto` (see [`Node::has_auto_content_visibility`]) and is outside the viewport. The
+    /// element itself is laid out as if its content was a placeholder of its `contain-intrinsic-size` (see
+    /// [`Node::contain_intrinsic_size`]).
+    pub fn is_content_skipped(&self, node_id: usize) -> bool {
+        self.nodes[node_id].has_auto_content_visibility()
+            && !self.relevant_content.contains(&node_id)
+    }
+
+    /// The ids of the elements whose content is currently skipped (see `is_content_skipped`)
+    pub fn skipped_content_nodes(&self) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .map(|(node_id, _)| node_id)
+            .filter(|&node_id| self.is_content_skipped(node_id))
+            .collect()
+    }
+
+    /// Work out which `content-visibility: auto` elements are in the viewport, now that they have been laid out,
+    /// invalidating the layout of those which moved into or out of it. Returns whether any did.
+    ///
+    /// Elements are laid out with their content skipped until they are found to be in the viewport, so offscreen
+    /// content is never laid out. Elements within skipped content haven't been laid out, so they stay skipped.
+    /// An element which touches the viewport counts as being in it, so that empty elements can be found to be in
+    /// the viewport. Clips applied by ancestors are ignored.
+    fn update_content_relevance(&mut self) -> bool {
+        let size = self.stylist_device().au_viewport_size();
+        let viewport = kurbo::Rect::from_origin_size(
+            (0.0, -self.scroll_offset),
+            (size.width.to_f64_px(), size.height.to_f64_px()),
+        );
+
+        let is_within_skipped_content = |node_id: usize| {
+            let mut ancestor = self.nodes[node_id].parent;
+            while let Some(ancestor_id) = ancestor {
+                if self.is_content_skipped(ancestor_id) {
+                    return true;
+                }
+                ancestor = self.nodes[ancestor_id].parent;
+            }
+            false
+        };
+        let relevant: HashSet<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.has_auto_content_visibility())
+            .map(|(node_id, _)| node_id)
+            .filter(|&node_id| {
+                let rect = self.scrolled_border_box(node_id);
+                !is_within_skipped_content(node_id)
+                    && rect.x0 <= viewport.x1
+                    && rect.x1 >= viewport.x0
+                    && rect.y0 <= viewport.y1
+                    && rect.y1 >= viewport.y0
+            })
+            .collect();
+
+        let changed: Vec<usize> = relevant
+            .symmetric_difference(&self.relevant_content)
+            .copied()
+            .filter(|&node_id| self.nodes.contains(node_id))
+            .collect();
+        self.relevant_content = relevant;
+        for &node_id in &changed {
+            self.invalidate_layout(node_id);
+        }
+        !changed.is_empty()
+    }
+
+    /// Invalidate the layout of nodes whose computed styles have changed since they were last laid out, in any of the
+    /// properties which affect layout (see `stylo_to_taffy::LAYOUT_AFFECTING_PROPERTIES`)
+    fn invalidate_restyled_nodes(&mut self) {
+        for (node_id, node) in self.nodes.iter_mut() {
+            let data = node.stylo_element_data.borrow();
+            let Some(style) = data.as_ref().and_then(|data| data.styles.get_primary()) else {
+                continue;
+            };
+            // Restyles which only change paint-only properties (such as `background-color`) keep the cached layout
+            let affects_layout = match &node.laid_out_style {
+                Some(laid_out_style) if ServoArc::ptr_eq(laid_out_style, style) => continue,
+                Some(laid_out_style) => {
+                    crate::stylo_to_taffy::layout_affecting_properties_differ(laid_out_style, style)
+                }
+                None => true,
+            };
+            if affects_layout {
+                self.layout_invalidations.insert(node_id);
+            }
+            node.laid_out_style = Some(style.clone());
+        }
+    }
+
+    /// Extend the set of invalidated nodes to include their ancestors
+    fn propagate_layout_invalidations(&mut self) {
+        let mut invalidations = HashSet::with_capacity(self.layout_invalidations.len());
+        for node_id in std::mem::take(&mut self.layout_invalidations) {
+            let mut next = Some(node_id);
+            while let Some(node_id) = next {
+                if !invalidations.insert(node_id) {
+                    break;
+                }
+                next = self.nodes.get(node_id).and_then(|node| node.parent);
+            }
+        }
+        self.layout_invalidations = invalidations;
+    }
+
+    // Takes (x, y) co-ordinates (relative to the )
+    pub fn hit(&self, x: f32, y: f32) -> Option<HitResult> {
+        if TDocument::as_node(&&self.nodes[0])
+            .first_element_child()
+            .is_none()
+        {
+            println!("No DOM - not resolving");
+            return None;
+        }
+
+        self.root_element().hit(x, y)
+    }
+
+    pub fn next_node(&self, start: &Node, mut filter: impl FnMut(&Node) -> bool) -> Option<usize> {
+        let start_id = start.id;
+        let mut node = start;
+        let mut look_in_children = true;
+        loop {
+            // Next is first child
+            let next = if look_in_children && !node.children.is_empty() {
+                let node_id = node.children[0];
+                &self.nodes[node_id]
+            }
+            // Next is next sibling or parent
+            else if let Some(parent) = node.parent_node() {
+                let self_idx = parent
+                    .children
+                    .iter()
+                    .position(|id| *id == node.id)
+                    .unwrap();
+                // Next is next sibling
+                if let Some(sibling_id) = parent.children.get(self_idx + 1) {
+                    look_in_children = true;
+                    &self.nodes[*sibling_id]
+                }
+                // Next is parent
+                else {
+                    look_in_children = false;
+                    node = parent;
+                    continue;
+                }
+            }
+            // Continue search from the root
+            else {
                 look_in_children = true;
                 self.root_node()
             };
@@ -578,15 +1728,118 @@ impl Document {
         }
     }
 
+    /// The order in which tab navigation visits focussable nodes: first the nodes with a positive `tabindex` in
+    /// increasing order of tabindex, and then the rest, with ties broken by document order. Only nodes within
+    /// the focus trap (if any) are included, and inert nodes are skipped. This doesn't change focus.
+    pub fn focus_order(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let root_id = self.focus_trap_root.unwrap_or(self.root_node().id);
+        self.walk_from(root_id, |node| {
+            if node.inert {
+                return WalkControl::SkipChildren;
+            }
+            if node.is_focussable() {
+                order.push(node.id);
+            }
+            WalkControl::Continue
+        });
+
+        // The sort is stable, so document order is kept within each tabindex
+        order.sort_by_key(|&node_id| {
+            let tabindex = self.nodes[node_id]
+                .attr(local_name!("tabindex"))
+                .and_then(|tabindex| tabindex.trim().parse::<i32>().ok());
+            match tabindex {
+                Some(tabindex) if tabindex > 0 => (false, tabindex),
+                _ => (true, 0),
+            }
+        });
+        order
+    }
+
     pub fn focus_next_node(&mut self) -> Option<usize> {
-        let focussed_node_id = self.get_focussed_node_id()?;
-        let id = self.next_node(&self.nodes[focussed_node_id], |node| node.is_focussable())?;
+        let order = self.focus_order();
+        // If focus is currently outside of the focus trap then start from the beginning of the trap
+        let focus_id = self.focus_node_id.filter(|&id| match self.focus_trap_root {
+            Some(root_id) => self.is_in_subtree(id, root_id),
+            None => true,
+        });
+        let id = match focus_id {
+            None => *order.first()?,
+            Some(focus_id) => match order.iter().position(|&id| id == focus_id) {
+                // Wrap around to the start of the order once we reach the end
+                Some(index) => order[(index + 1) % order.len()],
+                // A node which isn't in the order (such as one focussed by a click) continues in document order
+                None => self.next_node(&self.nodes[focus_id], |node| order.contains(&node.id))?,
+            },
+        };
+        self.focus_visible = true;
         self.set_focus_to(id);
+        self.scroll_into_view(id, ScrollAlign::Nearest);
         Some(id)
     }
 
+    /// Focus a node in response to a click or tap. Unlike keyboard focus this doesn't show a focus ring.
+    pub fn focus_by_pointer(&mut self, node_id: usize) -> bool {
+        self.focus_visible = false;
+        self.set_focus_to(node_id)
+    }
+
+    /// Whether the focussed node should show a focus ring (`:focus-visible`). This is the case when focus was last
+    /// moved with the keyboard but not when it was last moved by a pointer. Programmatic focus (`set_focus_to`)
+    /// keeps whichever applied before.
+    pub fn focus_visible(&self) -> bool {
+        self.focus_node_id.is_some() && self.focus_visible
+    }
+
+    /// Record that a node with an `autofocus` attribute has been inserted. If several nodes request
+    /// autofocus before the request is taken then the last one wins.
+    pub fn request_autofocus(&mut self, node_id: usize) {
+        self.autofocus_request = Some(node_id);
+    }
+
+    /// Take the pending autofocus request (if any). The caller is expected to focus the returned node
+    /// once it has finished inserting nodes.
+    pub fn take_autofocus_request(&mut self) -> Option<usize> {
+        self.autofocus_request.take()
+    }
+
+    /// Confine tab navigation to the focussable descendants of `root` (for example, for a modal dialog).
+    /// Passing `None` removes the trap, restoring tab navigation through the whole document.
+    pub fn set_focus_trap(&mut self, root: Option<usize>) {
+        self.focus_trap_root = root;
+    }
+
+    /// Whether `node_id` is `root_id` or one of its descendants
+    fn is_in_subtree(&self, node_id: usize, root_id: usize) -> bool {
+        node_id == root_id || self.is_descendant_of(node_id, root_id)
+    }
+
+    /// Whether `node_id` is a strict descendant of `ancestor_id`: a node is *not* a descendant of itself.
+    /// This walks up the tree from `node_id`, so it is proportional to the depth of the node.
+    pub fn is_descendant_of(&self, node_id: usize, ancestor_id: usize) -> bool {
+        let mut current = self.nodes.get(node_id).and_then(|node| node.parent);
+        while let Some(id) = current {
+            if id == ancestor_id {
+                return true;
+            }
+            current = self.nodes.get(id).and_then(|node| node.parent);
+        }
+        false
+    }
+
     pub fn set_focus_to(&mut self, focus_node_id: usize) -> bool {
+        // Inert nodes can't be focussed
+        if self.nodes[focus_node_id].inert {
+            return false;
+        }
+
+        let visible = self.focus_visible;
         if Some(focus_node_id) == self.focus_node_id {
+            // The node stays focussed, but how it was focussed may have changed
+            if self.nodes[focus_node_id].is_focus_visible() != visible {
+                self.snapshot_node_and(focus_node_id, |node| node.focus(visible));
+            }
             return false;
         }
 
@@ -598,7 +1851,7 @@ impl Document {
         }
 
         // Focus the new node
-        self.snapshot_node_and(focus_node_id, |node| node.focus());
+        self.snapshot_node_and(focus_node_id, |node| node.focus(visible));
 
         self.focus_node_id = Some(focus_node_id);
 
@@ -640,12 +1893,32 @@ impl Document {
     }
 
     pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.pending_viewport = None;
         self.viewport = viewport;
         self.set_stylist_device(self.viewport.make_device());
+
+        // Text is shaped at the viewport's scale
+        self.invalidate_all_layout();
+    }
+
+    /// Resize the viewport to `width` by `height` physical pixels. The resize is applied by the next `resolve`, so
+    /// calling this for every intermediate size during a drag-resize coalesces into a single restyle and relayout
+    /// at the final size.
+    pub fn resize_viewport(&mut self, width: u32, height: u32) {
+        let mut viewport = self
+            .pending_viewport
+            .take()
+            .unwrap_or_else(|| self.viewport.clone());
+        viewport.window_size = (width, height);
+        self.pending_viewport = Some(viewport);
+        self.mark_layout_dirty();
     }
 
     /// Update the device and reset the stylist to process the new size
     pub fn set_stylist_device(&mut self, device: Device) {
+        let viewport_size_changed =
+            device.au_viewport_size() != self.stylist.device().au_viewport_size();
+
         let origins = {
             let guard = &self.guard;
             let guards = StylesheetGuards {
@@ -655,6 +1928,15 @@ impl Document {
             self.stylist.set_device(device, &guards)
         };
         self.stylist.force_stylesheet_origins_dirty(origins);
+
+        // Viewport-relative lengths (vw, vh, vmin, vmax) are resolved against the device's viewport size
+        // during the cascade, so the whole tree needs recascading when the viewport is resized
+        if viewport_size_changed {
+            if let Some(root_id) = self.try_root_element().map(|node| node.id) {
+                self.nodes[root_id].set_restyle_hint(RestyleHint::recascade_subtree());
+            }
+        }
+
         self.clamp_scroll();
     }
 
@@ -668,6 +1950,13 @@ impl Document {
         resolve_layout_children_recursive(self, root_node_id);
 
         pub fn resolve_layout_children_recursive(doc: &mut Document, node_id: usize) {
+            // Subtrees which haven't been invalidated keep their existing layout children
+            if !doc.layout_invalidations.contains(&node_id)
+                && doc.nodes[node_id].layout_children.borrow().is_some()
+            {
+                return;
+            }
+
             doc.ensure_layout_children(node_id);
 
             let children = std::mem::take(&mut doc.nodes[node_id].children);
@@ -704,6 +1993,39 @@ impl Document {
         // taffy::print_tree(self, root_node_id)
     }
 
+    /// Compute the min-content and max-content widths of a node (its width when wrapped at every opportunity,
+    /// and when not wrapped at all). This assumes styles are resolved and layout has been run.
+    ///
+    /// Measuring re-breaks the lines of the node's text, so the node's layout is invalidated afterwards.
+    pub fn intrinsic_sizes(&mut self, node_id: usize) -> IntrinsicSizes {
+        use taffy::{LayoutPartialTree, RequestedAxis, RunMode, SizingMode};
+
+        let measure = |doc: &mut Document, available_width| {
+            let inputs = taffy::LayoutInput {
+                run_mode: RunMode::ComputeSize,
+                sizing_mode: SizingMode::InherentSize,
+                axis: RequestedAxis::Horizontal,
+                known_dimensions: taffy::Size::NONE,
+                parent_size: taffy::Size::NONE,
+                available_space: taffy::Size {
+                    width: available_width,
+                    height: AvailableSpace::MaxContent,
+                },
+                vertical_margins_are_collapsible: taffy::Line::FALSE,
+            };
+            doc.compute_child_layout(taffy::NodeId::from(node_id), inputs)
+                .size
+                .width
+        };
+
+        let sizes = IntrinsicSizes {
+            min: measure(self, AvailableSpace::MinContent),
+            max: measure(self, AvailableSpace::MaxContent),
+        };
+        self.invalidate_layout(node_id);
+        sizes
+    }
+
     pub fn set_document(&mut self, _content: String) {}
 
     pub fn add_element(&mut self) {}
@@ -749,21 +2071,609 @@ impl Document {
         self.clamp_scroll();
     }
 
-    /// Clamp scroll offset
-    fn clamp_scroll(&mut self) {
-        let content_height = self.root_element().final_layout.size.height as f64;
-        let viewport_height = self.stylist_device().au_viewport_size().height.to_f64_px();
+    /// Scroll the content under the point (`x`, `y`) by (`dx`, `dy`) CSS pixels, where positive deltas scroll
+    /// further into the content.
+    ///
+    /// The delta is applied to the nearest ancestor of the hit node which is a scroll container (`overflow: scroll`
+    /// or `overflow: auto`), clamped to its scrollable range. Any delta which it can't consume is chained to the
+    /// next scroll container up the tree, and finally to the document itself. Returns whether anything scrolled.
+    pub fn handle_wheel(&mut self, x: f32, y: f32, dx: f64, dy: f64) -> bool {
+        !self.apply_wheel(x, y, dx, dy).is_empty()
+    }
 
-        self.scroll_offset = self
-            .scroll_offset
-            .max(-(content_height - viewport_height))
-            .min(0.0);
+    /// Queue a wheel delta to be applied by the next call to `flush_scroll`. Deltas queued within a frame are
+    /// summed, so high-frequency wheel events scroll (and invalidate) the document once per frame rather than once
+    /// per event. The combined delta scrolls the content under the most recently queued position.
+    pub fn queue_scroll(&mut self, x: f32, y: f32, dx: f64, dy: f64) {
+        let delta = kurbo::Vec2::new(dx, dy);
+        let pending = self.pending_scroll.get_or_insert(PendingScroll {
+            x,
+            y,
+            delta: kurbo::Vec2::ZERO,
+        });
+        pending.x = x;
+        pending.y = y;
+        pending.delta += delta;
     }
 
-    pub fn visit<F>(&self, mut visit: F)
-    where
-        F: FnMut(usize, &Node),
-    {
+    /// Apply the wheel deltas queued by `queue_scroll` as a single scroll (see `handle_wheel`). Offsets are clamped
+    /// after the deltas have been combined, so deltas in opposite directions cancel out even at the edges of
+    /// a scroll container. Each scrolled container (or the root element, if the document scrolled) is marked as
+    /// changed. Returns whether anything scrolled.
+    pub fn flush_scroll(&mut self) -> bool {
+        let Some(PendingScroll { x, y, delta }) = self.pending_scroll.take() else {
+            return false;
+        };
+        let scrolled = self.apply_wheel(x, y, delta.x, delta.y);
+        self.changed.extend(scrolled.iter().copied());
+        !scrolled.is_empty()
+    }
+
+    /// The nearest ancestor of a node which is a scroll container (with `overflow: scroll` or `overflow: auto` on
+    /// either axis), or `None` if the node is only scrolled by the viewport.
+    ///
+    /// This is cached when the document is resolved, so it doesn't reflect changes to the tree or to styles since.
+    pub fn scroll_parent(&self, node_id: usize) -> Option<usize> {
+        self.nodes[node_id].scroll_parent
+    }
+
+    /// Recompute the cached scroll parent and inertness of every node, from the root down
+    fn update_inherited_state(&mut self) {
+        let mut stack = vec![(0, None, false)];
+        while let Some((node_id, scroll_parent, inert)) = stack.pop() {
+            let node = &mut self.nodes[node_id];
+            node.scroll_parent = scroll_parent;
+            node.inert = inert || node.has_inert_attribute();
+            let (scroll_x, scroll_y) = node.scrollable_axes();
+            let children_scroll_parent = if scroll_x || scroll_y {
+                Some(node_id)
+            } else {
+                scroll_parent
+            };
+            let inert = node.inert;
+            stack.extend(
+                node.children
+                    .iter()
+                    .map(|&child_id| (child_id, children_scroll_parent, inert)),
+            );
+        }
+    }
+
+    /// Remove focus from the focussed node if it has become inert. Returns whether focus was removed.
+    fn blur_inert_focus(&mut self) -> bool {
+        let Some(focus_node_id) = self.focus_node_id else {
+            return false;
+        };
+        if !self.nodes[focus_node_id].inert {
+            return false;
+        }
+        self.snapshot_node_and(focus_node_id, |node| node.blur());
+        self.focus_node_id = None;
+        true
+    }
+
+    /// How far a `position: sticky` element is shifted from its place in normal flow when painted, so that it stays
+    /// within the visible area of its scroll container (or the viewport) inset by its `top`, `right`, `bottom` and
+    /// `left` thresholds. The element is never shifted out of its parent's content box, so it scrolls away with its
+    /// parent. Zero for elements which aren't sticky, or which are within their sticky range.
+    ///
+    /// If `top` and `bottom` (or `left` and `right`) conflict then `top` (or `left`) wins.
+    pub fn sticky_offset(&self, node_id: usize) -> kurbo::Vec2 {
+        let node = &self.nodes[node_id];
+        let Some(insets) = node
+            .primary_styles()
+            .and_then(|style| crate::stylo_to_taffy::sticky_insets(&style))
+        else {
+            return kurbo::Vec2::ZERO;
+        };
+        let Some(parent_id) = node.parent else {
+            return kurbo::Vec2::ZERO;
+        };
+        let scroll_parent = node.scroll_parent;
+
+        // Rects are relative to the border box of the scroll container (or to the document), before scrolling
+        let mut origin = kurbo::Point::ZERO;
+        let mut current = node_id;
+        while Some(current) != scroll_parent {
+            let location = self.nodes[current].final_layout.location;
+            origin += kurbo::Vec2::new(location.x as f64, location.y as f64);
+            match self.nodes[current].parent {
+                Some(parent_id) => current = parent_id,
+                None => break,
+            }
+        }
+        let layout = node.final_layout;
+        let rect = kurbo::Rect::from_origin_size(
+            origin,
+            (layout.size.width as f64, layout.size.height as f64),
+        );
+
+        let parent = &self.nodes[parent_id];
+        let parent_layout = parent.final_layout;
+        let parent_origin =
+            origin - kurbo::Vec2::new(layout.location.x as f64, layout.location.y as f64);
+        let mut bounds = kurbo::Rect::new(
+            (parent_layout.border.left + parent_layout.padding.left) as f64,
+            (parent_layout.border.top + parent_layout.padding.top) as f64,
+            (parent_layout.size.width - parent_layout.border.right - parent_layout.padding.right)
+                as f64,
+            (parent_layout.size.height - parent_layout.border.bottom - parent_layout.padding.bottom)
+                as f64,
+        ) + parent_origin.to_vec2();
+        // A scroll container's content extends over its whole scrollable area
+        if scroll_parent == Some(parent_id) {
+            let max = parent.max_scroll_offset();
+            bounds.x1 += max.x;
+            bounds.y1 += max.y;
+        }
+
+        let visible = match scroll_parent {
+            Some(scroll_parent_id) => {
+                let container = &self.nodes[scroll_parent_id];
+                let layout = container.final_layout;
+                kurbo::Rect::new(
+                    layout.border.left as f64,
+                    layout.border.top as f64,
+                    (layout.size.width - layout.border.right - layout.scrollbar_size.width) as f64,
+                    (layout.size.height - layout.border.bottom - layout.scrollbar_size.height)
+                        as f64,
+                ) + container.scroll_offset
+            }
+            None => {
+                let size = self.stylist_device().au_viewport_size();
+                kurbo::Rect::from_origin_size(
+                    (0.0, -self.scroll_offset),
+                    (size.width.to_f64_px(), size.height.to_f64_px()),
+                )
+            }
+        };
+
+        // Percentages are relative to the size of the visible area
+        let resolve = |inset: taffy::LengthPercentageAuto, basis: f64| match inset {
+            taffy::LengthPercentageAuto::Length(length) => Some(length as f64),
+            taffy::LengthPercentageAuto::Percent(fraction) => Some(fraction as f64 * basis),
+            taffy::LengthPercentageAuto::Auto => None,
+        };
+        let mut offset = kurbo::Vec2::ZERO;
+        if let Some(right) = resolve(insets.right, visible.width()) {
+            offset.x = offset
+                .x
+                .min((visible.x1 - right - rect.x1).max(bounds.x0 - rect.x0));
+        }
+        if let Some(left) = resolve(insets.left, visible.width()) {
+            offset.x = offset
+                .x
+                .max((visible.x0 + left - rect.x0).min(bounds.x1 - rect.x1));
+        }
+        if let Some(bottom) = resolve(insets.bottom, visible.height()) {
+            offset.y = offset
+                .y
+                .min((visible.y1 - bottom - rect.y1).max(bounds.y0 - rect.y0));
+        }
+        if let Some(top) = resolve(insets.top, visible.height()) {
+            offset.y = offset
+                .y
+                .max((visible.y0 + top - rect.y0).min(bounds.y1 - rect.y1));
+        }
+        offset
+    }
+
+    /// Implementation of `handle_wheel`, returning the ids of the nodes which scrolled (the root element's id
+    /// stands for the document)
+    fn apply_wheel(&mut self, x: f32, y: f32, dx: f64, dy: f64) -> Vec<usize> {
+        let mut remaining = kurbo::Vec2::new(dx, dy);
+        let mut scrolled = Vec::new();
+
+        let mut next = self.hit(x, y).map(|hit| hit.node_id);
+        while let Some(node_id) = next {
+            if remaining == kurbo::Vec2::ZERO {
+                return scrolled;
+            }
+
+            let node = &self.nodes[node_id];
+            next = node.scroll_parent;
+            let (scroll_x, scroll_y) = node.scrollable_axes();
+            if !scroll_x && !scroll_y {
+                continue;
+            }
+
+            let max = node.max_scroll_offset();
+            let current = node.scroll_offset;
+            let mut target = current;
+            if scroll_x {
+                target.x = (current.x + remaining.x).clamp(0.0, max.x);
+            }
+            if scroll_y {
+                target.y = (current.y + remaining.y).clamp(0.0, max.y);
+            }
+
+            let applied = target - current;
+            if applied != kurbo::Vec2::ZERO {
+                self.nodes[node_id].scroll_offset = target;
+                remaining -= applied;
+                scrolled.push(node_id);
+            }
+        }
+
+        // Chain whatever is left to the document (which only scrolls vertically)
+        if remaining.y != 0.0 {
+            let before = self.scroll_offset;
+            self.scroll_offset -= remaining.y;
+            self.clamp_scroll();
+            if self.scroll_offset != before {
+                scrolled.push(self.root_element().id);
+            }
+        }
+
+        scrolled
+    }
+
+    /// Scroll each of a node's scrollable ancestors (and then the document) so that the node's border box is visible,
+    /// aligning it within each scroll container according to `align`. Scroll offsets are kept within the bounds
+    /// of each container's content, so the node may not end up exactly aligned.
+    ///
+    /// TODO: take transforms into account
+    pub fn scroll_into_view(&mut self, node_id: usize, align: ScrollAlign) {
+        let layout = self.nodes[node_id].final_layout;
+        let mut rect = kurbo::Rect::new(
+            0.0,
+            0.0,
+            layout.size.width as f64,
+            layout.size.height as f64,
+        );
+
+        // `rect` is relative to the border box of `current` (after it has been scrolled)
+        let mut current = node_id;
+        while let Some(parent_id) = self.nodes[current].parent {
+            let location = self.nodes[current].final_layout.location;
+            rect = rect + kurbo::Vec2::new(location.x as f64, location.y as f64);
+            current = parent_id;
+
+            let parent = &self.nodes[parent_id];
+            let (scroll_x, scroll_y) = parent.scrollable_axes();
+            if scroll_x || scroll_y {
+                let layout = parent.final_layout;
+                let max = parent.max_scroll_offset();
+                let mut offset = parent.scroll_offset;
+                if scroll_x {
+                    let client_width = layout.size.width
+                        - layout.border.left
+                        - layout.border.right
+                        - layout.scrollbar_size.width;
+                    offset.x = aligned_scroll_offset(
+                        offset.x,
+                        client_width as f64,
+                        rect.x0 - layout.border.left as f64,
+                        rect.width(),
+                        align,
+                    )
+                    .clamp(0.0, max.x);
+                }
+                if scroll_y {
+                    let client_height = layout.size.height
+                        - layout.border.top
+                        - layout.border.bottom
+                        - layout.scrollbar_size.height;
+                    offset.y = aligned_scroll_offset(
+                        offset.y,
+                        client_height as f64,
+                        rect.y0 - layout.border.top as f64,
+                        rect.height(),
+                        align,
+                    )
+                    .clamp(0.0, max.y);
+                }
+                self.nodes[parent_id].scroll_offset = offset;
+            }
+            rect = rect - self.nodes[parent_id].scroll_offset;
+        }
+
+        // Finally scroll the document (which only scrolls vertically, and whose offset is negative)
+        let viewport_height = self.stylist_device().au_viewport_size().height.to_f64_px();
+        self.scroll_offset = -aligned_scroll_offset(
+            -self.scroll_offset,
+            viewport_height,
+            rect.y0,
+            rect.height(),
+            align,
+        );
+        self.clamp_scroll();
+    }
+
+    /// Clamp scroll offset
+    fn clamp_scroll(&mut self) {
+        let content_height = self.root_element().final_layout.size.height as f64;
+        let viewport_height = self.stylist_device().au_viewport_size().height.to_f64_px();
+
+        self.scroll_offset = self
+            .scroll_offset
+            .max(-(content_height - viewport_height))
+            .min(0.0);
+    }
+
+    /// Compute the indices of the items of a virtualized list which are visible within `viewport`.
+    ///
+    /// The container declares its total number of items with `data-virtual-count` and the (fixed) height
+    /// of each item in CSS pixels with `data-virtual-item-height`. Items are assumed to be laid out
+    /// vertically from the top of the container's content box. `viewport` is in CSS pixels relative
+    /// to the window, and the document's scroll offset is taken into account. Containers which aren't
+    /// virtualized produce an empty range.
+    pub fn visible_range(&self, container_id: usize, viewport: kurbo::Rect) -> Range<usize> {
+        let Some(container) = self.get_node(container_id) else {
+            return 0..0;
+        };
+        let Some(element) = container.element_data() else {
+            return 0..0;
+        };
+        let (Some(item_count), Some(item_height)) = (
+            element.attr_parsed::<usize>(LocalName::from("data-virtual-count")),
+            element.attr_parsed::<f64>(LocalName::from("data-virtual-item-height")),
+        ) else {
+            return 0..0;
+        };
+
+        let layout = container.final_layout;
+        let content_top = container
+            .absolute_position(0.0, layout.border.top + layout.padding.top)
+            .y as f64;
+        let top = viewport.y0 - self.scroll_offset - content_top;
+        let bottom = viewport.y1 - self.scroll_offset - content_top;
+
+        visible_item_range(item_count, item_height, top, bottom)
+    }
+
+    /// Find all elements whose border box intersects `rect` (in CSS pixels relative to the top-left corner of the
+    /// document), in document order. Elements are only matched within the visible area of ancestors which clip their
+    /// overflow, and the content of scroll containers is matched where it has been scrolled to.
+    ///
+    /// This assumes layout is complete.
+    pub fn nodes_in_rect(&self, rect: kurbo::Rect) -> Vec<usize> {
+        let mut matches = Vec::new();
+        self.collect_nodes_in_rect(
+            self.root_element().id,
+            kurbo::Point::ZERO,
+            rect,
+            &mut matches,
+        );
+        matches
+    }
+
+    fn collect_nodes_in_rect(
+        &self,
+        node_id: usize,
+        parent_location: kurbo::Point,
+        rect: kurbo::Rect,
+        matches: &mut Vec<usize>,
+    ) {
+        let node = &self.nodes[node_id];
+        // Anonymous blocks aren't matched themselves, but position the inline boxes laid out within them
+        let is_anonymous = matches!(node.raw_dom_data, NodeData::AnonymousBlock(_));
+        if !node.is_element() && !is_anonymous {
+            return;
+        }
+
+        let layout = node.final_layout;
+        let origin =
+            parent_location + kurbo::Vec2::new(layout.location.x as f64, layout.location.y as f64);
+        let border_box = kurbo::Rect::from_origin_size(
+            origin,
+            (layout.size.width as f64, layout.size.height as f64),
+        );
+        if !is_anonymous && rects_overlap(border_box, rect) {
+            matches.push(node_id);
+        }
+
+        // Descendants of elements which clip their overflow are only visible within the element's border box
+        let is_clipped = node.style.overflow.x != taffy::Overflow::Visible
+            || node.style.overflow.y != taffy::Overflow::Visible;
+        let rect = if is_clipped {
+            rect.intersect(border_box)
+        } else {
+            rect
+        };
+        if is_clipped && !rects_overlap(border_box, rect) {
+            return;
+        }
+
+        // Children are positioned relative to the scrolled content
+        let children = node.layout_children.borrow().clone().unwrap_or_default();
+        for child_id in children {
+            self.collect_nodes_in_rect(child_id, origin - node.scroll_offset, rect, matches);
+        }
+    }
+
+    /// Map an x position (in CSS pixels, relative to the content box) within the first line of an inline root or text
+    /// input to the byte offset of the nearest cluster boundary in its text, for placing a caret.
+    ///
+    /// TODO: multiline text and right-to-left runs
+    pub fn text_offset_at(&self, node_id: usize, local_x: f32) -> Option<usize> {
+        let element = self.get_node(node_id)?.element_data()?;
+        let layout = match (element.inline_layout_data(), element.text_input_data()) {
+            (Some(inline_layout), _) => &inline_layout.layout,
+            (None, Some(input_data)) => input_data.editor.layout(),
+            (None, None) => return None,
+        };
+
+        let line = layout.lines().next()?;
+        let clusters: Vec<(Range<usize>, f32)> = line
+            .runs()
+            .flat_map(|run| {
+                run.visual_clusters()
+                    .map(|cluster| (cluster.text_range(), cluster.advance()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let x = local_x * self.viewport.scale() - line.metrics().offset;
+        Some(nearest_cluster_boundary(&clusters, x))
+    }
+
+    /// Select text within an inline root. `anchor` and `focus` are byte offsets into the inline root's text and the
+    /// selection spans the text between them (in either order). Replaces any existing selection.
+    pub fn set_text_selection(&mut self, node_id: usize, anchor: usize, focus: usize) {
+        self.text_selection = Some(TextSelection {
+            node_id,
+            anchor,
+            focus,
+        });
+    }
+
+    pub fn clear_text_selection(&mut self) {
+        self.text_selection = None;
+    }
+
+    pub fn text_selection(&self) -> Option<&TextSelection> {
+        self.text_selection.as_ref()
+    }
+
+    /// The focussed text input and the range of its text which is selected, if the text selection is within it.
+    /// The range is empty if the selection is collapsed, and `None` is returned if it isn't within the text.
+    fn focused_input_selection(&self) -> Option<(usize, Range<usize>)> {
+        let node_id = self.focus_node_id?;
+        let selection = self.text_selection.filter(|s| s.node_id == node_id)?;
+        let text = self.nodes[node_id]
+            .element_data()?
+            .text_input_data()?
+            .text();
+        let range = selection.range();
+        text.get(range.clone())?;
+        Some((node_id, range))
+    }
+
+    /// Insert `text` into the focussed text input, replacing its selection (if any), and collapse the selection to
+    /// the end of the inserted text. Returns whether a text input was focussed.
+    pub fn paste_text(&mut self, text: &str) -> bool {
+        let Some(node_id) = self.focus_node_id else {
+            return false;
+        };
+
+        if let Some((node_id, range)) = self.focused_input_selection() {
+            let mut value = self.input_value(node_id).unwrap();
+            value.replace_range(range.clone(), text);
+            self.set_input_value(node_id, &value);
+            let caret = range.start + text.len();
+            self.set_text_selection(node_id, caret, caret);
+            return true;
+        }
+
+        let node = &mut self.nodes[node_id];
+        let text_input_data = node
+            .raw_dom_data
+            .downcast_element_mut()
+            .and_then(|el| el.text_input_data_mut());
+        let Some(input_data) = text_input_data else {
+            return false;
+        };
+        // Without a document selection, committing text replaces the editor's own selection
+        let text_event = TextEvent::Ime(Ime::Commit(text.to_string()));
+        input_data.editor.text_event(&text_event);
+        self.changed.insert(node_id);
+        self.invalidate_layout(node_id);
+        self.scroll_text_input_to_cursor(node_id);
+        true
+    }
+
+    /// The text selected within the focussed text input (if any), for copying to the clipboard
+    pub fn copy_selection(&self) -> Option<String> {
+        let (node_id, range) = self.focused_input_selection()?;
+        if range.is_empty() {
+            return None;
+        }
+        Some(self.input_value(node_id)?[range].to_string())
+    }
+
+    /// Remove the text selected within the focussed text input (if any) and return it, for writing to the
+    /// clipboard. The selection is collapsed to where the removed text was.
+    pub fn cut_selection(&mut self) -> Option<String> {
+        let (node_id, range) = self.focused_input_selection()?;
+        let cut = self.copy_selection()?;
+        let mut value = self.input_value(node_id).unwrap();
+        value.replace_range(range.clone(), "");
+        self.set_input_value(node_id, &value);
+        self.set_text_selection(node_id, range.start, range.start);
+        Some(cut)
+    }
+
+    /// Take the text most recently cut or copied by a `cut` or `copy` event (if any). The caller is expected to
+    /// write it to the system clipboard.
+    pub fn take_clipboard_text(&mut self) -> Option<String> {
+        self.clipboard_text.take()
+    }
+
+    /// The rectangles to highlight for the text selected within an inline root, one per line the selection spans,
+    /// in CSS pixels relative to the node's content box. Returns an empty vec if the node has no selection, or its
+    /// selection is collapsed.
+    ///
+    /// TODO: right-to-left runs
+    pub fn selection_rects(&self, node_id: usize) -> Vec<kurbo::Rect> {
+        let Some(selection) = self
+            .text_selection
+            .as_ref()
+            .filter(|selection| selection.node_id == node_id && !selection.is_collapsed())
+        else {
+            return Vec::new();
+        };
+        let Some(text_layout) = self
+            .get_node(node_id)
+            .and_then(|node| node.element_data())
+            .and_then(|element| element.inline_layout_data())
+        else {
+            return Vec::new();
+        };
+
+        let scale = self.viewport.scale_f64();
+        let range = selection.range();
+        text_layout
+            .layout
+            .lines()
+            .filter_map(|line| {
+                let clusters: Vec<(Range<usize>, f32)> = line
+                    .runs()
+                    .flat_map(|run| {
+                        run.visual_clusters()
+                            .map(|cluster| (cluster.text_range(), cluster.advance()))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                let (x0, x1) = selected_extent(&clusters, &range)?;
+
+                let metrics = line.metrics();
+                let top = metrics.baseline - metrics.ascent - metrics.leading / 2.0;
+                let rect = kurbo::Rect::new(
+                    (metrics.offset + x0) as f64,
+                    top as f64,
+                    (metrics.offset + x1) as f64,
+                    (top + metrics.size()) as f64,
+                );
+                Some(rect.scale_from_origin(1.0 / scale))
+            })
+            .collect()
+    }
+
+    /// Scroll the text of a text input horizontally such that its caret is within the input's content box
+    fn scroll_text_input_to_cursor(&mut self, node_id: usize) {
+        let scale = self.viewport.scale();
+        let node = &mut self.nodes[node_id];
+        let layout = node.final_layout;
+        let content_width = layout.size.width
+            - layout.padding.left
+            - layout.padding.right
+            - layout.border.left
+            - layout.border.right;
+
+        if let Some(input_data) = node
+            .raw_dom_data
+            .downcast_element_mut()
+            .and_then(|el| el.text_input_data_mut())
+        {
+            input_data.scroll_to_cursor(content_width * scale);
+        }
+    }
+
+    pub fn visit<F>(&self, mut visit: F)
+    where
+        F: FnMut(usize, &Node),
+    {
         let mut stack = VecDeque::new();
         stack.push_front(0);
 
@@ -776,6 +2686,186 @@ impl Document {
             }
         }
     }
+
+    /// Visit `start` and its descendants depth-first in tree order. The return value of `visit` controls the rest of
+    /// the traversal: [`WalkControl::SkipChildren`] skips the descendants of the node just visited, and
+    /// [`WalkControl::Stop`] ends the traversal.
+    pub fn walk_from<F>(&self, start: usize, mut visit: F)
+    where
+        F: FnMut(&Node) -> WalkControl,
+    {
+        let mut stack = vec![start];
+        while let Some(node_key) = stack.pop() {
+            let node = &self.nodes[node_key];
+            match visit(node) {
+                WalkControl::Continue => stack.extend(node.children.iter().rev()),
+                WalkControl::SkipChildren => {}
+                WalkControl::Stop => return,
+            }
+        }
+    }
+}
+
+/// The text offset of the cluster boundary nearest to `x`, given the byte range and advance of each
+/// cluster of a left-to-right line in visual order
+fn nearest_cluster_boundary(clusters: &[(Range<usize>, f32)], x: f32) -> usize {
+    let mut edge = 0.0;
+    let mut offset = 0;
+    for (range, advance) in clusters {
+        if x < edge + advance / 2.0 {
+            return range.start;
+        }
+        edge += advance;
+        offset = range.end;
+    }
+    offset
+}
+
+/// The horizontal extent of the clusters of a left-to-right line which intersect the text `range`, relative to the
+/// start of the line, given the byte range and advance of each cluster in visual order
+fn selected_extent(clusters: &[(Range<usize>, f32)], range: &Range<usize>) -> Option<(f32, f32)> {
+    let mut edge = 0.0;
+    let mut extent: Option<(f32, f32)> = None;
+    for (cluster_range, advance) in clusters {
+        if cluster_range.start < range.end && range.start < cluster_range.end {
+            let start = extent.map_or(edge, |(start, _)| start);
+            extent = Some((start, edge + advance));
+        }
+        edge += advance;
+    }
+    extent
+}
+
+/// The indices of a longest strictly increasing subsequence of `values`, in increasing order
+fn longest_increasing_subsequence(values: &[usize]) -> Vec<usize> {
+    // `tails[len]` is the index of the smallest value which ends an increasing subsequence of length `len + 1`
+    let mut tails: Vec<usize> = Vec::new();
+    let mut predecessors: Vec<Option<usize>> = vec![None; values.len()];
+    for (idx, &value) in values.iter().enumerate() {
+        let len = tails.partition_point(|&tail| values[tail] < value);
+        predecessors[idx] = len.checked_sub(1).map(|prev| tails[prev]);
+        if len == tails.len() {
+            tails.push(idx);
+        } else {
+            tails[len] = idx;
+        }
+    }
+
+    let mut subsequence = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(idx) = current {
+        subsequence.push(idx);
+        current = predecessors[idx];
+    }
+    subsequence.reverse();
+    subsequence
+}
+
+/// Whether two rectangles share some area (rectangles which merely touch don't overlap)
+fn rects_overlap(a: kurbo::Rect, b: kurbo::Rect) -> bool {
+    a.x0 < b.x1 && b.x0 < a.x1 && a.y0 < b.y1 && b.y0 < a.y1
+}
+
+/// The range of fixed-height items which intersect the vertical span `top..bottom`
+/// (relative to the top of the first item)
+fn visible_item_range(item_count: usize, item_height: f64, top: f64, bottom: f64) -> Range<usize> {
+    if item_count == 0 || item_height <= 0.0 || bottom <= top {
+        return 0..0;
+    }
+
+    let start = (top / item_height).floor().max(0.0) as usize;
+    let end = (bottom / item_height).ceil().max(0.0) as usize;
+    start.min(item_count)..end.min(item_count)
+}
+
+/// Wheel deltas queued by `Document::queue_scroll`, and the position of the most recent one
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PendingScroll {
+    x: f32,
+    y: f32,
+    delta: kurbo::Vec2,
+}
+
+/// The changes made by `Document::reconcile_children_by_key`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyedChanges {
+    /// Nodes which were inserted for new keys
+    pub inserted: Vec<usize>,
+    /// Existing children which were moved to a new position relative to the other kept children
+    pub moved: Vec<usize>,
+    /// Children which were removed (along with their descendants)
+    pub removed: Vec<usize>,
+    /// Nodes given for existing keys which were removed in favour of the existing children
+    pub discarded: Vec<usize>,
+}
+
+/// What [`Document::walk_from`] should do after visiting a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkControl {
+    /// Go on to visit the node's children
+    Continue,
+    /// Don't visit the node's descendants, but go on to its next sibling
+    SkipChildren,
+    /// End the traversal
+    Stop,
+}
+
+/// A selection of the text within an inline root (or a text node), as byte offsets into its text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextSelection {
+    pub node_id: usize,
+    /// Where the selection started
+    pub anchor: usize,
+    /// Where the selection ends (the end which moves when the selection is extended)
+    pub focus: usize,
+}
+
+impl TextSelection {
+    /// The selected text range, with its start before its end
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.focus)..self.anchor.max(self.focus)
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.focus
+    }
+}
+
+/// Where to position a node within its scroll containers when scrolling it into view (see
+/// [`Document::scroll_into_view`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScrollAlign {
+    /// Scroll as little as possible: not at all if the node is already visible, otherwise align whichever edge
+    /// is nearest
+    #[default]
+    Nearest,
+    /// Align the start (top or left) edge of the node with the start of the container
+    Start,
+    /// Centre the node within the container
+    Center,
+    /// Align the end (bottom or right) edge of the node with the end of the container
+    End,
+}
+
+/// The scroll offset (along one axis) which positions the span `start..start + len` of a container's content
+/// within its `view_len`-long visible area according to `align`, given the container's `current` offset
+fn aligned_scroll_offset(
+    current: f64,
+    view_len: f64,
+    start: f64,
+    len: f64,
+    align: ScrollAlign,
+) -> f64 {
+    let end = start + len;
+    match align {
+        ScrollAlign::Start => start,
+        ScrollAlign::End => end - view_len,
+        ScrollAlign::Center => start + (len - view_len) / 2.0,
+        ScrollAlign::Nearest if start < current => start,
+        // Spans longer than the view are aligned to their start
+        ScrollAlign::Nearest if end > current + view_len => (end - view_len).min(start),
+        ScrollAlign::Nearest => current,
+    }
 }
 
 impl AsRef<Document> for Document {
@@ -789,3 +2879,1525 @@ impl AsMut<Document> for Document {
         self
     }
 }
+
+#[test]
+fn focus_trap_confines_tab_navigation() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <button id="before"></button>
+        <div id="trap"><button id="first"></button><button id="last"></button></div>
+        <button id="after"></button>
+    </body></html>"#;
+    let mut doc = parse(html);
+
+    doc.set_focus_trap(Some(by_id(&doc, "trap")));
+    doc.set_focus_to(by_id(&doc, "last"));
+    assert_eq!(doc.focus_next_node(), Some(by_id(&doc, "first")));
+    assert_eq!(doc.get_focus_node_id(), Some(by_id(&doc, "first")));
+
+    doc.set_focus_trap(None);
+    doc.set_focus_to(by_id(&doc, "last"));
+    assert_eq!(doc.focus_next_node(), Some(by_id(&doc, "after")));
+}
+
+#[test]
+fn visible_range_of_scrolled_virtual_list() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="list" data-virtual-count="1000" data-virtual-item-height="20"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    let list_id = by_id(&doc, "list");
+    doc.nodes[list_id].final_layout.location.y = 100.0;
+    doc.scroll_offset = -250.0;
+
+    let viewport = kurbo::Rect::new(0.0, 0.0, 800.0, 600.0);
+    assert_eq!(doc.visible_range(list_id, viewport), 7..38);
+
+    assert_eq!(visible_item_range(10, 20.0, -500.0, 100.0), 0..5);
+    assert_eq!(visible_item_range(10, 20.0, 150.0, 900.0), 7..10);
+    assert_eq!(visible_item_range(10, 20.0, 300.0, 900.0), 10..10);
+}
+
+#[test]
+fn absolute_child_is_positioned_by_its_insets() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="parent" style="position: relative; width: 200px; height: 200px"><div id="child" style="position: absolute; top: 10px; left: 20px; width: 5px; height: 5px"></div></div></body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let location = doc.nodes[by_id(&doc, "child")].final_layout.location;
+    assert_eq!((location.x, location.y), (20.0, 10.0));
+}
+
+#[test]
+fn nodes_in_rect_selects_overlapping_grid_cells() {
+    use crate::testing::{by_id, parse};
+
+    // A 2x2 grid of 10px squares
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="grid" style="display: grid; grid-template-columns: 10px 10px; grid-template-rows: 10px 10px; width: 20px">
+            <div id="a"></div><div id="b"></div><div id="c"></div><div id="d"></div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let grid_id = by_id(&doc, "grid");
+
+    // A rectangle partially overlapping the two right-hand cells
+    let rect = kurbo::Rect::new(15.0, 5.0, 30.0, 15.0);
+    let cells: Vec<usize> = doc
+        .nodes_in_rect(rect)
+        .into_iter()
+        .filter(|node_id| doc.nodes[*node_id].parent == Some(grid_id))
+        .collect();
+    assert_eq!(cells, vec![by_id(&doc, "b"), by_id(&doc, "d")]);
+
+    // Cells outside of a clipping ancestor are not matched
+    doc.nodes[grid_id].final_layout.size.width = 10.0;
+    doc.nodes[grid_id].style.overflow = taffy::Point {
+        x: taffy::Overflow::Hidden,
+        y: taffy::Overflow::Hidden,
+    };
+    assert!(!doc.nodes_in_rect(rect).contains(&by_id(&doc, "b")));
+}
+
+#[test]
+fn nodes_in_rect_follows_scrolled_content_and_anonymous_blocks() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="scroller" style="overflow: hidden; width: 100px; height: 50px">
+            <div id="spacer" style="height: 100px"></div>
+            <div id="below" style="height: 20px"></div>
+        </div>
+        <div id="mixed" style="width: 100px; font-size: 16px; line-height: 20px">
+            <div style="height: 30px"></div>
+            Text <span id="chip" style="display: inline-block; width: 20px; height: 10px"></span>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    // Once scrolled by 60px, the item below the spacer is visible at the bottom of the scroll container
+    let visible = kurbo::Rect::new(0.0, 40.0, 100.0, 50.0);
+    assert!(!doc.nodes_in_rect(visible).contains(&by_id(&doc, "below")));
+    doc.nodes[by_id(&doc, "scroller")].scroll_offset = kurbo::Vec2::new(0.0, 60.0);
+    let matches = doc.nodes_in_rect(visible);
+    assert!(matches.contains(&by_id(&doc, "below")));
+    assert!(!matches.contains(&by_id(&doc, "spacer")));
+
+    // The inline block is positioned within the anonymous block wrapping the text, on the line 30px below the top
+    // of its parent
+    let top = doc.nodes[by_id(&doc, "mixed")].final_layout.location.y as f64 + 30.0;
+    let line = kurbo::Rect::new(0.0, top, 100.0, top + 20.0);
+    let matches = doc.nodes_in_rect(line);
+    assert!(matches.contains(&by_id(&doc, "chip")));
+    assert!(matches
+        .iter()
+        .all(|&node_id| doc.nodes[node_id].is_element()));
+}
+
+#[test]
+fn input_value_round_trips() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><input id="name" value="initial"></body></html>"#;
+    let mut doc = parse(html);
+    let input_id = by_id(&doc, "name");
+
+    assert_eq!(doc.input_value(input_id).as_deref(), Some("initial"));
+    doc.set_input_value(input_id, "updated");
+    assert_eq!(doc.input_value(input_id).as_deref(), Some("updated"));
+    assert_eq!(doc.nodes[input_id].attrs().unwrap().len(), 2);
+
+    // Shrinking the text clamps the selection to its new length
+    doc.set_text_selection(input_id, 2, 7);
+    doc.set_input_value(input_id, "up");
+    assert_eq!(doc.input_value(input_id).as_deref(), Some("up"));
+    assert_eq!(doc.text_selection().unwrap().range(), 2..2);
+
+    // The caret is moved back to the end of the text, or to the start of a character it now falls within
+    doc.set_input_value(input_id, "hello");
+    doc.set_text_selection(input_id, 5, 5);
+    doc.set_input_value(input_id, "h\u{e9}");
+    assert_eq!(doc.text_selection().unwrap().range(), 3..3);
+    doc.set_text_selection(input_id, 2, 2);
+    doc.set_input_value(input_id, "\u{e9}\u{e9}");
+    assert_eq!(doc.text_selection().unwrap().range(), 2..2);
+    doc.set_text_selection(input_id, 1, 1);
+    doc.set_input_value(input_id, "\u{e9}");
+    assert_eq!(doc.text_selection().unwrap().range(), 0..0);
+}
+
+#[test]
+fn deferred_layout_runs_once_on_flush() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="box"></div></body></html>"#;
+    let mut doc = parse(html);
+    let box_id = by_id(&doc, "box");
+    doc.resolve();
+    assert!(!doc.needs_layout());
+
+    doc.set_defer_layout(true);
+    for _ in 0..50 {
+        doc.toggle_attribute(box_id, QualName::new(None, ns!(), local_name!("hidden")));
+        doc.resolve();
+        assert!(doc.needs_layout());
+    }
+
+    assert!(doc.flush_layout());
+    assert!(!doc.needs_layout());
+    assert!(!doc.flush_layout());
+}
+
+#[test]
+fn child_count_changes_are_reported_once_per_batch() {
+    use crate::testing::{by_id, parse};
+
+    let html =
+        r#"<!DOCTYPE html><html><body><ul id="list"><li id="first"></li></ul></body></html>"#;
+    let mut doc = parse(html);
+    let list_id = by_id(&doc, "list");
+    let first_id = by_id(&doc, "first");
+    assert_eq!(doc.child_count(list_id), 1);
+
+    // Appending a sibling after the first item adds a child to the list
+    let second_id = doc.create_node(NodeData::new_element("li"));
+    doc.append(first_id, &[second_id]);
+    assert_eq!(doc.child_count(list_id), 2);
+    assert_eq!(doc.take_child_count_changes(), vec![list_id]);
+    assert!(doc.take_child_count_changes().is_empty());
+
+    // Adding and then removing a child leaves the count unchanged
+    let third_id = doc.create_node(NodeData::new_element("li"));
+    doc.insert_before(first_id, &[third_id]);
+    doc.remove_node(third_id);
+    assert!(doc.take_child_count_changes().is_empty());
+
+    doc.remove_node(second_id);
+    assert_eq!(doc.take_child_count_changes(), vec![list_id]);
+}
+
+#[test]
+fn synthetic_events_bubble_through_listeners() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="outer"><div id="middle"><div id="inner"></div></div></div></body></html>"#;
+    let mut doc = parse(html);
+
+    doc.nodes[by_id(&doc, "inner")].add_event_listener("longpress");
+    doc.nodes[by_id(&doc, "outer")].add_event_listener("longpress");
+
+    // Unregistered events are not dispatched
+    assert!(doc
+        .dispatch_synthetic(by_id(&doc, "inner"), "longpress")
+        .is_empty());
+
+    doc.register_synthetic_event(String::from("longpress"));
+    assert_eq!(
+        doc.dispatch_synthetic(by_id(&doc, "inner"), "longpress"),
+        vec![by_id(&doc, "inner"), by_id(&doc, "outer")]
+    );
+}
+
+#[test]
+fn reserved_nodes_do_not_reallocate() {
+    let mut doc = Document::new(Viewport::new(800, 600, 1.0));
+    doc.reserve_nodes(1000);
+    let capacity = doc.nodes.capacity();
+
+    let ids: Vec<usize> = (0..1000)
+        .map(|i| doc.create_text_node(&i.to_string()))
+        .collect();
+    assert_eq!(doc.nodes.capacity(), capacity);
+    assert_eq!(ids.len(), 1000);
+    assert!(ids.iter().all(|&id| doc.nodes[id].is_text_node()));
+}
+
+#[test]
+fn paused_changes_are_resolved_on_resume() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="a"></div><div id="b"></div></body></html>"#;
+    let mut doc = parse(html);
+    doc.add_stylesheet("[data-hidden] { display: none }");
+    doc.resolve();
+    let is_hidden = |doc: &Document, name: &str| {
+        doc.nodes[by_id(doc, name)].style.display == taffy::Display::None
+    };
+    let data_hidden = || QualName::new(None, ns!(), LocalName::from("data-hidden"));
+
+    doc.pause_reactivity();
+    for name in ["a", "b"] {
+        doc.toggle_attribute(by_id(&doc, name), data_hidden());
+        doc.resolve();
+    }
+    assert!(!is_hidden(&doc, "a") && !is_hidden(&doc, "b"));
+
+    doc.resume_reactivity();
+    assert!(is_hidden(&doc, "a") && is_hidden(&doc, "b"));
+}
+
+#[test]
+fn text_offsets_snap_to_cluster_boundaries() {
+    // Each character of "héllo" is a 10px wide cluster, and "é" is two bytes long
+    let text = "héllo";
+    let clusters: Vec<(Range<usize>, f32)> = text
+        .char_indices()
+        .map(|(start, c)| (start..start + c.len_utf8(), 10.0))
+        .collect();
+
+    assert_eq!(nearest_cluster_boundary(&clusters, -5.0), 0);
+    assert_eq!(nearest_cluster_boundary(&clusters, 4.0), 0);
+    assert_eq!(nearest_cluster_boundary(&clusters, 14.0), 1);
+    assert_eq!(nearest_cluster_boundary(&clusters, 16.0), 3);
+    assert_eq!(nearest_cluster_boundary(&clusters, 1000.0), text.len());
+    assert!(text.is_char_boundary(nearest_cluster_boundary(&clusters, 16.0)));
+}
+
+#[test]
+fn selected_extent_covers_intersecting_clusters() {
+    let clusters: Vec<(Range<usize>, f32)> = "hello world"
+        .char_indices()
+        .map(|(start, c)| (start..start + c.len_utf8(), 10.0))
+        .collect();
+
+    assert_eq!(selected_extent(&clusters, &(0..5)), Some((0.0, 50.0)));
+    assert_eq!(selected_extent(&clusters, &(6..8)), Some((60.0, 80.0)));
+    assert_eq!(selected_extent(&clusters, &(20..25)), None);
+}
+
+#[test]
+fn selection_rects_cover_each_selected_line() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <p id="short">Hello world</p>
+        <p id="wrapped" style="width: 60px">aaaa bbbb cccc dddd</p>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let short = by_id(&doc, "short");
+    let wrapped = by_id(&doc, "wrapped");
+
+    // Collapsed selections have no highlight
+    doc.set_text_selection(short, 3, 3);
+    assert!(doc.selection_rects(short).is_empty());
+
+    // "world" is within the first (and only) line
+    doc.set_text_selection(short, 11, 6);
+    let rects = doc.selection_rects(short);
+    assert_eq!(rects.len(), 1);
+    assert!(rects[0].x0 > 0.0 && rects[0].width() > 0.0 && rects[0].height() > 0.0);
+    // The selection only applies to its own node
+    assert!(doc.selection_rects(wrapped).is_empty());
+
+    // "bbbb cccc" spans the first two lines of the wrapped paragraph
+    doc.set_text_selection(wrapped, 5, 14);
+    let rects = doc.selection_rects(wrapped);
+    assert_eq!(rects.len(), 2);
+    assert!(rects[0].y1 <= rects[1].y0);
+    assert_eq!(rects[1].x0, 0.0);
+}
+
+#[test]
+fn wheel_scrolling_chains_to_the_parent_at_boundaries() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="outer" style="overflow: auto; width: 100px; height: 100px">
+            <div id="inner" style="overflow: auto; width: 100px; height: 50px">
+                <div style="height: 100px"></div>
+            </div>
+            <div style="height: 150px"></div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let outer = by_id(&doc, "outer");
+    let inner = by_id(&doc, "inner");
+    let offset = |doc: &Document, id: usize| doc.nodes[id].scroll_offset.y;
+
+    // The inner container consumes the delta until it reaches the end of its content
+    assert!(doc.handle_wheel(10.0, 10.0, 0.0, 30.0));
+    assert_eq!((offset(&doc, inner), offset(&doc, outer)), (30.0, 0.0));
+
+    // Then the rest chains to the outer container
+    assert!(doc.handle_wheel(10.0, 10.0, 0.0, 40.0));
+    assert_eq!((offset(&doc, inner), offset(&doc, outer)), (50.0, 20.0));
+
+    // Scrolling back consumes the inner container's offset first
+    assert!(doc.handle_wheel(10.0, 10.0, 0.0, -60.0));
+    assert_eq!((offset(&doc, inner), offset(&doc, outer)), (0.0, 10.0));
+
+    // Nothing can scroll past the top of the document
+    assert!(doc.handle_wheel(10.0, 10.0, 0.0, -20.0));
+    assert!(!doc.handle_wheel(10.0, 10.0, 0.0, -20.0));
+    assert_eq!((offset(&doc, inner), offset(&doc, outer)), (0.0, 0.0));
+}
+
+#[test]
+fn queued_wheel_deltas_are_coalesced_and_clamped_once() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="list" style="overflow: auto; width: 100px; height: 50px">
+            <div style="height: 100px"></div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let list = by_id(&doc, "list");
+    doc.changed.clear();
+
+    // Nothing scrolls until the queue is flushed
+    doc.queue_scroll(10.0, 10.0, 0.0, 40.0);
+    doc.queue_scroll(10.0, 10.0, 0.0, 40.0);
+    doc.queue_scroll(10.0, 10.0, 0.0, -20.0);
+    assert_eq!(doc.nodes[list].scroll_offset.y, 0.0);
+    assert!(doc.changed.is_empty());
+
+    // The combined delta of 60px is clamped to the 50px scroll range. Clamping each delta would have
+    // left the list at 30px.
+    assert!(doc.flush_scroll());
+    assert_eq!(doc.nodes[list].scroll_offset.y, 50.0);
+    assert_eq!(doc.changed, HashSet::from([list]));
+
+    // The queue is empty after flushing
+    assert!(!doc.flush_scroll());
+}
+
+#[test]
+fn viewport_resizes_are_applied_once_by_resolve() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="half" style="width: 50vw; height: 10px"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let half = by_id(&doc, "half");
+    assert_eq!(doc.nodes[half].final_layout.size.width, 400.0);
+
+    // Intermediate sizes aren't applied
+    doc.resize_viewport(700, 600);
+    doc.resize_viewport(640, 600);
+    doc.resize_viewport(500, 400);
+    assert!(doc.needs_layout());
+    assert_eq!(doc.viewport.window_size, (800, 600));
+    assert_eq!(doc.nodes[half].final_layout.size.width, 400.0);
+
+    // A single resolve restyles viewport-relative lengths and relayouts at the final size
+    doc.resolve();
+    assert_eq!(doc.viewport.window_size, (500, 400));
+    assert_eq!(doc.nodes[half].final_layout.size.width, 250.0);
+    assert!(doc.pending_viewport.is_none());
+}
+
+#[test]
+fn changing_text_only_relayouts_its_ancestors() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="a"><p id="pa">Hello</p></div><div id="b"><p id="pb">World</p></div></body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let initial_count = doc.relayout_count();
+    assert!(initial_count > 0);
+
+    // Nothing has changed, so nothing is laid out
+    doc.resolve();
+    assert_eq!(doc.relayout_count(), 0);
+
+    let pa = by_id(&doc, "pa");
+    let text_id = doc.nodes[pa].children[0];
+    let NodeData::Text(ref mut text) = doc.nodes[text_id].raw_dom_data else {
+        panic!("expected a text node");
+    };
+    text.content = String::from("Hello there");
+    doc.invalidate_layout(text_id);
+    doc.resolve();
+
+    for name in ["pa", "a"] {
+        assert!(
+            doc.relayout_nodes.contains(&by_id(&doc, name)),
+            "{name} should be relaid out"
+        );
+    }
+    for name in ["pb", "b"] {
+        assert!(
+            !doc.relayout_nodes.contains(&by_id(&doc, name)),
+            "{name} should not be relaid out"
+        );
+    }
+    assert!(doc.relayout_count() < initial_count);
+}
+
+#[test]
+fn key_repeats_are_delivered_to_the_focussed_node() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><input id="input" type="text"></body></html>"#;
+    let mut doc = parse(html);
+    let input = by_id(&doc, "input");
+    doc.set_key_repeat_timing(Duration::from_millis(100), Duration::from_millis(20));
+
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    assert!(doc.key_down(KeyCode::ArrowLeft, start));
+
+    // Repeats are dropped while nothing is focussed
+    assert!(doc.tick_key_repeat(at(100)).is_empty());
+
+    doc.set_focus_to(input);
+    assert!(doc.tick_key_repeat(at(110)).is_empty());
+    assert_eq!(
+        doc.tick_key_repeat(at(130)),
+        vec![(input, KeyCode::ArrowLeft)]
+    );
+
+    doc.key_up(KeyCode::ArrowLeft);
+    assert!(doc.tick_key_repeat(at(200)).is_empty());
+}
+
+#[test]
+fn descendant_checks_are_strict() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="outer"><p id="a"><span id="deep"></span></p><p id="b"></p></div></body></html>"#;
+    let doc = parse(html);
+
+    assert!(doc.is_descendant_of(by_id(&doc, "a"), by_id(&doc, "outer")));
+    assert!(doc.is_descendant_of(by_id(&doc, "deep"), by_id(&doc, "outer")));
+    assert!(doc.is_descendant_of(by_id(&doc, "deep"), doc.root_element().id));
+    assert!(!doc.is_descendant_of(by_id(&doc, "outer"), by_id(&doc, "deep")));
+    assert!(!doc.is_descendant_of(by_id(&doc, "b"), by_id(&doc, "a")));
+    assert!(!doc.is_descendant_of(by_id(&doc, "deep"), by_id(&doc, "b")));
+    assert!(!doc.is_descendant_of(by_id(&doc, "outer"), by_id(&doc, "outer")));
+}
+
+#[test]
+fn set_text_fast_only_updates_text_nodes() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><p id="log">first</p></body></html>"#;
+    let mut doc = parse(html);
+    let log = by_id(&doc, "log");
+    let text_id = doc.nodes[log].children[0];
+    doc.changed.clear();
+
+    doc.set_text_fast(text_id, "second");
+    assert_eq!(doc.nodes[log].text_content(), "second");
+    assert!(doc.changed.contains(&text_id));
+    assert!(doc.layout_invalidations.contains(&text_id));
+
+    // Elements are left untouched
+    doc.set_text_fast(log, "third");
+    assert_eq!(doc.nodes[log].text_content(), "second");
+    assert!(doc.nodes[log].is_element());
+}
+
+#[test]
+fn intrinsic_sizes_of_text() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="words">aaa aaaaaaaaa</div>
+        <div id="word">aaaaaaaaa</div>
+        <div style="display: flex"><div id="unwrapped">aaa aaaaaaaaa</div></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let words = doc.intrinsic_sizes(by_id(&doc, "words"));
+    let word = doc.intrinsic_sizes(by_id(&doc, "word"));
+
+    // A single word can't be wrapped
+    assert_eq!(word.min, word.max);
+    // The min-content width is that of the widest word, and the max-content width that of the whole line (which a
+    // flex item is given when there is room for it)
+    assert_eq!(words.min, word.min);
+    let unwrapped = &doc.nodes[by_id(&doc, "unwrapped")].final_layout;
+    assert_eq!(words.max, unwrapped.size.width);
+    assert!(words.max > word.max);
+}
+
+#[test]
+fn user_data_is_dropped_with_its_node() {
+    use crate::testing::{by_id, parse};
+    use std::sync::Arc;
+
+    let html = r#"<!DOCTYPE html><html><body><div id="outer"><canvas id="canvas"></canvas></div></body></html>"#;
+    let mut doc = parse(html);
+    let canvas = by_id(&doc, "canvas");
+
+    let handle = Arc::new(42u32);
+    doc.set_user_data(canvas, Box::new(handle.clone()));
+    assert_eq!(
+        doc.user_data::<Arc<u32>>(canvas).map(|data| **data),
+        Some(42)
+    );
+    assert!(doc.user_data::<String>(canvas).is_none());
+    assert_eq!(Arc::strong_count(&handle), 2);
+
+    // Removing an ancestor drops the data of its descendants
+    doc.remove_node(by_id(&doc, "outer"));
+    assert_eq!(Arc::strong_count(&handle), 1);
+    assert!(doc.user_data::<Arc<u32>>(canvas).is_none());
+}
+
+#[test]
+fn auto_overflow_only_reserves_scrollbar_space_when_content_overflows() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="fits" style="overflow-y: auto; width: 100px; height: 50px"><div id="short" style="height: 50px"></div></div>
+        <div id="overflows" style="overflow-y: auto; width: 100px; height: 50px"><div id="tall" style="height: 80px"></div></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.set_scrollbar_width(10.0);
+    doc.resolve();
+    let node = |name: &str| &doc.nodes[by_id(&doc, name)];
+
+    assert!(!node("fits").auto_scrollbars.y);
+    assert_eq!(node("short").final_layout.size.width, 100.0);
+
+    assert!(node("overflows").auto_scrollbars.y);
+    assert!(!node("overflows").auto_scrollbars.x);
+    assert_eq!(node("tall").final_layout.size.width, 90.0);
+}
+
+#[test]
+fn a_scrollbar_in_one_axis_can_add_a_scrollbar_in_the_other() {
+    use crate::testing::{by_id, parse};
+
+    // The content only overflows horizontally once the vertical scrollbar takes up 10px of the width
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="scroller" style="overflow: auto; width: 100px; height: 50px"><div style="width: 95px; height: 80px"></div></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.set_scrollbar_width(10.0);
+    doc.resolve();
+    let scroller = &doc.nodes[by_id(&doc, "scroller")];
+
+    assert!(scroller.auto_scrollbars.y);
+    assert!(scroller.auto_scrollbars.x);
+    assert_eq!(scroller.final_layout.scrollbar_size.width, 10.0);
+    assert_eq!(scroller.final_layout.scrollbar_size.height, 10.0);
+}
+
+#[test]
+fn collect_garbage_removes_only_detached_nodes() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="list"></div></body></html>"#;
+    let mut doc = parse(html);
+    let list_id = by_id(&doc, "list");
+    doc.collect_garbage();
+
+    let attached = doc.create_text_node("attached");
+    doc.nodes[list_id].children.push(attached);
+    doc.nodes[attached].parent = Some(list_id);
+    let detached = doc.create_text_node("detached");
+    let node_count = doc.nodes.len();
+
+    assert_eq!(doc.collect_garbage(), 1);
+    assert_eq!(doc.nodes.len(), node_count - 1);
+    assert!(doc.get_node(detached).is_none());
+    assert!(doc.get_node(attached).is_some());
+    assert!(doc.get_node(list_id).is_some());
+    assert_eq!(doc.collect_garbage(), 0);
+}
+
+#[test]
+fn focus_is_only_visible_after_keyboard_navigation() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><button id="first">1</button><button id="second">2</button></body></html>"#;
+    let mut doc = parse(html);
+    assert!(!doc.focus_visible());
+
+    doc.focus_by_pointer(by_id(&doc, "first"));
+    assert!(!doc.focus_visible());
+    assert!(!doc.nodes[by_id(&doc, "first")].is_focus_visible());
+
+    // Tab
+    assert_eq!(doc.focus_next_node(), Some(by_id(&doc, "second")));
+    assert!(doc.focus_visible());
+    assert!(doc.nodes[by_id(&doc, "second")].is_focus_visible());
+
+    // Clicking the already focussed node hides the focus ring
+    doc.focus_by_pointer(by_id(&doc, "second"));
+    assert!(!doc.focus_visible());
+    assert!(!doc.nodes[by_id(&doc, "second")].is_focus_visible());
+}
+
+#[test]
+fn listeners_in_subtree_excludes_nodes_outside_the_subtree() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="component"><p id="shallow"><span id="deep"></span></p><p id="quiet"></p></div>
+        <div id="outside"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+
+    for name in ["component", "deep", "shallow", "outside"] {
+        let node_id = by_id(&doc, name);
+        doc.nodes[node_id].add_event_listener("click");
+    }
+    doc.nodes[by_id(&doc, "quiet")].add_event_listener("input");
+
+    assert_eq!(
+        doc.listeners_in_subtree(by_id(&doc, "component"), "click"),
+        vec![by_id(&doc, "shallow"), by_id(&doc, "deep")]
+    );
+    assert!(doc
+        .listeners_in_subtree(by_id(&doc, "outside"), "click")
+        .is_empty());
+}
+
+#[test]
+fn upgrading_placeholders_only_marks_what_changed() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="list"></div></body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let list_id = by_id(&doc, "list");
+
+    let to_element = doc.create_node(NodeData::Comment);
+    let to_text = doc.create_node(NodeData::Comment);
+    for placeholder in [to_element, to_text] {
+        doc.nodes[list_id].children.push(placeholder);
+        doc.nodes[placeholder].parent = Some(list_id);
+    }
+    doc.resolve();
+    doc.changed.clear();
+
+    let span = QualName::new(None, ns!(html), local_name!("span"));
+    doc.upgrade_placeholder_to_element(to_element, span);
+    assert!(doc.nodes[to_element].is_element());
+    assert!(doc.nodes[to_element].stylo_element_data.borrow().is_some());
+
+    doc.upgrade_placeholder_to_text(to_text, "hello");
+    assert!(doc.nodes[to_text].is_text_node());
+    assert!(doc.nodes[to_text].stylo_element_data.borrow().is_none());
+
+    assert_eq!(doc.changed, HashSet::from([to_element, to_text]));
+    assert_eq!(
+        doc.layout_invalidations,
+        HashSet::from([to_element, to_text, list_id])
+    );
+}
+
+#[test]
+fn listeners_at_the_same_depth_are_in_document_order() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="list"><p id="last"></p></div></body></html>"#;
+    let mut doc = parse(html);
+    let list_id = by_id(&doc, "list");
+    let last = by_id(&doc, "last");
+
+    // Insert nodes in reverse so that ids are in the opposite order to the document
+    let mut expected = vec![last];
+    for _ in 0..5 {
+        let p = doc.create_node(NodeData::Element(ElementNodeData::new(
+            QualName::new(None, ns!(html), local_name!("p")),
+            Vec::new(),
+        )));
+        doc.insert_before(expected[0], &[p]);
+        expected.insert(0, p);
+    }
+    for &id in &expected {
+        doc.nodes[id].add_event_listener("click");
+    }
+
+    for _ in 0..3 {
+        assert_eq!(doc.listeners_in_subtree(list_id, "click"), expected);
+    }
+}
+
+#[test]
+fn em_margins_follow_the_elements_own_font_size() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="font-size: 10px">
+        <div id="box" style="font-size: 20px; margin: 2em; padding: 0.5em"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let box_id = by_id(&doc, "box");
+    // The body has no padding, so the box's left margin is its offset within the body
+    assert_eq!(doc.nodes[box_id].final_layout.location.x, 40.0);
+    assert_eq!(doc.nodes[box_id].final_layout.padding.top, 10.0);
+
+    doc.set_style_attribute(box_id, "font-size: 30px; margin: 2em; padding: 0.5em");
+    doc.resolve();
+    assert_eq!(doc.nodes[box_id].final_layout.location.x, 60.0);
+    assert_eq!(doc.nodes[box_id].final_layout.padding.top, 15.0);
+}
+
+#[test]
+fn tab_navigation_continues_after_the_focussed_node_is_removed() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><button id="first">1</button><button id="second">2</button><button id="third">3</button></body></html>"#;
+    let mut doc = parse(html);
+
+    let second = by_id(&doc, "second");
+    doc.set_focus_to(second);
+    doc.remove_node(second);
+    assert_eq!(doc.get_focus_node_id(), None);
+
+    assert_eq!(doc.focus_next_node(), Some(by_id(&doc, "first")));
+    assert_eq!(doc.focus_next_node(), Some(by_id(&doc, "third")));
+}
+
+#[test]
+fn scroll_into_view_scrolls_each_nested_container() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="outer" style="overflow-y: auto; height: 100px">
+            <div style="height: 300px"></div>
+            <div id="inner" style="overflow-y: auto; height: 100px">
+                <div style="height: 200px"></div>
+                <div id="item" style="height: 20px"></div>
+                <div style="height: 200px"></div>
+            </div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let (outer, inner, item) = (
+        by_id(&doc, "outer"),
+        by_id(&doc, "inner"),
+        by_id(&doc, "item"),
+    );
+    let offsets = |doc: &Document| {
+        (
+            doc.nodes[inner].scroll_offset.y,
+            doc.nodes[outer].scroll_offset.y,
+        )
+    };
+
+    // The item (at 200..220 within the inner container) is brought to the bottom edge of both containers
+    doc.scroll_into_view(item, ScrollAlign::Nearest);
+    assert_eq!(offsets(&doc), (120.0, 300.0));
+
+    // It is now visible, so scrolling to the nearest edge again does nothing
+    doc.scroll_into_view(item, ScrollAlign::Nearest);
+    assert_eq!(offsets(&doc), (120.0, 300.0));
+
+    doc.scroll_into_view(item, ScrollAlign::Start);
+    assert_eq!(offsets(&doc), (200.0, 300.0));
+
+    doc.scroll_into_view(item, ScrollAlign::Center);
+    assert_eq!(offsets(&doc), (160.0, 300.0));
+
+    // The item's rect lies within the visible part of the outer container
+    let item_top = 300.0 + 200.0 - offsets(&doc).0 - offsets(&doc).1;
+    assert!(item_top >= 0.0 && item_top + 20.0 <= 100.0);
+}
+
+#[test]
+fn index_in_parent_tracks_insertions_and_removals() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><ul id="list"><li id="a"></li><li id="b"></li></ul></body></html>"#;
+    let mut doc = parse(html);
+    let (a, b) = (by_id(&doc, "a"), by_id(&doc, "b"));
+    assert_eq!(doc.index_in_parent(by_id(&doc, "list")), Some(0));
+    assert_eq!(doc.index_in_parent(b), Some(1));
+
+    let first = doc.create_text_node("first");
+    doc.insert_before(a, &[first]);
+    let last = doc.create_text_node("last");
+    doc.append(b, &[last]);
+    assert_eq!(doc.index_in_parent(first), Some(0));
+    assert_eq!(doc.index_in_parent(a), Some(1));
+    assert_eq!(doc.index_in_parent(b), Some(2));
+    assert_eq!(doc.index_in_parent(last), Some(3));
+
+    doc.remove_node(a);
+    assert_eq!(doc.index_in_parent(b), Some(1));
+    assert_eq!(doc.index_in_parent(last), Some(2));
+
+    assert_eq!(doc.index_in_parent(doc.root_node().id), None);
+    let detached = doc.create_text_node("detached");
+    assert_eq!(doc.index_in_parent(detached), None);
+}
+
+#[test]
+fn remove_many_skips_descendants_of_removed_nodes() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <ul id="list"><li id="a"><span id="a-child"></span></li><li id="b"></li><li id="c"></li></ul>
+        <p id="other"><span id="other-child"></span></p>
+    </body></html>"#;
+    let mut doc = parse(html);
+    let (list, a, a_child, c) = (
+        by_id(&doc, "list"),
+        by_id(&doc, "a"),
+        by_id(&doc, "a-child"),
+        by_id(&doc, "c"),
+    );
+    let (other, other_child) = (by_id(&doc, "other"), by_id(&doc, "other-child"));
+    doc.take_child_count_changes();
+
+    // `a-child` is removed along with `a`, and ids are only processed once however often they appear
+    let removed = doc.remove_many(&[a_child, a, c, a, other_child]);
+    assert_eq!(removed, 4);
+
+    assert_eq!(doc.nodes[list].children, vec![by_id(&doc, "b")]);
+    assert_eq!(doc.index_in_parent(by_id(&doc, "b")), Some(0));
+    assert!(doc.nodes[other].children.is_empty());
+    assert!(!doc.nodes_to_id.contains_key("a-child"));
+    assert_eq!(doc.take_child_count_changes(), {
+        let mut changed = vec![list, other];
+        changed.sort_unstable();
+        changed
+    });
+    assert!(doc.debug_check_invariants().is_empty());
+
+    // Removing nodes which no longer exist does nothing
+    assert_eq!(doc.remove_many(&[a, c]), 0);
+}
+
+#[test]
+fn longest_increasing_subsequence_of_positions() {
+    assert_eq!(longest_increasing_subsequence(&[]), Vec::<usize>::new());
+    assert_eq!(longest_increasing_subsequence(&[0, 1, 2]), vec![0, 1, 2]);
+    assert_eq!(longest_increasing_subsequence(&[2, 0, 1]), vec![1, 2]);
+    assert_eq!(
+        longest_increasing_subsequence(&[3, 0, 4, 1, 2]),
+        vec![1, 3, 4]
+    );
+}
+
+#[test]
+fn keyed_children_are_reconciled_with_minimal_moves() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><ul id="list"><li data-key="a"></li><li data-key="b"></li><li data-key="c"></li><li data-key="d"></li></ul><ul id="other"><li></li><li></li></ul></body></html>"#;
+    let mut doc = parse(html);
+    let list = by_id(&doc, "list");
+    let li = |doc: &mut Document| {
+        let name = QualName::new(None, ns!(html), local_name!("li"));
+        doc.create_node(NodeData::Element(ElementNodeData::new(name, Vec::new())))
+    };
+    let keys = |doc: &Document| -> Vec<String> {
+        doc.nodes[list]
+            .children
+            .iter()
+            .map(|&id| {
+                doc.nodes[id]
+                    .attr(LocalName::from("data-key"))
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    };
+    let old = doc.nodes[list].children.clone();
+    let (a, b, c, d) = (old[0], old[1], old[2], old[3]);
+
+    // Moving the last item to the front only moves that item
+    let changes = doc.reconcile_children_by_key(
+        list,
+        vec![
+            ("d".into(), d),
+            ("a".into(), a),
+            ("b".into(), b),
+            ("c".into(), c),
+        ],
+    );
+    assert_eq!(changes.moved, vec![d]);
+    assert!(changes.inserted.is_empty() && changes.removed.is_empty());
+    assert_eq!(keys(&doc), ["d", "a", "b", "c"]);
+
+    // Existing nodes are reused for their keys, new keys are inserted and missing keys are removed
+    let e = li(&mut doc);
+    let duplicate_a = li(&mut doc);
+    let changes = doc.reconcile_children_by_key(
+        list,
+        vec![
+            ("a".into(), duplicate_a),
+            ("e".into(), e),
+            ("c".into(), c),
+            ("d".into(), d),
+        ],
+    );
+    assert_eq!(changes.inserted, vec![e]);
+    assert_eq!(changes.moved, vec![d]);
+    assert_eq!(changes.removed, vec![b]);
+    assert_eq!(changes.discarded, vec![duplicate_a]);
+    assert_eq!(doc.nodes[list].children, vec![a, e, c, d]);
+    assert_eq!(keys(&doc), ["a", "e", "c", "d"]);
+    assert!(!doc.nodes.contains(b) && !doc.nodes.contains(duplicate_a));
+    assert!(doc.debug_check_invariants().is_empty());
+
+    // An unchanged order makes no changes
+    let changes = doc.reconcile_children_by_key(
+        list,
+        vec![
+            ("a".into(), a),
+            ("e".into(), e),
+            ("c".into(), c),
+            ("d".into(), d),
+        ],
+    );
+    assert_eq!(changes, KeyedChanges::default());
+
+    // Nodes inserted from another parent are moved rather than shared between both parents
+    let other = by_id(&doc, "other");
+    let f = doc.nodes[other].children[0];
+    let changes = doc.reconcile_children_by_key(
+        list,
+        vec![
+            ("a".into(), a),
+            ("e".into(), e),
+            ("c".into(), c),
+            ("d".into(), d),
+            ("f".into(), f),
+        ],
+    );
+    assert_eq!(changes.inserted, vec![f]);
+    assert_eq!(doc.nodes[list].children, vec![a, e, c, d, f]);
+    assert_eq!(doc.nodes[f].parent, Some(list));
+    assert_eq!(doc.nodes[other].children.len(), 1);
+    assert_eq!(doc.nodes[doc.nodes[other].children[0]].child_idx, 0);
+    assert!(doc.debug_check_invariants().is_empty());
+
+    // Stale ids leave the children untouched
+    let removed = li(&mut doc);
+    doc.remove_node(removed);
+    let changes = doc.reconcile_children_by_key(list, vec![("a".into(), a), ("g".into(), removed)]);
+    assert_eq!(changes, KeyedChanges::default());
+    assert_eq!(doc.nodes[list].children, vec![a, e, c, d, f]);
+    assert_eq!(
+        doc.reconcile_children_by_key(removed, Vec::new()),
+        KeyedChanges::default()
+    );
+}
+
+#[test]
+fn root_node_is_the_document_and_root_element_is_html() {
+    use crate::DocumentHtmlParser;
+
+    let mut doc = Document::new(Viewport::new(800, 600, 1.0));
+    assert!(matches!(doc.root_node().raw_dom_data, NodeData::Document));
+    assert_eq!(doc.root_node().id, 0);
+    assert!(doc.try_root_element().is_none());
+
+    DocumentHtmlParser::parse_into_doc(&mut doc, "<!DOCTYPE html><html><body></body></html>");
+    let root = doc.root_element();
+    assert!(root
+        .raw_dom_data
+        .is_element_with_tag_name(&local_name!("html")));
+    assert_eq!(root.parent, Some(doc.root_node().id));
+    assert_eq!(doc.try_root_element().map(|node| node.id), Some(root.id));
+}
+
+#[test]
+fn spellcheck_is_inherited_and_can_be_disabled() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <textarea id="default"></textarea>
+        <form spellcheck="false">
+            <input id="inherited" type="text">
+            <div spellcheck="true"><input id="reenabled" type="search"></div>
+            <input id="invalid" type="text" spellcheck="maybe">
+        </form>
+        <input id="explicit" type="text" spellcheck="false">
+        <input id="password" type="password">
+        <div id="not-editable" spellcheck="true"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    assert_eq!(
+        doc.spellcheck_regions(),
+        vec![by_id(&doc, "default"), by_id(&doc, "reenabled")]
+    );
+    assert!(!doc.nodes[by_id(&doc, "invalid")].is_spellchecked());
+    assert!(!doc.nodes[by_id(&doc, "not-editable")].is_spellchecked());
+}
+
+#[test]
+fn walk_from_skips_pruned_subtrees_and_stops() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="a"><div id="pruned"><span id="inner"><b id="deep"></b></span></div><p id="b"></p></div>
+        <div id="c"></div><div id="d"></div>
+    </body></html>"#;
+    let doc = parse(html);
+    let element_id = |node: &Node| node.attr(local_name!("id")).map(str::to_string);
+
+    let mut visited = Vec::new();
+    doc.walk_from(by_id(&doc, "a"), |node| {
+        visited.extend(element_id(node));
+        match element_id(node).as_deref() {
+            Some("pruned") => WalkControl::SkipChildren,
+            _ => WalkControl::Continue,
+        }
+    });
+    assert_eq!(visited, vec!["a", "pruned", "b"]);
+
+    let mut visited = Vec::new();
+    doc.walk_from(doc.root_node().id, |node| {
+        visited.extend(element_id(node));
+        match element_id(node).as_deref() {
+            Some("c") => WalkControl::Stop,
+            _ => WalkControl::Continue,
+        }
+    });
+    assert_eq!(visited, vec!["a", "pruned", "inner", "deep", "b", "c"]);
+}
+
+#[test]
+fn once_listeners_are_removed_and_passive_listeners_cannot_prevent_default() {
+    use crate::events::ListenerOptions;
+    use crate::testing::{by_id, parse};
+
+    let html =
+        r#"<!DOCTYPE html><html><body><div id="outer"><div id="inner"></div></div></body></html>"#;
+    let mut doc = parse(html);
+    let inner = by_id(&doc, "inner");
+    let outer = by_id(&doc, "outer");
+
+    let once = ListenerOptions {
+        once: true,
+        ..Default::default()
+    };
+    doc.nodes[inner].add_event_listener_with_options("click", once);
+    let capture = ListenerOptions {
+        capture: true,
+        ..Default::default()
+    };
+    doc.nodes[outer].add_event_listener_with_options("click", capture);
+
+    let mut called = Vec::new();
+    doc.dispatch_event(inner, "click", |event| called.push(event.current_target));
+    assert_eq!(called, vec![outer, inner]);
+    assert!(!doc.nodes[inner].has_event_listener("click"));
+
+    let mut called = Vec::new();
+    doc.dispatch_event(inner, "click", |event| called.push(event.current_target));
+    assert_eq!(called, vec![outer]);
+
+    let passive = ListenerOptions {
+        passive: true,
+        ..Default::default()
+    };
+    doc.nodes[inner].add_event_listener_with_options("wheel", passive);
+    assert!(!doc.dispatch_event(inner, "wheel", |event| event.prevent_default()));
+    doc.nodes[outer].add_event_listener("wheel");
+    assert!(doc.dispatch_event(inner, "wheel", |event| event.prevent_default()));
+}
+
+#[test]
+fn scroll_parents_are_the_nearest_scroll_containers() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="outer" style="overflow: auto; height: 100px">
+            <div id="clipped" style="overflow: hidden">
+                <div id="inner" style="overflow-y: scroll; height: 50px">
+                    <p id="deep"><span id="deeper">Text</span></p>
+                </div>
+                <p id="sibling"></p>
+            </div>
+        </div>
+        <p id="outside"></p>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    assert_eq!(doc.scroll_parent(by_id(&doc, "outer")), None);
+    assert_eq!(doc.scroll_parent(by_id(&doc, "outside")), None);
+    // `overflow: hidden` clips but doesn't make a scroll container
+    assert_eq!(
+        doc.scroll_parent(by_id(&doc, "clipped")),
+        Some(by_id(&doc, "outer"))
+    );
+    assert_eq!(
+        doc.scroll_parent(by_id(&doc, "inner")),
+        Some(by_id(&doc, "outer"))
+    );
+    assert_eq!(
+        doc.scroll_parent(by_id(&doc, "sibling")),
+        Some(by_id(&doc, "outer"))
+    );
+    assert_eq!(
+        doc.scroll_parent(by_id(&doc, "deep")),
+        Some(by_id(&doc, "inner"))
+    );
+    assert_eq!(
+        doc.scroll_parent(by_id(&doc, "deeper")),
+        Some(by_id(&doc, "inner"))
+    );
+
+    // Changes to overflow are picked up when the document is resolved
+    let inner = by_id(&doc, "inner");
+    doc.set_style_attribute(inner, "height: 50px");
+    doc.resolve();
+    assert_eq!(
+        doc.scroll_parent(by_id(&doc, "deeper")),
+        Some(by_id(&doc, "outer"))
+    );
+}
+
+#[test]
+fn offscreen_content_visibility_auto_content_is_not_laid_out() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="onscreen" style="content-visibility: auto"><p id="shown" style="height: 20px"></p></div>
+        <div style="height: 1000px"></div>
+        <div id="offscreen" style="content-visibility: auto; contain-intrinsic-size: 300px 200px">
+            <p id="hidden" style="height: 20px"></p>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    assert_eq!(doc.skipped_content_nodes(), vec![by_id(&doc, "offscreen")]);
+    assert_eq!(
+        doc.nodes[by_id(&doc, "shown")].final_layout.size.height,
+        20.0
+    );
+    // The skipped element is sized by its placeholder, and its content isn't laid out
+    assert_eq!(
+        doc.nodes[by_id(&doc, "offscreen")].final_layout.size.height,
+        200.0
+    );
+    assert_eq!(
+        doc.nodes[by_id(&doc, "hidden")].final_layout.size.height,
+        0.0
+    );
+    assert_eq!(doc.paint_index(by_id(&doc, "hidden")), None);
+
+    doc.scroll_offset = -700.0;
+    doc.resolve();
+    assert_eq!(doc.skipped_content_nodes(), vec![by_id(&doc, "onscreen")]);
+    assert_eq!(
+        doc.nodes[by_id(&doc, "offscreen")].final_layout.size.height,
+        20.0
+    );
+    assert_eq!(
+        doc.nodes[by_id(&doc, "hidden")].final_layout.size.height,
+        20.0
+    );
+    assert!(doc.paint_index(by_id(&doc, "hidden")).is_some());
+}
+
+#[test]
+fn deleting_backward_removes_whole_graphemes_and_words() {
+    use crate::testing::{by_id, parse};
+
+    let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+    let html = format!(
+        r#"<!DOCTYPE html><html><body><p id="emoji">a{family}b</p><p id="words">hello world  </p></body></html>"#
+    );
+    let mut doc = parse(&html);
+    let text_node = |doc: &Document, name: &str| doc.nodes[by_id(doc, name)].children[0];
+    let text =
+        |doc: &Document, node_id: usize| doc.nodes[node_id].text_data().unwrap().content.clone();
+
+    // The whole emoji cluster is deleted, not just its last code point
+    let emoji = text_node(&doc, "emoji");
+    let caret = 1 + family.len();
+    doc.set_text_selection(emoji, caret, caret);
+    assert!(doc.delete_backward_grapheme(emoji));
+    assert_eq!(text(&doc, emoji), "ab");
+    assert_eq!(
+        doc.text_selection().map(|s| (s.anchor, s.focus)),
+        Some((1, 1))
+    );
+    assert!(doc.changed.contains(&emoji));
+
+    // The word is deleted along with the whitespace after it
+    let words = text_node(&doc, "words");
+    doc.set_text_selection(words, 13, 13);
+    assert!(doc.delete_backward_word(words));
+    assert_eq!(text(&doc, words), "hello ");
+    assert!(doc.delete_backward_word(words));
+    assert_eq!(text(&doc, words), "");
+    assert!(!doc.delete_backward_word(words));
+}
+
+#[test]
+fn sticky_elements_stay_within_their_scroll_container() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="container" style="overflow: auto; width: 200px; height: 100px">
+            <div id="section" style="height: 300px">
+                <div id="header" style="position: sticky; top: 0; height: 20px"></div>
+            </div>
+            <div style="height: 200px"></div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let (container, header) = (by_id(&doc, "container"), by_id(&doc, "header"));
+    // The painted position of the header relative to the top of the container
+    let painted_y = |doc: &Document, scroll_y: f64| {
+        doc.nodes[header].final_layout.location.y as f64 + doc.sticky_offset(header).y - scroll_y
+    };
+
+    // In normal flow before the container is scrolled
+    assert_eq!(doc.sticky_offset(header), kurbo::Vec2::ZERO);
+
+    // Pinned to the top of the container while its section is visible
+    for scroll_y in [50.0, 150.0, 280.0] {
+        doc.nodes[container].scroll_offset = kurbo::Vec2::new(0.0, scroll_y);
+        assert_eq!(painted_y(&doc, scroll_y), 0.0);
+    }
+
+    // Scrolls out along with the bottom of its section
+    doc.nodes[container].scroll_offset = kurbo::Vec2::new(0.0, 290.0);
+    assert_eq!(doc.sticky_offset(header).y, 280.0);
+    assert_eq!(painted_y(&doc, 290.0), -10.0);
+}
+
+#[test]
+fn focus_order_puts_positive_tabindex_first() {
+    use crate::testing::{by_id, parse};
+
+    let html = r##"<!DOCTYPE html><html><body>
+        <button id="a"></button>
+        <input id="b" tabindex="2">
+        <div id="c" tabindex="0"></div>
+        <a id="d" href="#" tabindex="1"></a>
+        <button id="e" tabindex="-1"></button>
+        <button id="f" tabindex="2"></button>
+        <div id="g"></div>
+        <button id="h" disabled></button>
+    </body></html>"##;
+    let mut doc = parse(html);
+    let expected: Vec<usize> = ["d", "b", "f", "a", "c"]
+        .iter()
+        .map(|name| by_id(&doc, *name))
+        .collect();
+
+    assert_eq!(doc.focus_order(), expected);
+    assert_eq!(doc.get_focus_node_id(), None);
+
+    // Tabbing follows the same order, wrapping around at the end
+    let tabbed: Vec<usize> = (0..6).filter_map(|_| doc.focus_next_node()).collect();
+    assert_eq!(tabbed[..5], expected[..]);
+    assert_eq!(tabbed[5], expected[0]);
+}
+
+#[test]
+fn inert_subtrees_are_not_focussable_or_hit() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body id="body" style="margin: 0">
+        <div id="dialog" inert style="height: 100px">
+            <button id="inside" style="display: block; width: 100px; height: 50px"></button>
+        </div>
+        <button id="outside"></button>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let (body, dialog) = (by_id(&doc, "body"), by_id(&doc, "dialog"));
+    let (inside, outside) = (by_id(&doc, "inside"), by_id(&doc, "outside"));
+
+    assert!(doc.nodes[inside].is_inert());
+    assert!(!doc.nodes[outside].is_inert());
+    assert_eq!(doc.focus_order(), vec![outside]);
+    assert!(!doc.set_focus_to(inside));
+    assert_eq!(doc.get_focus_node_id(), None);
+    // The pointer passes through the inert subtree to the body beneath
+    assert_eq!(doc.hit(10.0, 10.0).map(|hit| hit.node_id), Some(body));
+
+    doc.toggle_attribute(dialog, QualName::new(None, ns!(), LocalName::from("inert")));
+    doc.resolve();
+    assert!(!doc.nodes[inside].is_inert());
+    assert_eq!(doc.focus_order(), vec![inside, outside]);
+    assert_eq!(doc.hit(10.0, 10.0).map(|hit| hit.node_id), Some(inside));
+    assert!(doc.set_focus_to(inside));
+
+    // A focussed node loses focus when it becomes inert
+    doc.toggle_attribute(dialog, QualName::new(None, ns!(), LocalName::from("inert")));
+    doc.resolve();
+    assert_eq!(doc.get_focus_node_id(), None);
+    assert!(!doc.nodes[inside]
+        .element_state
+        .contains(style_dom::ElementState::FOCUS));
+}
+
+#[test]
+fn collect_garbage_skips_stale_layout_children_and_keeps_extra_roots() {
+    use crate::testing::{by_id, parse};
+
+    let html =
+        r#"<!DOCTYPE html><html><body><div id="list"><p id="item">Item</p></div></body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let item_id = by_id(&doc, "item");
+
+    let template = doc.create_text_node("template");
+    let detached = doc.create_text_node("detached");
+    // The removed node is still listed in its parent's layout children until the next layout
+    doc.remove_node(item_id);
+
+    assert_eq!(doc.collect_garbage_with_roots([template]), 1);
+    assert!(doc.get_node(template).is_some());
+    assert!(doc.get_node(detached).is_none());
+    assert_eq!(doc.collect_garbage(), 1);
+    assert!(doc.get_node(template).is_none());
+}
+
+#[test]
+fn removing_the_style_attribute_clears_inline_declarations() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0"><div id="box" style="width: 50px"></div></body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let box_id = by_id(&doc, "box");
+    assert_eq!(doc.nodes[box_id].final_layout.size.width, 50.0);
+
+    doc.remove_style_attribute(box_id);
+    doc.resolve();
+    let element = doc.nodes[box_id].element_data().unwrap();
+    assert_eq!(element.attr(local_name!("style")), None);
+    assert!(element.style_attribute.is_none());
+    assert_eq!(doc.nodes[box_id].final_layout.size.width, 800.0);
+}
+
+#[test]
+fn capturing_and_bubbling_listeners_for_the_same_event_coexist() {
+    use crate::events::ListenerOptions;
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><div id="outer"><button id="button"></button></div></body></html>"#;
+    let mut doc = parse(html);
+    let outer = by_id(&doc, "outer");
+    let button = by_id(&doc, "button");
+
+    let capture = ListenerOptions {
+        capture: true,
+        once: true,
+        ..Default::default()
+    };
+    doc.nodes[outer].add_event_listener_with_options("click", capture);
+    doc.nodes[outer].add_event_listener("click");
+    // Registering the same phase again is ignored
+    doc.nodes[outer].add_event_listener("click");
+    assert_eq!(doc.nodes[outer].listeners.len(), 2);
+
+    let mut calls = Vec::new();
+    doc.dispatch_event(button, "click", |event| calls.push(event.current_target));
+    assert_eq!(calls, vec![outer, outer]);
+
+    // The capturing listener was registered with `once`
+    calls.clear();
+    doc.dispatch_event(button, "click", |event| calls.push(event.current_target));
+    assert_eq!(calls, vec![outer]);
+    assert_eq!(doc.nodes[outer].listeners.len(), 1);
+}
+
+#[test]
+fn paste_replaces_the_selection_and_cut_removes_it() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><input id="input" type="text" value="hello world"></body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let input = by_id(&doc, "input");
+    doc.set_focus_to(input);
+    let selection = |doc: &Document| doc.text_selection().map(|s| (s.anchor, s.focus));
+
+    // Pasting replaces the selected "world" (selected backwards) and leaves the caret after the pasted text
+    doc.set_text_selection(input, 11, 6);
+    assert!(doc.paste_text("there"));
+    assert_eq!(doc.input_value(input).as_deref(), Some("hello there"));
+    assert_eq!(selection(&doc), Some((11, 11)));
+
+    // Copying leaves the text as it was, while cutting removes the selection and returns it
+    doc.set_text_selection(input, 0, 6);
+    assert_eq!(doc.copy_selection().as_deref(), Some("hello "));
+    assert_eq!(doc.input_value(input).as_deref(), Some("hello there"));
+    assert_eq!(doc.cut_selection().as_deref(), Some("hello "));
+    assert_eq!(doc.input_value(input).as_deref(), Some("there"));
+    assert_eq!(selection(&doc), Some((0, 0)));
+
+    // Nothing is cut or copied from a collapsed selection
+    assert_eq!(doc.cut_selection(), None);
+
+    // Cut and copy events leave the removed text for the shell to write to the clipboard
+    doc.set_text_selection(input, 0, 3);
+    let event = RendererEvent {
+        target: input,
+        data: EventData::Cut,
+    };
+    doc.handle_event(event);
+    assert_eq!(doc.take_clipboard_text().as_deref(), Some("the"));
+    assert_eq!(doc.input_value(input).as_deref(), Some("re"));
+    assert_eq!(doc.take_clipboard_text(), None);
+}
+
+#[test]
+fn viewport_units_resolve_against_the_viewport_and_update_when_it_resizes() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="half-width" style="width: 50vw; height: 100vh"></div>
+        <div id="vmin" style="width: 10vmin; height: 10vmax"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let size = |doc: &Document, name: &str| {
+        let size = doc.nodes[by_id(doc, name)].final_layout.size;
+        (size.width, size.height)
+    };
+
+    assert_eq!(size(&doc, "half-width"), (400.0, 600.0));
+    // vmin uses the smaller dimension (the height) and vmax the larger (the width)
+    assert_eq!(size(&doc, "vmin"), (60.0, 80.0));
+
+    // Resizing the viewport re-resolves every viewport-relative length
+    doc.set_viewport(Viewport::new(500, 1000, 1.0));
+    doc.resolve();
+    assert_eq!(size(&doc, "half-width"), (250.0, 1000.0));
+    assert_eq!(size(&doc, "vmin"), (50.0, 100.0));
+}
+
+#[test]
+fn paint_only_restyles_keep_the_layout_cache() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="box" style="width: 100px; height: 50px; background-color: red"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let box_id = by_id(&doc, "box");
+
+    // Changing colours restyles the node without laying anything out again
+    doc.set_style_attribute(
+        box_id,
+        "width: 100px; height: 50px; background-color: blue; border-color: green",
+    );
+    doc.resolve();
+    assert_eq!(doc.relayout_count(), 0);
+
+    // Changing its size lays it out again
+    doc.set_style_attribute(box_id, "width: 200px; height: 50px; background-color: blue");
+    doc.resolve();
+    assert!(doc.relayout_nodes.contains(&box_id));
+    assert_eq!(doc.nodes[box_id].final_layout.size.width, 200.0);
+}
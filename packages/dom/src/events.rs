@@ -1,7 +1,62 @@
+use std::time::{Duration, Instant};
+
 use winit::event::{Ime, KeyEvent, Modifiers};
+use winit::keyboard::KeyCode;
 
 pub struct EventListener {
     pub name: String,
+    pub options: ListenerOptions,
+}
+
+/// Options controlling how a listener is called (see [`Document::dispatch_event`](crate::Document::dispatch_event))
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ListenerOptions {
+    /// Remove the listener after it has been called once
+    pub once: bool,
+    /// The listener never prevents the default action (so e.g. scrolling doesn't need to wait for it)
+    pub passive: bool,
+    /// Call the listener while the event travels down from the root to the target, rather than while it bubbles up
+    pub capture: bool,
+}
+
+/// An event being dispatched to a listener
+#[derive(Debug, Clone)]
+pub struct DispatchedEvent {
+    /// The node the event was dispatched to
+    pub target: usize,
+    /// The node whose listener is being called
+    pub current_target: usize,
+    pub(crate) passive: bool,
+    default_prevented: bool,
+    pub(crate) propagation_stopped: bool,
+}
+
+impl DispatchedEvent {
+    pub(crate) fn new(target: usize) -> Self {
+        Self {
+            target,
+            current_target: target,
+            passive: false,
+            default_prevented: false,
+            propagation_stopped: false,
+        }
+    }
+
+    /// Prevent the default action of the event. Ignored when called from a passive listener.
+    pub fn prevent_default(&mut self) {
+        if !self.passive {
+            self.default_prevented = true;
+        }
+    }
+
+    pub fn default_prevented(&self) -> bool {
+        self.default_prevented
+    }
+
+    /// Don't call the listeners of any further nodes
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
 }
 
 #[derive(Debug)]
@@ -19,10 +74,25 @@ impl RendererEvent {
 
 #[derive(Debug)]
 pub enum EventData {
-    Click { x: f32, y: f32, mods: Modifiers },
-    KeyPress { event: KeyEvent, mods: Modifiers },
+    Click {
+        x: f32,
+        y: f32,
+        mods: Modifiers,
+    },
+    KeyPress {
+        event: KeyEvent,
+        mods: Modifiers,
+    },
     Ime(Ime),
     Hover,
+    /// Clipboard text to be inserted into the focussed text input, replacing its selection
+    Paste {
+        text: String,
+    },
+    /// Request to cut the focussed text input's selection to the clipboard
+    Cut,
+    /// Request to copy the focussed text input's selection to the clipboard
+    Copy,
 }
 
 impl EventData {
@@ -32,6 +102,9 @@ impl EventData {
             EventData::KeyPress { .. } => "keypress",
             EventData::Ime { .. } => "input",
             EventData::Hover => "mouseover",
+            EventData::Paste { .. } => "paste",
+            EventData::Cut => "cut",
+            EventData::Copy => "copy",
         }
     }
 }
@@ -45,3 +118,110 @@ pub struct HitResult {
     /// The y coordinate of the hit within the hit target's border-box
     pub y: f32,
 }
+
+/// Tracks held keys in order to generate auto-repeat events (distinct from the initial keydown) at a fixed cadence:
+/// the first repeat fires `initial_delay` after the key is pressed and subsequent repeats fire every `interval`.
+#[derive(Debug, Clone)]
+pub struct KeyRepeatState {
+    pub initial_delay: Duration,
+    pub interval: Duration,
+    held: Vec<HeldKey>,
+}
+
+#[derive(Debug, Clone)]
+struct HeldKey {
+    code: KeyCode,
+    next_repeat: Instant,
+}
+
+impl Default for KeyRepeatState {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(500), Duration::from_millis(33))
+    }
+}
+
+/// The shortest interval between repeats. Shorter intervals (including zero) are clamped to this.
+pub const MIN_KEY_REPEAT_INTERVAL: Duration = Duration::from_millis(1);
+
+impl KeyRepeatState {
+    pub fn new(initial_delay: Duration, interval: Duration) -> Self {
+        Self {
+            initial_delay,
+            interval: interval.max(MIN_KEY_REPEAT_INTERVAL),
+            held: Vec::new(),
+        }
+    }
+
+    /// Record that a key was pressed at `now`. Returns `false` if the key was already held.
+    pub fn press(&mut self, code: KeyCode, now: Instant) -> bool {
+        if self.is_held(code) {
+            return false;
+        }
+        self.held.push(HeldKey {
+            code,
+            next_repeat: now + self.initial_delay,
+        });
+        true
+    }
+
+    /// Record that a key was released, stopping its repeats
+    pub fn release(&mut self, code: KeyCode) {
+        self.held.retain(|key| key.code != code);
+    }
+
+    /// Stop repeating all keys (e.g. because the window lost focus)
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+
+    pub fn is_held(&self, code: KeyCode) -> bool {
+        self.held.iter().any(|key| key.code == code)
+    }
+
+    /// The repeats which are due at `now`, in order. A key which has been held for several intervals since the
+    /// last tick produces one repeat for each elapsed interval.
+    pub fn tick(&mut self, now: Instant) -> Vec<KeyCode> {
+        // The interval is public, so it is clamped here too (a zero interval would repeat forever)
+        let interval = self.interval.max(MIN_KEY_REPEAT_INTERVAL);
+        let mut repeats = Vec::new();
+        for key in &mut self.held {
+            while key.next_repeat <= now {
+                repeats.push(key.code);
+                key.next_repeat += interval;
+            }
+        }
+        repeats
+    }
+}
+
+#[test]
+fn held_key_repeats_after_initial_delay_at_interval() {
+    let start = Instant::now();
+    let ms = Duration::from_millis;
+    let mut state = KeyRepeatState::new(ms(300), ms(50));
+
+    assert!(state.press(KeyCode::ArrowRight, start));
+    // Repeated keydowns while held are not new presses
+    assert!(!state.press(KeyCode::ArrowRight, start + ms(10)));
+
+    assert!(state.tick(start + ms(299)).is_empty());
+    assert_eq!(state.tick(start + ms(300)), vec![KeyCode::ArrowRight]);
+    assert!(state.tick(start + ms(349)).is_empty());
+    assert_eq!(state.tick(start + ms(350)), vec![KeyCode::ArrowRight]);
+
+    // Several intervals elapsing between ticks produce one repeat each
+    assert_eq!(state.tick(start + ms(500)).len(), 3);
+
+    state.release(KeyCode::ArrowRight);
+    assert!(state.tick(start + ms(1000)).is_empty());
+}
+
+#[test]
+fn zero_repeat_interval_is_clamped() {
+    let start = Instant::now();
+    let mut state = KeyRepeatState::new(Duration::ZERO, Duration::ZERO);
+    assert_eq!(state.interval, MIN_KEY_REPEAT_INTERVAL);
+
+    state.press(KeyCode::Backspace, start);
+    assert_eq!(state.tick(start + Duration::from_millis(5)).len(), 6);
+}
@@ -215,6 +215,10 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
             self.doc.nodes_to_id.insert(id_attr.to_string(), id);
         }
 
+        if node.attr(local_name!("autofocus")).is_some() {
+            self.doc.request_autofocus(id);
+        }
+
         // Custom post-processing by element tag name
         match name.local.as_ref() {
             "link" => self.load_linked_stylesheet(id),
@@ -392,3 +396,14 @@ fn parses_some_html() {
 
     // Now our tree should have some nodes in it
 }
+
+#[test]
+fn last_autofocus_element_is_requested() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><input id="a" autofocus><input id="b" autofocus></body></html>"#;
+    let mut doc = parse(html);
+
+    assert_eq!(doc.take_autofocus_request(), Some(by_id(&doc, "b")));
+    assert_eq!(doc.take_autofocus_request(), None);
+}
@@ -8,12 +8,13 @@ use style::{
     shared_lock::StylesheetGuards,
     values::{
         computed::Display,
+        generics::length::LengthOrNumber,
         specified::box_::{DisplayInside, DisplayOutside},
     },
 };
 
 use crate::{
-    node::{NodeKind, NodeSpecificData, TextBrush, TextInputData, TextLayout},
+    node::{NodeKind, NodeSpecificData, PseudoContent, TextBrush, TextInputData, TextLayout},
     stylo_to_parley, Document, ElementNodeData, Node, NodeData,
 };
 
@@ -47,7 +48,18 @@ pub(crate) fn collect_layout_children(
         }
     }
 
-    if doc.nodes[container_node_id].children.is_empty() {
+    // Elements whose content is skipped are laid out as if they were empty
+    if doc.is_content_skipped(container_node_id) {
+        doc.nodes[container_node_id].is_inline_root = false;
+        return;
+    }
+
+    // Elements with generated content but no children still need an inline layout for that content
+    let container = &doc.nodes[container_node_id];
+    if container.children.is_empty()
+        && container.generated_content(PseudoContent::Before).is_none()
+        && container.generated_content(PseudoContent::After).is_none()
+    {
         return;
     }
 
@@ -256,9 +268,10 @@ fn collect_complex_layout_children(
                     &parent_style,
                 );
                 let mut element_data = ElementData::default();
-                element_data.styles.primary = Some(style);
+                element_data.styles.primary = Some(style.clone());
                 element_data.set_restyled();
                 *doc.nodes[node_id].stylo_element_data.borrow_mut() = Some(element_data);
+                doc.nodes[node_id].laid_out_style = Some(style);
 
                 layout_children.push(node_id);
                 *anonymous_block_id = Some(node_id);
@@ -334,15 +347,24 @@ pub(crate) fn build_inline_layout(
         .unwrap_or(WhiteSpaceCollapse::Collapse);
     builder.set_white_space_mode(collapse_mode);
 
+    let tab_size = tab_size(root_node).unwrap_or(DEFAULT_TAB_SIZE);
+
+    if let Some(content) = root_node.generated_content(PseudoContent::Before) {
+        builder.push_text(content);
+    }
     for child_id in root_node.children.iter().copied() {
         build_inline_layout_recursive(
             &mut builder,
             &doc.nodes,
             child_id,
             collapse_mode,
+            tab_size,
             root_line_height,
         );
     }
+    if let Some(content) = root_node.generated_content(PseudoContent::After) {
+        builder.push_text(content);
+    }
 
     let (layout, text) = builder.build();
 
@@ -365,9 +387,12 @@ pub(crate) fn build_inline_layout(
         nodes: &Slab<Node>,
         node_id: usize,
         collapse_mode: WhiteSpaceCollapse,
+        tab_size: usize,
         root_line_height: f32,
     ) {
         let node = &nodes[node_id];
+        // Text nodes have no styles of their own, so they use their parent's tab size
+        let tab_size = self::tab_size(node).unwrap_or(tab_size);
 
         // Set whitespace collapsing mode
         let collapse_mode = node
@@ -402,6 +427,7 @@ pub(crate) fn build_inline_layout(
                                 nodes,
                                 child_id,
                                 collapse_mode,
+                                tab_size,
                                 root_line_height,
                             );
                         }
@@ -436,15 +462,22 @@ pub(crate) fn build_inline_layout(
 
                             builder.push_style_span(style);
 
+                            if let Some(content) = node.generated_content(PseudoContent::Before) {
+                                builder.push_text(content);
+                            }
                             for child_id in node.children.iter().copied() {
                                 build_inline_layout_recursive(
                                     builder,
                                     nodes,
                                     child_id,
                                     collapse_mode,
+                                    tab_size,
                                     root_line_height,
                                 );
                             }
+                            if let Some(content) = node.generated_content(PseudoContent::After) {
+                                builder.push_text(content);
+                            }
 
                             builder.pop_style_span();
                         }
@@ -463,10 +496,98 @@ pub(crate) fn build_inline_layout(
                 };
             }
             NodeData::Text(data) => {
-                builder.push_text(&data.content);
+                if matches!(collapse_mode, WhiteSpaceCollapse::Preserve)
+                    && data.content.contains('\t')
+                {
+                    builder.push_text(&expand_tabs(&data.content, tab_size));
+                } else {
+                    builder.push_text(&data.content);
+                }
             }
             NodeData::Comment => {}
             NodeData::Document => unreachable!(),
         }
     }
 }
+
+/// The default width of a tab character, in spaces
+const DEFAULT_TAB_SIZE: usize = 8;
+
+/// The number of spaces a tab expands to within a node, from its computed `tab-size` (which is inherited).
+/// Lengths are converted to a number of spaces assuming that a space is half an em wide.
+///
+/// TODO: measure the advance of a space in the node's font
+fn tab_size(node: &Node) -> Option<usize> {
+    let style = node.primary_styles()?;
+    let spaces = match style.get_inherited_text().tab_size {
+        LengthOrNumber::Number(number) => number.0,
+        LengthOrNumber::Length(length) => {
+            let space_width = style.get_font().font_size.used_size.0.px() / 2.0;
+            if space_width <= 0.0 {
+                return Some(0);
+            }
+            length.0.px() / space_width
+        }
+    };
+    Some(spaces.max(0.0).round() as usize)
+}
+
+/// Replace each tab in preserved whitespace with enough spaces to reach the next tab stop, with tab stops
+/// every `tab_size` characters from the start of each line of the text
+fn expand_tabs(text: &str, tab_size: usize) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = if tab_size == 0 {
+                    0
+                } else {
+                    tab_size - column % tab_size
+                };
+                expanded.extend(std::iter::repeat(' ').take(spaces));
+                column += spaces;
+            }
+            '\n' => {
+                expanded.push(c);
+                column = 0;
+            }
+            _ => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    expanded
+}
+
+#[test]
+fn tabs_expand_to_the_next_tab_stop() {
+    assert_eq!(expand_tabs("\tx", 4), "    x");
+    assert_eq!(expand_tabs("ab\tx", 4), "ab  x");
+    assert_eq!(expand_tabs("abcd\tx\n\ty", 4), "abcd    x\n    y");
+    assert_eq!(expand_tabs("\tx", 0), "x");
+}
+
+#[test]
+fn tab_size_changes_advance_of_preformatted_tabs() {
+    use crate::testing::{by_id, parse};
+
+    let html = "<!DOCTYPE html><html><body>
+        <pre id=\"four\" style=\"tab-size: 4\">\tx</pre>
+        <pre id=\"eight\">\tx</pre>
+    </body></html>";
+    let mut doc = parse(html);
+    doc.resolve();
+    let width = |name: &str| {
+        doc.nodes[by_id(&doc, name)]
+            .raw_dom_data
+            .downcast_element()
+            .and_then(|element| element.inline_layout_data())
+            .map(|text| text.layout.width())
+            .unwrap()
+    };
+
+    assert!(width("four") > 0.0);
+    assert!(width("four") < width("eight"));
+}
@@ -9,10 +9,13 @@ use crate::{
     document::Document,
     image::{image_measure_function, ImageContext},
     node::Node,
+    stylo_to_taffy,
 };
 use html5ever::local_name;
 use std::cell::Ref;
 use std::sync::Arc;
+use style::computed_values::direction::T as Direction;
+use style::computed_values::text_wrap_mode::T as TextWrapMode;
 use taffy::{
     compute_block_layout, compute_cached_layout, compute_flexbox_layout, compute_grid_layout,
     compute_leaf_layout, prelude::*, Cache, FlexDirection, LayoutPartialTree, MaybeMath as _,
@@ -104,6 +107,8 @@ impl LayoutPartialTree for Document {
         inputs: taffy::tree::LayoutInput,
     ) -> taffy::tree::LayoutOutput {
         compute_cached_layout(self, node_id, inputs, |tree, node_id, inputs| {
+            tree.relayout_nodes.insert(node_id.into());
+            tree.resolve_children_calc_sizes(node_id.into(), inputs);
             let node = &mut tree.nodes[node_id.into()];
 
             match &mut node.raw_dom_data {
@@ -214,6 +219,15 @@ impl LayoutPartialTree for Document {
                         return computed;
                     }
 
+                    // Elements whose content is skipped are laid out as if their content was a placeholder of their
+                    // `contain-intrinsic-size` (see `Document::is_content_skipped`)
+                    if node.has_auto_content_visibility()
+                        && !tree.relevant_content.contains(&usize::from(node_id))
+                    {
+                        let placeholder = node.contain_intrinsic_size();
+                        return compute_leaf_layout(inputs, &node.style, |_, _| placeholder);
+                    }
+
                     if node.is_table_root {
                         let NodeSpecificData::TableRoot(context) = &tree.nodes[node_id.into()]
                             .raw_dom_data
@@ -306,7 +320,47 @@ impl taffy::LayoutGridContainer for Document {
     }
 }
 
+/// The min-content and max-content widths of a node in CSS pixels, as computed by [`Document::intrinsic_sizes`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntrinsicSizes {
+    pub min: f32,
+    pub max: f32,
+}
+
 impl Document {
+    /// Resolve the `calc()` sizes of a node's children (see [`stylo_to_taffy::resolve_calc_sizes`]) against the
+    /// node's content box (their containing block) before the node's layout algorithm reads their styles
+    fn resolve_children_calc_sizes(&mut self, node_id: usize, inputs: taffy::tree::LayoutInput) {
+        let node = &self.nodes[node_id];
+        let padding_border = node.style.padding.resolve_or_zero(inputs.parent_size.width)
+            + node.style.border.resolve_or_zero(inputs.parent_size.width);
+        let basis = Size {
+            width: inputs
+                .known_dimensions
+                .width
+                .map(|width| (width - padding_border.horizontal_axis_sum()).max(0.0)),
+            height: inputs
+                .known_dimensions
+                .height
+                .map(|height| (height - padding_border.vertical_axis_sum()).max(0.0)),
+        };
+        let Some(children) = node.layout_children.borrow().clone() else {
+            return;
+        };
+
+        for child_id in children {
+            let child = &mut self.nodes[child_id];
+            let stylo_element_data = child.stylo_element_data.borrow();
+            let Some(style) = stylo_element_data
+                .as_ref()
+                .and_then(|data| data.styles.get_primary())
+            else {
+                continue;
+            };
+            stylo_to_taffy::resolve_calc_sizes(style, &mut child.style, basis);
+        }
+    }
+
     fn compute_inline_layout(
         &mut self,
         node_id: NodeId,
@@ -326,6 +380,12 @@ impl Document {
         // TODO: eliminate clone
         let style = self.nodes[usize::from(node_id)].style.clone();
 
+        // Text with `white-space: pre` or `nowrap` is only broken at forced line breaks
+        let wraps = self.nodes[usize::from(node_id)]
+            .primary_styles()
+            .map(|s| s.get_inherited_text().text_wrap_mode != TextWrapMode::Nowrap)
+            .unwrap_or(true);
+
         let output = compute_leaf_layout(inputs, &style, |_known_dimensions, available_space| {
             // Short circuit if inline context contains no text or inline boxes
             if inline_layout.text.is_empty() && inline_layout.layout.inline_boxes().is_empty() {
@@ -354,11 +414,7 @@ impl Document {
             }
 
             // Perform inline layout
-            let max_advance = match available_space.width {
-                AvailableSpace::Definite(px) => Some(px * scale),
-                AvailableSpace::MinContent => Some(0.0),
-                AvailableSpace::MaxContent => None,
-            };
+            let max_advance = max_line_advance(available_space.width, scale, wraps);
 
             let alignment = self.nodes[usize::from(node_id)]
                 .primary_styles()
@@ -366,13 +422,20 @@ impl Document {
                     use parley::layout::Alignment;
                     use style::values::specified::TextAlignKeyword;
 
+                    // The start and end of a line are swapped in right-to-left text
+                    // TODO: also set the base direction used by parley's bidi algorithm
+                    let (start, end) = match s.clone_direction() {
+                        Direction::Ltr => (Alignment::Start, Alignment::End),
+                        Direction::Rtl => (Alignment::End, Alignment::Start),
+                    };
+
                     match s.clone_text_align() {
-                        TextAlignKeyword::Start => Alignment::Start,
+                        TextAlignKeyword::Start => start,
                         TextAlignKeyword::Left => Alignment::Start,
                         TextAlignKeyword::Right => Alignment::End,
                         TextAlignKeyword::Center => Alignment::Middle,
                         TextAlignKeyword::Justify => Alignment::Justified,
-                        TextAlignKeyword::End => Alignment::End,
+                        TextAlignKeyword::End => end,
                         TextAlignKeyword::MozCenter => Alignment::Middle,
                         TextAlignKeyword::MozLeft => Alignment::Start,
                         TextAlignKeyword::MozRight => Alignment::End,
@@ -570,6 +633,19 @@ impl PrintTree for Document {
 //     }
 // }
 
+/// The width (in scaled pixels) at which lines of text should be soft wrapped, or `None` if lines should only
+/// be broken at forced line breaks (such as preserved newlines)
+fn max_line_advance(available_width: AvailableSpace, scale: f32, wraps: bool) -> Option<f32> {
+    if !wraps {
+        return None;
+    }
+    match available_width {
+        AvailableSpace::Definite(px) => Some(px * scale),
+        AvailableSpace::MinContent => Some(0.0),
+        AvailableSpace::MaxContent => None,
+    }
+}
+
 pub struct RefCellChildIter<'a> {
     items: Ref<'a, [usize]>,
     idx: usize,
@@ -589,3 +665,61 @@ impl<'a> Iterator for RefCellChildIter<'a> {
         })
     }
 }
+
+#[test]
+fn only_wrapping_text_is_soft_wrapped() {
+    assert_eq!(max_line_advance(AvailableSpace::Definite(100.0), 2.0, true), Some(200.0));
+    assert_eq!(max_line_advance(AvailableSpace::MinContent, 1.0, true), Some(0.0));
+    assert_eq!(max_line_advance(AvailableSpace::Definite(100.0), 2.0, false), None);
+    assert_eq!(max_line_advance(AvailableSpace::MinContent, 1.0, false), None);
+}
+
+#[test]
+fn flex_items_share_free_space_by_grow_and_shrink_factors() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div style="display: flex; width: 300px"><div id="one" style="flex: 1"></div><div id="two" style="flex: 2"></div></div>
+        <div style="display: flex; width: 400px"><div id="quarter" style="flex: 0 0 25%"></div><div id="half" style="flex: 1 1 50%"></div></div>
+        <div style="display: flex; width: 400px"><div id="slow" style="flex: 0 1 300px"></div><div id="fast" style="flex: 0 3 300px"></div></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let width = |name: &str| doc.nodes[by_id(&doc, name)].final_layout.size.width;
+
+    // `flex: <number>` has a basis of 0%, so all of the space is shared out in proportion to the grow factors
+    assert_eq!((width("one"), width("two")), (100.0, 200.0));
+
+    // Percentage bases are resolved against the container's width before the remaining 100px is shared out
+    assert_eq!((width("quarter"), width("half")), (100.0, 300.0));
+
+    // Overflow of 200px is taken from each item in proportion to its shrink factor times its basis
+    assert_eq!((width("slow"), width("fast")), (250.0, 150.0));
+}
+
+#[test]
+fn math_function_sizes_are_resolved_against_the_containing_block() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div style="width: 100px"><div id="narrow" style="width: clamp(100px, 50%, 300px)"></div></div>
+        <div style="width: 400px"><div id="medium" style="width: clamp(100px, 50%, 300px)"></div></div>
+        <div style="width: 1000px"><div id="wide" style="width: clamp(100px, 50%, 300px)"></div></div>
+        <div style="width: 400px; padding: 0 50px"><div id="min" style="width: min(50%, 300px)"></div></div>
+        <div style="width: 400px"><div id="max" style="width: max(5%, 20px + 5%)"></div></div>
+        <div style="width: 400px"><div id="capped" style="max-width: min(25%, 300px)">Text</div></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let width = |name: &str| doc.nodes[by_id(&doc, name)].final_layout.size.width;
+
+    // The preferred 50% is clamped to between 100px and 300px
+    assert_eq!(width("narrow"), 100.0);
+    assert_eq!(width("medium"), 200.0);
+    assert_eq!(width("wide"), 300.0);
+
+    // Percentages are of the parent's content box
+    assert_eq!(width("min"), 200.0);
+    assert_eq!(width("max"), 40.0);
+    assert_eq!(width("capped"), 100.0);
+}
@@ -9,6 +9,8 @@
 //! ## Feature flags
 //!  - `default`: Enables the features listed below.
 //!  - `tracing`: Enables tracing support.
+//!  - `testing` (not enabled by default): Enables the `testing` helpers, such as `assert_dom_snapshot!`, for use
+//!    in the tests of other crates.
 
 /// The DOM implementation.
 ///
@@ -42,16 +44,32 @@ pub mod util;
 
 pub mod debug;
 
+pub mod accessibility;
+
+#[cfg(feature = "accesskit")]
+pub mod accessibility_tree;
+
+pub mod display_list;
+
+pub mod snapshot;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub mod transform;
+
 pub mod events;
 
 pub mod viewport;
 
-pub use document::{Document, DocumentLike};
+pub use document::{
+    Document, DocumentLike, KeyedChanges, ScrollAlign, TextSelection, WalkControl,
+};
 pub use html5ever::{
     local_name, namespace_prefix, namespace_url, ns, Namespace, NamespaceStaticSet, Prefix,
     PrefixStaticSet, QualName,
 };
 pub use htmlsink::DocumentHtmlParser;
-pub use node::{ElementNodeData, Node, NodeData, TextNodeData};
+pub use node::{ElementNodeData, Node, NodeData, PseudoContent, TextNodeData};
 pub use string_cache::Atom;
 pub use viewport::Viewport;
@@ -1,15 +1,19 @@
 use atomic_refcell::{AtomicRef, AtomicRefCell};
-use html5ever::{local_name, LocalName, QualName};
+use html5ever::{local_name, namespace_url, ns, LocalName, Namespace, QualName};
 use image::DynamicImage;
 use selectors::matching::QuirksMode;
 use slab::Slab;
+use std::any::Any;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::Write;
 use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use style::computed_values::object_fit::T as ObjectFit;
+use style::computed_values::pointer_events::T as PointerEvents;
 use style::invalidation::element::restyle_hints::RestyleHint;
-use style::values::computed::Display;
+use style::values::computed::{ContainIntrinsicSize, ContentVisibility, Display};
 use style::values::specified::box_::{DisplayInside, DisplayOutside};
 use style_dom::ElementState;
 // use string_cache::Atom;
@@ -26,11 +30,11 @@ use style::{
 };
 use taffy::{
     prelude::{Layout, Style},
-    Cache,
+    Cache, Point,
 };
 use url::Url;
 
-use crate::events::{EventListener, HitResult};
+use crate::events::{EventListener, HitResult, ListenerOptions};
 use crate::layout::table::TableContext;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -75,7 +79,17 @@ pub struct Node {
     pub cache: Cache,
     pub unrounded_layout: Layout,
     pub final_layout: Layout,
+    /// The computed styles as of the last layout pass, used to detect restyles which require relayout
+    pub laid_out_style: Option<ServoArc<ComputedValues>>,
     pub listeners: Vec<EventListener>,
+    /// How far (in CSS pixels) the content of a scroll container has been scrolled from its top-left corner
+    pub scroll_offset: peniko::kurbo::Vec2,
+    /// Whether each `overflow: auto` axis currently shows a scrollbar (because its content overflows)
+    pub auto_scrollbars: Point<bool>,
+    /// The nearest ancestor which is a scroll container, as of the last restyle (see [`Document::scroll_parent`](crate::Document::scroll_parent))
+    pub(crate) scroll_parent: Option<usize>,
+    /// Whether this node is within an `inert` subtree, as of the last restyle (see [`Node::is_inert`])
+    pub(crate) inert: bool,
 
     // Flags
     pub is_inline_root: bool,
@@ -107,7 +121,12 @@ impl Node {
             cache: Cache::new(),
             unrounded_layout: Layout::new(),
             final_layout: Layout::new(),
+            laid_out_style: None,
             listeners: Default::default(),
+            scroll_offset: peniko::kurbo::Vec2::ZERO,
+            auto_scrollbars: Point { x: false, y: false },
+            scroll_parent: None,
+            inert: false,
             is_inline_root: false,
             is_table_root: false,
         }
@@ -174,12 +193,22 @@ impl Node {
         self.set_restyle_hint(RestyleHint::RESTYLE_SELF);
     }
 
-    pub fn focus(&mut self) {
-        self.element_state
-            .insert(ElementState::FOCUS | ElementState::FOCUSRING);
+    /// Mark this node as focussed. `visible` controls whether it shows a focus ring (`:focus-visible`).
+    pub fn focus(&mut self, visible: bool) {
+        self.element_state.insert(ElementState::FOCUS);
+        self.element_state.set(ElementState::FOCUSRING, visible);
         self.set_restyle_hint(RestyleHint::RESTYLE_SELF);
     }
 
+    pub fn is_focussed(&self) -> bool {
+        self.element_state.contains(ElementState::FOCUS)
+    }
+
+    /// Whether this node is focussed and should show a focus ring
+    pub fn is_focus_visible(&self) -> bool {
+        self.element_state.contains(ElementState::FOCUSRING)
+    }
+
     pub fn blur(&mut self) {
         self.element_state
             .remove(ElementState::FOCUS | ElementState::FOCUSRING);
@@ -224,6 +253,34 @@ pub enum NodeData {
 }
 
 impl NodeData {
+    /// Create a (detached) HTML element with the tag name `tag` and no attributes
+    pub fn new_element(tag: impl Into<String>) -> Self {
+        let name = QualName::new(None, ns!(html), LocalName::from(tag.into()));
+        NodeData::Element(ElementNodeData::new(name, Vec::new()))
+    }
+
+    /// Create a text node with the content `content`
+    pub fn new_text(content: impl Into<String>) -> Self {
+        NodeData::Text(TextNodeData::new(content.into()))
+    }
+
+    /// Set the namespace of an element (for example to create SVG elements). Has no effect on other kinds of node.
+    pub fn with_namespace(mut self, ns: Namespace) -> Self {
+        if let Some(element) = self.downcast_element_mut() {
+            element.name.ns = ns;
+        }
+        self
+    }
+
+    /// Set the attribute `name` to `value`, replacing any existing value. Has no effect on non-element nodes.
+    pub fn with_attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        if let Some(element) = self.downcast_element_mut() {
+            let name = QualName::new(None, ns!(), LocalName::from(name.into()));
+            element.set_attribute(name, value.into());
+        }
+        self
+    }
+
     pub fn downcast_element(&self) -> Option<&ElementNodeData> {
         match self {
             Self::Element(data) => Some(data),
@@ -272,6 +329,9 @@ impl NodeData {
 /// The tokenizer creates all attributes this way, but the tree
 /// builder will adjust certain attribute names inside foreign
 /// content (MathML, SVG).
+///
+/// Attribute names (and namespaces) are interned atoms, so attributes with the same name share a
+/// single allocation and compare by handle rather than by string contents.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct Attribute {
     /// The name of the attribute (e.g. the `class` in `<div class="test">`)
@@ -280,6 +340,34 @@ pub struct Attribute {
     pub value: String,
 }
 
+/// A cache of typed values parsed from an element's attributes, keyed by attribute name.
+/// Each entry stores the raw string it was parsed from so that it can be invalidated when the attribute changes.
+/// The cache is filled while styling (which may run in parallel), so it is held in an `AtomicRefCell`.
+#[derive(Default)]
+pub struct AttributeCache {
+    entries: AtomicRefCell<HashMap<LocalName, CachedAttribute>>,
+}
+
+impl Clone for AttributeCache {
+    fn clone(&self) -> Self {
+        Self {
+            entries: AtomicRefCell::new(self.entries.borrow().clone()),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct CachedAttribute {
+    raw: String,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl std::fmt::Debug for AttributeCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.entries.borrow().keys()).finish()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ElementNodeData {
     /// The elements tag name, namespace and prefix
@@ -288,7 +376,9 @@ pub struct ElementNodeData {
     /// The elements id attribute parsed as an atom (if it has one)
     pub id: Option<Atom>,
 
-    /// The element's attributes
+    /// The element's attributes, in insertion order (setting an existing attribute keeps its position), so that
+    /// iteration and serialization are deterministic. Elements have few attributes, so a linear scan is fast enough
+    /// for lookups.
     pub attrs: Vec<Attribute>,
 
     /// Whether the element is focussable
@@ -306,6 +396,9 @@ pub struct ElementNodeData {
 
     /// The element's template contents (\<template\> elements only)
     pub template_contents: Option<usize>,
+
+    /// Typed values parsed from the element's attributes (see [`ElementNodeData::attr_parsed_cached`])
+    pub attr_cache: AttributeCache,
     // /// Whether the node is a [HTML integration point] (https://html.spec.whatwg.org/multipage/#html-integration-point)
     // pub mathml_annotation_xml_integration_point: bool,
 }
@@ -326,6 +419,7 @@ impl ElementNodeData {
             style_attribute: Default::default(),
             node_specific_data: NodeSpecificData::None,
             template_contents: None,
+            attr_cache: AttributeCache::default(),
             // listeners: FxHashSet::default(),
         };
         data.flush_is_focussable();
@@ -346,6 +440,81 @@ impl ElementNodeData {
         attr.value.parse::<T>().ok()
     }
 
+    /// Parse the attribute `name` using `parse`, reusing the previously parsed value if the attribute's
+    /// raw string has not changed since it was last parsed.
+    pub fn attr_parsed_cached<T: Clone + Send + Sync + 'static>(
+        &self,
+        name: &str,
+        parse: impl FnOnce(&str) -> Option<T>,
+    ) -> Option<T> {
+        let name = LocalName::from(name);
+        let mut entries = self.attr_cache.entries.borrow_mut();
+        let Some(raw) = self.attr(name.clone()) else {
+            entries.remove(&name);
+            return None;
+        };
+
+        if let Some(cached) = entries.get(&name) {
+            if cached.raw == raw {
+                if let Some(value) = cached.value.downcast_ref::<Option<T>>() {
+                    return value.clone();
+                }
+            }
+        }
+
+        let value = parse(raw);
+        entries.insert(
+            name,
+            CachedAttribute {
+                raw: raw.to_string(),
+                value: Arc::new(value.clone()),
+            },
+        );
+        value
+    }
+
+    /// Set the attribute `name` to `value`, replacing the value of any existing attribute with the same local name
+    pub fn set_attribute(&mut self, name: QualName, value: String) {
+        if name.local == local_name!("id") {
+            self.id = Some(Atom::from(value.as_str()));
+        }
+
+        match self
+            .attrs
+            .iter_mut()
+            .find(|attr| attr.name.local == name.local)
+        {
+            Some(attr) => attr.value = value,
+            None => self.attrs.push(Attribute { name, value }),
+        }
+        self.flush_is_focussable();
+    }
+
+    /// Add the attribute `name` (with an empty value) if it is absent, or remove it if it is present.
+    /// Returns whether the attribute is present after toggling.
+    pub fn toggle_attribute(&mut self, name: QualName) -> bool {
+        if name.local == local_name!("id") {
+            self.id = None;
+        }
+
+        let is_present = match self.attrs.iter().position(|attr| attr.name == name) {
+            Some(idx) => {
+                self.attrs.remove(idx);
+                false
+            }
+            None => {
+                self.attrs.push(Attribute {
+                    name,
+                    value: String::new(),
+                });
+                true
+            }
+        };
+
+        self.flush_is_focussable();
+        is_present
+    }
+
     pub fn image_data(&self) -> Option<&ImageData> {
         match self.node_specific_data {
             NodeSpecificData::Image(ref data) => Some(data),
@@ -403,7 +572,8 @@ impl ElementNodeData {
     }
 
     pub fn flush_is_focussable(&mut self) {
-        let disabled: bool = self.attr_parsed(local_name!("disabled")).unwrap_or(false);
+        // `disabled` is a boolean attribute, so its presence (whatever its value) disables the element
+        let disabled = self.attr(local_name!("disabled")).is_some();
         let tabindex: Option<i32> = self.attr_parsed(local_name!("tabindex"));
 
         self.is_focussable = !disabled
@@ -475,6 +645,9 @@ pub struct TextInputData {
     pub editor: Box<parley::editor::TextEditor<String>>,
     /// Whether the input is a singleline or multiline input
     pub is_multiline: bool,
+    /// Horizontal offset (in scaled pixels) by which the text of a singleline input is scrolled
+    /// so that the caret remains visible when the text overflows the input's content box
+    pub scroll_offset: f32,
 }
 
 impl TextInputData {
@@ -483,8 +656,62 @@ impl TextInputData {
         Self {
             editor,
             is_multiline,
+            scroll_offset: 0.0,
         }
     }
+
+    /// The current text of the input
+    pub fn text(&self) -> String {
+        self.editor.text().to_string()
+    }
+
+    /// Replace the text of the input. The editor clamps the caret and selection to the new text.
+    pub fn set_text(&mut self, text: String) {
+        self.editor.set_text(text);
+        self.scroll_offset = 0.0;
+    }
+
+    /// Update the scroll offset such that the caret is within a content box of `visible_width` (scaled pixels)
+    pub fn scroll_to_cursor(&mut self, visible_width: f32) {
+        // Multiline inputs wrap their text rather than scrolling horizontally
+        if self.is_multiline {
+            self.scroll_offset = 0.0;
+            return;
+        }
+
+        let text_width = self.editor.layout().width();
+        if let Some(cursor_line) = self.editor.get_cursor_line() {
+            self.scroll_offset = caret_scroll_offset(
+                self.scroll_offset,
+                cursor_line.p0.x as f32,
+                text_width,
+                visible_width,
+            );
+        }
+    }
+}
+
+/// Computes the horizontal scroll offset that keeps a caret at `caret_x` within `visible_width`,
+/// scrolling as little as possible from the `current` offset.
+fn caret_scroll_offset(current: f32, caret_x: f32, text_width: f32, visible_width: f32) -> f32 {
+    let offset = if caret_x < current {
+        caret_x
+    } else if caret_x > current + visible_width {
+        caret_x - visible_width
+    } else {
+        current
+    };
+
+    // Don't leave empty space after the end of the text once it no longer overflows
+    let max_offset = (text_width.max(caret_x) - visible_width).max(0.0);
+    offset.clamp(0.0, max_offset)
+}
+
+/// Which side of an element's content a pseudo-element's generated content is placed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PseudoContent {
+    Before,
+    After,
 }
 
 /// Heterogeneous data that depends on the element's type.
@@ -631,6 +858,30 @@ impl Node {
             .map(|id| self.with(*id))
     }
 
+    /// The text direction of the node, as established by the `dir` attribute (or `direction` property) of it or its
+    /// nearest ancestor. Text nodes take the direction of their parent.
+    pub fn direction(&self) -> style::computed_values::direction::T {
+        let styles = self.primary_styles().or_else(|| {
+            self.parent
+                .and_then(|parent_id| self.with(parent_id).primary_styles())
+        });
+        styles
+            .map(|style| style.clone_direction())
+            .unwrap_or(style::computed_values::direction::T::Ltr)
+    }
+
+    /// Find the nearest inclusive ancestor (this node, its parent, its grandparent, ...) which satisfies `predicate`.
+    /// This mirrors the DOM's `Element.closest()`.
+    pub fn closest(&self, predicate: impl Fn(&Node) -> bool) -> Option<usize> {
+        let mut node = self;
+        loop {
+            if predicate(node) {
+                return Some(node.id);
+            }
+            node = self.with(node.parent?);
+        }
+    }
+
     pub fn is_element(&self) -> bool {
         matches!(self.raw_dom_data, NodeData::Element { .. })
     }
@@ -717,6 +968,88 @@ impl Node {
         Some(&attr.value)
     }
 
+    /// The text generated before or after the element's content by its `::before` or `::after` pseudo-element,
+    /// taken from its `before-content` or `after-content` attribute. Generated content is added to the element's
+    /// inline layout but not to the tree, so it is only displayed within inline formatting contexts.
+    ///
+    /// TODO: use the `content` property of the element's pseudo-element styles
+    pub fn generated_content(&self, pseudo: PseudoContent) -> Option<&str> {
+        let name = match pseudo {
+            PseudoContent::Before => "before-content",
+            PseudoContent::After => "after-content",
+        };
+        self.attr(LocalName::from(name))
+            .filter(|content| !content.is_empty())
+    }
+
+    /// Whether the element has `content-visibility: auto`, so that its content is only laid out and painted while
+    /// it is in the viewport (see [`Document::is_content_skipped`](crate::Document::is_content_skipped))
+    pub fn has_auto_content_visibility(&self) -> bool {
+        self.primary_styles().is_some_and(|style| {
+            style.get_box().clone_content_visibility() == ContentVisibility::Auto
+        })
+    }
+
+    /// The size of the placeholder which stands in for the element's content while it is skipped, from its
+    /// `contain-intrinsic-size`. Axes where it is `none` have no placeholder size.
+    ///
+    /// TODO: `auto <length>` should use the last size the content was laid out at, rather than the length
+    pub fn contain_intrinsic_size(&self) -> taffy::Size<f32> {
+        let Some(style) = self.primary_styles() else {
+            return taffy::Size::ZERO;
+        };
+        let length = |size: &ContainIntrinsicSize| match size {
+            ContainIntrinsicSize::Length(length) | ContainIntrinsicSize::AutoLength(length) => {
+                length.0.px()
+            }
+            ContainIntrinsicSize::None | ContainIntrinsicSize::AutoNone => 0.0,
+        };
+        let position = style.get_position();
+        taffy::Size {
+            width: length(&position.contain_intrinsic_width),
+            height: length(&position.contain_intrinsic_height),
+        }
+    }
+
+    /// Whether this node has the `inert` attribute itself
+    pub fn has_inert_attribute(&self) -> bool {
+        self.attr(LocalName::from("inert")).is_some()
+    }
+
+    /// Whether this node is within the subtree of an element with the `inert` attribute. Inert nodes are still
+    /// painted, but they can't be focussed, are never the target of pointer events, and are hidden from
+    /// assistive technologies.
+    ///
+    /// This is cached when the document is resolved, so it doesn't reflect changes to the tree or attributes since.
+    pub fn is_inert(&self) -> bool {
+        self.inert
+    }
+
+    /// Whether this node is an editable text field whose content should be spellchecked. The `spellcheck`
+    /// attribute (`true` or `false`) is inherited from the nearest ancestor which sets it, and defaults to true.
+    /// Password fields are never spellchecked.
+    pub fn is_spellchecked(&self) -> bool {
+        let Some(element) = self.element_data() else {
+            return false;
+        };
+        if element.text_input_data().is_none()
+            || element.attr(local_name!("type")) == Some("password")
+        {
+            return false;
+        }
+
+        let mut node = Some(self);
+        while let Some(current) = node {
+            match current.attr(local_name!("spellcheck")) {
+                Some("false") => return false,
+                Some("true" | "") => return true,
+                // Invalid values are ignored
+                _ => node = current.parent.map(|parent_id| self.with(parent_id)),
+            }
+        }
+        true
+    }
+
     pub fn primary_styles(&self) -> Option<AtomicRef<'_, ComputedValues>> {
         let stylo_element_data = self.stylo_element_data.borrow();
         if stylo_element_data
@@ -770,35 +1103,169 @@ impl Node {
             .unwrap_or(0)
     }
 
+    /// Register a listener for the event `name` (if one isn't already registered)
+    pub fn add_event_listener(&mut self, name: &str) {
+        self.add_event_listener_with_options(name, ListenerOptions::default());
+    }
+
+    /// Register a listener for the event `name` with `options`, if one isn't already registered for the same phase.
+    /// As in the DOM, a node can have both a capturing and a non-capturing listener for an event.
+    pub fn add_event_listener_with_options(&mut self, name: &str, options: ListenerOptions) {
+        let is_registered = self
+            .listeners
+            .iter()
+            .any(|listener| listener.name == name && listener.options.capture == options.capture);
+        if !is_registered {
+            self.listeners.push(EventListener {
+                name: name.to_string(),
+                options,
+            });
+        }
+    }
+
+    pub fn remove_event_listener(&mut self, name: &str) {
+        self.listeners.retain(|listener| listener.name != name);
+    }
+
+    pub fn has_event_listener(&self, name: &str) -> bool {
+        self.listeners.iter().any(|listener| listener.name == name)
+    }
+
+    /// Takes an (x, y) position (relative to this node's top-left corner) and returns the same position
+    /// relative to the top-left corner of the document
+    pub fn absolute_position(&self, x: f32, y: f32) -> Point<f32> {
+        let x = x + self.final_layout.location.x;
+        let y = y + self.final_layout.location.y;
+
+        match self.parent {
+            Some(parent_id) => self.with(parent_id).absolute_position(x, y),
+            None => Point { x, y },
+        }
+    }
+
+    /// Whether the node's content can be scrolled horizontally and vertically (`overflow: scroll` or `overflow: auto`)
+    pub fn scrollable_axes(&self) -> (bool, bool) {
+        use style::values::computed::Overflow;
+
+        let Some(style) = self.primary_styles() else {
+            return (false, false);
+        };
+        let scrollable = |overflow| matches!(overflow, Overflow::Scroll | Overflow::Auto);
+        let box_styles = style.get_box();
+        (
+            scrollable(box_styles.overflow_x),
+            scrollable(box_styles.overflow_y),
+        )
+    }
+
+    /// The largest scroll offset of the node: how far its content extends beyond its padding box
+    pub fn max_scroll_offset(&self) -> peniko::kurbo::Vec2 {
+        let layout = &self.final_layout;
+        let (mut content_right, mut content_bottom) = (0.0f32, 0.0f32);
+        for &child_id in self
+            .layout_children
+            .borrow()
+            .as_deref()
+            .unwrap_or(&self.children)
+        {
+            let child = &self.with(child_id).final_layout;
+            content_right = content_right.max(child.location.x + child.size.width);
+            content_bottom = content_bottom.max(child.location.y + child.size.height);
+        }
+
+        // Child locations are relative to the border box. Scrollbars take space away from the client area.
+        let client_width = layout.size.width
+            - layout.border.left
+            - layout.border.right
+            - layout.scrollbar_size.width;
+        let client_height = layout.size.height
+            - layout.border.top
+            - layout.border.bottom
+            - layout.scrollbar_size.height;
+        peniko::kurbo::Vec2::new(
+            (content_right + layout.padding.right - layout.border.left - client_width).max(0.0)
+                as f64,
+            (content_bottom + layout.padding.bottom - layout.border.top - client_height).max(0.0)
+                as f64,
+        )
+    }
+
     /// Takes an (x, y) position (relative to the *parent's* top-left corner) and returns:
     ///    - None if the position is outside of this node's bounds
     ///    - Some(HitResult) if the position is within the node but doesn't match any children
     ///    - The result of recursively calling child.hit() on the the child element that is
     ///      positioned at that position if there is one.
     ///
+    /// Children of a scroll container are hit-tested at the position offset by its `scroll_offset`, as they are
+    /// drawn scrolled by that amount.
+    ///
     /// TODO: z-index
     /// (If multiple children are positioned at the position then a random one will be recursed into)
     pub fn hit(&self, x: f32, y: f32) -> Option<HitResult> {
+        // Inert subtrees are never hit, so pointer events pass through to whatever is beneath them
+        if self.inert {
+            return None;
+        }
+
         let x = x - self.final_layout.location.x;
         let y = y - self.final_layout.location.y;
 
+        // Map the position into the node's untransformed coordinate space
+        let transform = self.local_transform();
+        let (x, y) = if transform == peniko::kurbo::Affine::IDENTITY {
+            (x, y)
+        } else if transform.determinant() == 0.0 {
+            // The node (and its subtree) is collapsed by its transform (e.g. by `scale(0)`) so covers no area
+            return None;
+        } else {
+            let point = transform.inverse() * peniko::kurbo::Point::new(x as f64, y as f64);
+            (point.x as f32, point.y as f32)
+        };
+
         let size = self.final_layout.size;
         if x < 0.0 || x > size.width || y < 0.0 || y > size.height {
             return None;
         }
 
         // Call `.hit()` on each child in turn. If any return `Some` then return that value. Else return `Some(self.id).
+        // Children are positioned relative to the scrolled content.
+        // Children are tested even if this node has `pointer-events: none`, as they may set it back to `auto`.
+        let child_x = x + self.scroll_offset.x as f32;
+        let child_y = y + self.scroll_offset.y as f32;
         self.children
             .iter()
-            .find_map(|&i| self.with(i).hit(x, y))
-            .or(Some(HitResult {
-                node_id: self.id,
-                x,
-                y,
-            }))
+            .find_map(|&i| self.with(i).hit(child_x, child_y))
+            .or_else(|| {
+                (self.pointer_events() != PointerEvents::None).then_some(HitResult {
+                    node_id: self.id,
+                    x,
+                    y,
+                })
+            })
+    }
+
+    /// The computed `pointer-events` of this node. Nodes with `pointer-events: none` are never the target of
+    /// pointer events (which instead pass through to whatever is beneath them). Text nodes use their parent's value.
+    pub fn pointer_events(&self) -> PointerEvents {
+        let styles = self.primary_styles().or_else(|| {
+            self.parent
+                .and_then(|parent| self.with(parent).primary_styles())
+        });
+        styles
+            .map(|style| style.get_inherited_ui().pointer_events)
+            .unwrap_or(PointerEvents::Auto)
+    }
+
+    /// The computed `object-fit` of this node, which determines how replaced content (such as an image) is sized
+    /// within its content box (see [`Document::object_fit_rect`](crate::Document::object_fit_rect))
+    pub fn object_fit(&self) -> ObjectFit {
+        self.primary_styles()
+            .map(|style| style.get_position().object_fit)
+            .unwrap_or(ObjectFit::Fill)
     }
 }
 
+/// Nodes are equal if they have the same id (i.e. they are the same node of the same document).
 /// It might be wrong to expose this since what does *equality* mean outside the dom?
 impl PartialEq for Node {
     fn eq(&self, other: &Self) -> bool {
@@ -826,3 +1293,196 @@ impl std::fmt::Debug for Node {
             .finish()
     }
 }
+
+#[test]
+fn caret_at_end_of_long_text_is_scrolled_into_view() {
+    let offset = caret_scroll_offset(0.0, 500.0, 500.0, 100.0);
+    assert_eq!(offset, 400.0);
+    assert!(500.0 - offset <= 100.0);
+
+    // Moving the caret back to the start scrolls back to the beginning of the text
+    assert_eq!(caret_scroll_offset(offset, 0.0, 500.0, 100.0), 0.0);
+
+    // Text which fits within the input is never scrolled
+    assert_eq!(caret_scroll_offset(0.0, 80.0, 80.0, 100.0), 0.0);
+}
+
+#[test]
+fn toggle_attribute_flips_presence() {
+    let name = |local| QualName::new(None, ns!(html), local);
+    let mut button = ElementNodeData::new(name(local_name!("button")), Vec::new());
+
+    assert!(button.toggle_attribute(name(local_name!("disabled"))));
+    assert_eq!(button.attr(local_name!("disabled")), Some(""));
+
+    assert!(!button.toggle_attribute(name(local_name!("disabled"))));
+    assert_eq!(button.attr(local_name!("disabled")), None);
+}
+
+#[test]
+fn attr_parsed_cached_only_reparses_changed_attributes() {
+    use std::cell::Cell;
+
+    let name = |local| QualName::new(None, ns!(html), local);
+    let mut div = ElementNodeData::new(
+        name(local_name!("div")),
+        vec![Attribute {
+            name: name(local_name!("bgcolor")),
+            value: String::from("red"),
+        }],
+    );
+
+    let parse_count = Cell::new(0);
+    let parse = |raw: &str| {
+        parse_count.set(parse_count.get() + 1);
+        crate::util::parse_color(raw)
+    };
+
+    let red = Some([255.0, 0.0, 0.0, 1.0]);
+    assert_eq!(div.attr_parsed_cached("bgcolor", parse), red);
+    assert_eq!(div.attr_parsed_cached("bgcolor", parse), red);
+    assert_eq!(parse_count.get(), 1);
+
+    div.attrs[0].value = String::from("blue");
+    assert_eq!(
+        div.attr_parsed_cached("bgcolor", parse),
+        Some([0.0, 0.0, 255.0, 1.0])
+    );
+    assert_eq!(parse_count.get(), 2);
+}
+
+#[test]
+fn builds_elements_with_attributes() {
+    let data = NodeData::new_element("a")
+        .with_attribute("href", "https://example.com")
+        .with_attribute("id", "link");
+
+    let NodeData::Element(element) = &data else {
+        panic!("expected an element, got {:?}", data.kind());
+    };
+    assert_eq!(element.name.local, local_name!("a"));
+    assert_eq!(element.name.ns, ns!(html));
+    assert_eq!(
+        element.attr(local_name!("href")),
+        Some("https://example.com")
+    );
+    assert_eq!(element.id, Some(Atom::from("link")));
+    assert!(element.is_focussable);
+
+    let svg = NodeData::new_element("svg").with_namespace(ns!(svg));
+    assert_eq!(svg.downcast_element().unwrap().name.ns, ns!(svg));
+
+    let text = NodeData::new_text("hello");
+    assert!(matches!(text, NodeData::Text(ref data) if data.content == "hello"));
+}
+
+#[test]
+fn attribute_names_are_interned() {
+    let a = NodeData::new_element("div").with_attribute("data-interned-name", "1");
+    let b = NodeData::new_element("span").with_attribute("data-interned-name", "2");
+
+    let name = |data: &NodeData| data.attrs().unwrap()[0].name.local.clone();
+    assert_eq!(name(&a), name(&b));
+    assert!(std::ptr::eq(name(&a).as_ptr(), name(&b).as_ptr()));
+
+    assert_eq!(a.attr(LocalName::from("data-interned-name")), Some("1"));
+    assert_eq!(b.attr(LocalName::from("data-interned-name")), Some("2"));
+}
+
+#[test]
+fn closest_finds_the_nearest_matching_ancestor() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="outer" class="card"><div id="inner" class="card"><p><span id="label">Hello</span></p></div></div>
+    </body></html>"#;
+    let doc = parse(html);
+    let label = &doc.nodes[by_id(&doc, "label")];
+    let text = label.with(label.children[0]);
+    assert!(text.is_text_node());
+
+    let is_card = |node: &Node| node.attr(local_name!("class")) == Some("card");
+    assert_eq!(text.closest(is_card), Some(by_id(&doc, "inner")));
+    assert_eq!(label.closest(|node| node.id == label.id), Some(label.id));
+    assert_eq!(
+        text.closest(|node| node.attr(local_name!("class")) == Some("missing")),
+        None
+    );
+}
+
+#[test]
+fn dir_attribute_sets_direction_of_subtree() {
+    use crate::testing::{by_id, parse};
+    use style::computed_values::direction::T as Direction;
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="rtl" dir="rtl"><p id="child">Hello</p><p id="override" dir="ltr">Hello</p></div>
+        <p id="auto" dir="auto">123 שלום</p>
+        <p id="default">Hello</p>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let direction = |name: &str| doc.nodes[by_id(&doc, name)].direction();
+
+    assert_eq!(direction("rtl"), Direction::Rtl);
+    assert_eq!(direction("child"), Direction::Rtl);
+    assert_eq!(direction("override"), Direction::Ltr);
+    assert_eq!(direction("auto"), Direction::Rtl);
+    assert_eq!(direction("default"), Direction::Ltr);
+}
+
+#[test]
+fn attributes_iterate_in_insertion_order() {
+    let mut data = NodeData::new_element("div")
+        .with_attribute("zeta", "1")
+        .with_attribute("alpha", "2")
+        .with_attribute("mu", "3");
+    data.downcast_element_mut().unwrap().set_attribute(
+        QualName::new(None, ns!(), LocalName::from("alpha")),
+        "4".into(),
+    );
+
+    let attrs: Vec<(&str, &str)> = data
+        .attrs()
+        .unwrap()
+        .iter()
+        .map(|attr| (attr.name.local.as_ref(), attr.value.as_str()))
+        .collect();
+    assert_eq!(attrs, vec![("zeta", "1"), ("alpha", "4"), ("mu", "3")]);
+}
+
+#[test]
+fn pointer_events_none_passes_hits_through_except_to_reenabled_descendants() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body style="margin: 0">
+        <div id="overlay" style="pointer-events: none; position: absolute; width: 200px; height: 200px">
+            <div id="reenabled" style="pointer-events: auto; width: 50px; height: 50px"></div>
+        </div>
+        <div id="content" style="width: 200px; height: 200px"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    // The overlay covers the content but lets clicks through to it
+    let hit = doc.hit(100.0, 100.0).unwrap();
+    assert_eq!(hit.node_id, by_id(&doc, "content"));
+
+    // A descendant of the overlay which re-enables pointer events is still clickable
+    let hit = doc.hit(25.0, 25.0).unwrap();
+    assert_eq!(hit.node_id, by_id(&doc, "reenabled"));
+}
+
+#[test]
+fn nodes_know_their_own_id_and_compare_by_it() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><p id="a"></p><p id="b"></p></body></html>"#;
+    let mut doc = parse(html);
+    let (a, b) = (by_id(&doc, "a"), by_id(&doc, "b"));
+
+    assert_eq!(doc.get_node(a).unwrap().id, a);
+    assert_eq!(doc.get_node_mut(b).unwrap().id, b);
+    assert_eq!(doc.get_node(a), doc.get_node(a));
+    assert_ne!(doc.get_node(a), doc.get_node(b));
+}
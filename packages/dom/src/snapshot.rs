@@ -0,0 +1,188 @@
+//! Capturing and restoring the state of a document's tree (e.g. for time-travel debugging)
+
+use std::collections::HashMap;
+
+use slab::Slab;
+
+use crate::{Document, Node, NodeData};
+
+/// A copy of a document's tree structure and node data, created with [`Document::snapshot_tree`].
+///
+/// Styles and layout are not captured: they are derived from the tree and are recomputed on the next
+/// [`Document::resolve`] after the snapshot is restored.
+#[derive(Debug, Clone)]
+pub struct TreeSnapshot {
+    nodes: Vec<NodeSnapshot>,
+    nodes_to_id: HashMap<String, usize>,
+    hover_node_id: Option<usize>,
+    focus_node_id: Option<usize>,
+    scroll_offset: f64,
+}
+
+#[derive(Debug, Clone)]
+struct NodeSnapshot {
+    id: usize,
+    parent: Option<usize>,
+    child_idx: usize,
+    children: Vec<usize>,
+    data: NodeData,
+}
+
+impl Document {
+    /// Capture the current tree so that it can later be restored with [`Document::restore_tree`]
+    pub fn snapshot_tree(&self) -> TreeSnapshot {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(id, node)| NodeSnapshot {
+                id,
+                parent: node.parent,
+                child_idx: node.child_idx,
+                children: node.children.clone(),
+                data: node.raw_dom_data.clone(),
+            })
+            .collect();
+
+        TreeSnapshot {
+            nodes,
+            nodes_to_id: self.nodes_to_id.clone(),
+            hover_node_id: self.hover_node_id,
+            focus_node_id: self.focus_node_id,
+            scroll_offset: self.scroll_offset,
+        }
+    }
+
+    /// Replace the entire tree with a previously captured snapshot. Node ids are preserved exactly, as are the
+    /// hovered and focussed nodes. The text selection, queued scrolling, in-flight transitions and data attached
+    /// with [`Document::set_user_data`] are discarded.
+    pub fn restore_tree(&mut self, snapshot: &TreeSnapshot) {
+        let slab_ptr = self.nodes.as_mut() as *mut Slab<Node>;
+        let nodes: Slab<Node> = snapshot
+            .nodes
+            .iter()
+            .map(|snapshot| {
+                let mut node = Node::new(
+                    slab_ptr,
+                    snapshot.id,
+                    self.guard.clone(),
+                    snapshot.data.clone(),
+                );
+                node.parent = snapshot.parent;
+                node.child_idx = snapshot.child_idx;
+                node.children = snapshot.children.clone();
+                if node.raw_dom_data.downcast_element().is_some() {
+                    *node.stylo_element_data.borrow_mut() = Some(Default::default());
+                }
+                (snapshot.id, node)
+            })
+            .collect();
+
+        // Replace the contents of the box (rather than the box itself) so that the tree pointers stay valid
+        *self.nodes = nodes;
+
+        self.nodes_to_id = snapshot.nodes_to_id.clone();
+        self.scroll_offset = snapshot.scroll_offset;
+
+        // Node states aren't captured, so hover and focus are re-applied to the restored nodes
+        self.hover_node_id = snapshot.hover_node_id.filter(|&id| self.nodes.contains(id));
+        let mut maybe_id = self.hover_node_id;
+        while let Some(id) = maybe_id {
+            self.nodes[id].hover();
+            maybe_id = self.nodes[id].parent;
+        }
+        self.focus_node_id = snapshot.focus_node_id.filter(|&id| self.nodes.contains(id));
+        if let Some(id) = self.focus_node_id {
+            self.nodes[id].focus(self.focus_visible);
+        }
+
+        // Other state which refers to nodes may refer to nodes which no longer exist, or to different nodes which
+        // now have the same ids, so it is reset. This includes data attached by integrations.
+        self.focus_trap_root = None;
+        self.autofocus_request = None;
+        self.text_selection = None;
+        self.clipboard_text = None;
+        self.pending_scroll = None;
+        self.user_data.clear();
+        self.animations.sets.write().clear();
+        self.child_count_changes.clear();
+        self.relayout_nodes.clear();
+        self.relevant_content.clear();
+
+        self.changed = self.nodes.iter().map(|(id, _)| id).collect();
+        self.invalidate_all_layout();
+    }
+}
+
+#[test]
+fn restoring_a_snapshot_undoes_later_mutations() {
+    use crate::testing::{by_id, parse};
+
+    fn dump(doc: &Document) -> Vec<String> {
+        doc.nodes
+            .iter()
+            .map(|(id, node)| {
+                format!(
+                    "{id} {:?} {:?} {:?}",
+                    node.parent,
+                    node.children,
+                    node.raw_dom_data.attrs()
+                )
+            })
+            .collect()
+    }
+
+    let html = r#"<!DOCTYPE html><html><body><div id="list" class="a"></div></body></html>"#;
+    let mut doc = parse(html);
+    let list_id = by_id(&doc, "list");
+
+    let first = doc.create_text_node("first");
+    doc.append(list_id, &[first]);
+    let before = dump(&doc);
+    let snapshot = doc.snapshot_tree();
+
+    let second = doc.create_text_node("second");
+    doc.append(list_id, &[second]);
+    doc.nodes[list_id]
+        .raw_dom_data
+        .downcast_element_mut()
+        .unwrap()
+        .attrs[1]
+        .value = String::from("b");
+    doc.remove_node(first);
+    assert_ne!(dump(&doc), before);
+
+    doc.restore_tree(&snapshot);
+    assert_eq!(dump(&doc), before);
+    assert!(doc.debug_check_invariants().is_empty());
+}
+
+#[test]
+fn restoring_a_snapshot_resets_state_which_refers_to_nodes() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body><input id="name"><div id="list"></div></body></html>"#;
+    let mut doc = parse(html);
+    let input_id = by_id(&doc, "name");
+    let list_id = by_id(&doc, "list");
+    doc.set_focus_to(input_id);
+    let snapshot = doc.snapshot_tree();
+
+    let text = doc.create_text_node("added");
+    doc.append(list_id, &[text]);
+    doc.set_focus_to(list_id);
+    doc.set_user_data(text, Box::new(1u32));
+    doc.set_text_selection(text, 0, 5);
+    doc.clipboard_text = Some(String::from("copied"));
+    doc.queue_scroll(10.0, 10.0, 0.0, 20.0);
+
+    doc.restore_tree(&snapshot);
+    assert!(!doc.nodes.contains(text));
+    assert_eq!(doc.get_focus_node_id(), Some(input_id));
+    assert!(doc.nodes[input_id].is_focussed());
+    assert!(!doc.nodes[list_id].is_focussed());
+    assert!(doc.user_data::<u32>(text).is_none());
+    assert_eq!(doc.text_selection(), None);
+    assert_eq!(doc.take_clipboard_text(), None);
+    assert!(doc.pending_scroll.is_none());
+    assert!(doc.debug_check_invariants().is_empty());
+}
@@ -6,6 +6,7 @@ use std::sync::atomic::Ordering;
 use crate::node::Node;
 
 use crate::node::NodeData;
+use crate::util::parse_color;
 use atomic_refcell::{AtomicRef, AtomicRefMut};
 use html5ever::{local_name, LocalName, Namespace};
 use selectors::{
@@ -19,11 +20,11 @@ use style::properties::{Importance, PropertyDeclaration};
 use style::rule_tree::CascadeLevel;
 use style::selector_parser::PseudoElement;
 use style::stylesheets::layer_rule::LayerOrder;
-use style::values::computed::Percentage;
+use style::values::computed::{Overflow, Percentage};
 use style::values::specified::box_::DisplayOutside;
 use style::CaseSensitivityExt;
 use style::{
-    animation::DocumentAnimationSet,
+    animation::AnimationSetKey,
     context::{
         QuirksMode, RegisteredSpeculativePainter, RegisteredSpeculativePainters,
         SharedStyleContext, StyleContext,
@@ -48,6 +49,8 @@ use style::values::computed::text::TextAlign as StyloTextAlign;
 impl crate::document::Document {
     /// Walk the whole tree, converting styles to layout
     pub fn flush_styles_to_layout(&mut self, children: Vec<usize>) {
+        let scrollbar_width = self.scrollbar_width;
+
         // make a floating element
         for child in children.iter() {
             let (display, mut children) = {
@@ -62,6 +65,16 @@ impl crate::document::Document {
                 };
 
                 node.style = stylo_to_taffy::entire_style(style);
+                node.style.scrollbar_width = scrollbar_width;
+
+                // `overflow: auto` only reserves space for a scrollbar when the content overflows
+                let box_styles = style.get_box();
+                if box_styles.overflow_x == Overflow::Auto && !node.auto_scrollbars.x {
+                    node.style.overflow.x = taffy::Overflow::Hidden;
+                }
+                if box_styles.overflow_y == Overflow::Auto && !node.auto_scrollbars.y {
+                    node.style.overflow.y = taffy::Overflow::Hidden;
+                }
 
                 node.display_outer = match style.clone_display().outside() {
                     DisplayOutside::None => crate::node::DisplayOuter::None,
@@ -71,10 +84,6 @@ impl crate::document::Document {
                     DisplayOutside::InternalTable => crate::node::DisplayOuter::Block,
                 };
 
-                // Clear Taffy cache
-                // TODO: smarter cache invalidation
-                node.cache.clear();
-
                 // would like to change this not require a clone, but requires some refactoring
                 (
                     node.style.display,
@@ -129,8 +138,8 @@ impl crate::document::Document {
             options: GLOBAL_STYLE_DATA.options.clone(),
             guards,
             visited_styles_enabled: false,
-            animations: DocumentAnimationSet::default().clone(),
-            current_time_for_animations: 0.0,
+            animations: self.animations.clone(),
+            current_time_for_animations: self.animation_time,
             snapshot_map: &self.snapshots,
             registered_speculative_painters: &RegisteredPaintersImpl,
         };
@@ -540,16 +549,24 @@ impl<'a> TElement for BlitzNode<'a> {
 
     fn animation_rule(
         &self,
-        _: &SharedStyleContext,
+        context: &SharedStyleContext,
     ) -> Option<Arc<Locked<PropertyDeclarationBlock>>> {
-        None
+        context.animations.get_animation_declarations(
+            &AnimationSetKey::new_for_non_pseudo(TNode::opaque(self)),
+            context.current_time_for_animations,
+            &self.guard,
+        )
     }
 
     fn transition_rule(
         &self,
-        _context: &SharedStyleContext,
+        context: &SharedStyleContext,
     ) -> Option<Arc<Locked<PropertyDeclarationBlock>>> {
-        None
+        context.animations.get_transition_declarations(
+            &AnimationSetKey::new_for_non_pseudo(TNode::opaque(self)),
+            context.current_time_for_animations,
+            &self.guard,
+        )
     }
 
     fn state(&self) -> ElementState {
@@ -660,27 +677,29 @@ impl<'a> TElement for BlitzNode<'a> {
     }
 
     fn may_have_animations(&self) -> bool {
-        false
+        true
     }
 
-    fn has_animations(&self, _context: &SharedStyleContext) -> bool {
-        false
+    fn has_animations(&self, context: &SharedStyleContext) -> bool {
+        self.has_css_animations(context, None) || self.has_css_transitions(context, None)
     }
 
     fn has_css_animations(
         &self,
-        _context: &SharedStyleContext,
-        _pseudo_element: Option<style::selector_parser::PseudoElement>,
+        context: &SharedStyleContext,
+        pseudo_element: Option<style::selector_parser::PseudoElement>,
     ) -> bool {
-        false
+        let key = AnimationSetKey::new(TNode::opaque(self), pseudo_element);
+        context.animations.has_active_animations(&key)
     }
 
     fn has_css_transitions(
         &self,
-        _context: &SharedStyleContext,
-        _pseudo_element: Option<style::selector_parser::PseudoElement>,
+        context: &SharedStyleContext,
+        pseudo_element: Option<style::selector_parser::PseudoElement>,
     ) -> bool {
-        false
+        let key = AnimationSetKey::new(TNode::opaque(self), pseudo_element);
+        context.animations.has_active_transitions(&key)
     }
 
     fn shadow_root(&self) -> Option<<Self::ConcreteNode as TNode>::ConcreteShadowRoot> {
@@ -744,29 +763,6 @@ impl<'a> TElement for BlitzNode<'a> {
             ));
         };
 
-        fn parse_color_attr(value: &str) -> Option<(u8, u8, u8, f32)> {
-            if !value.starts_with('#') {
-                return None;
-            }
-
-            let value = &value[1..];
-            if value.len() == 3 {
-                let r = u8::from_str_radix(&value[0..1], 16).ok()?;
-                let g = u8::from_str_radix(&value[1..2], 16).ok()?;
-                let b = u8::from_str_radix(&value[2..3], 16).ok()?;
-                return Some((r, g, b, 1.0));
-            }
-
-            if value.len() == 6 {
-                let r = u8::from_str_radix(&value[0..2], 16).ok()?;
-                let g = u8::from_str_radix(&value[2..4], 16).ok()?;
-                let b = u8::from_str_radix(&value[4..6], 16).ok()?;
-                return Some((r, g, b, 1.0));
-            }
-
-            None
-        }
-
         fn parse_size_attr(value: &str) -> Option<style::values::specified::LengthPercentage> {
             use style::values::specified::{AbsoluteLength, LengthPercentage, NoCalcLength};
             if let Some(value) = value.strip_suffix("px") {
@@ -805,8 +801,27 @@ impl<'a> TElement for BlitzNode<'a> {
                 }
             }
 
+            if *name == local_name!("dir") {
+                use style::computed_values::direction::T as Direction;
+
+                // TODO: `dir="auto"` is only re-resolved when the element is restyled, not when its text changes
+                let direction = match value.to_ascii_lowercase().as_str() {
+                    "ltr" => Some(Direction::Ltr),
+                    "rtl" => Some(Direction::Rtl),
+                    "auto" => Some(
+                        crate::util::first_strong_direction(&self.text_content())
+                            .unwrap_or(Direction::Ltr),
+                    ),
+                    _ => None,
+                };
+
+                if let Some(direction) = direction {
+                    push_style(PropertyDeclaration::Direction(direction));
+                }
+            }
+
             if *name == local_name!("width") {
-                if let Some(width) = parse_size_attr(value) {
+                if let Some(width) = elem.attr_parsed_cached("width", parse_size_attr) {
                     use style::values::generics::{length::Size, NonNegative};
                     push_style(PropertyDeclaration::Width(Size::LengthPercentage(
                         NonNegative(width),
@@ -815,7 +830,7 @@ impl<'a> TElement for BlitzNode<'a> {
             }
 
             if *name == local_name!("height") {
-                if let Some(height) = parse_size_attr(value) {
+                if let Some(height) = elem.attr_parsed_cached("height", parse_size_attr) {
                     use style::values::generics::{length::Size, NonNegative};
                     push_style(PropertyDeclaration::Height(Size::LengthPercentage(
                         NonNegative(height),
@@ -825,9 +840,15 @@ impl<'a> TElement for BlitzNode<'a> {
 
             if *name == local_name!("bgcolor") {
                 use style::values::specified::Color;
-                if let Some((r, g, b, a)) = parse_color_attr(value) {
+                if let Some([r, g, b, a]) = elem.attr_parsed_cached("bgcolor", parse_color) {
+                    let color = AbsoluteColor::srgb_legacy(
+                        r.round() as u8,
+                        g.round() as u8,
+                        b.round() as u8,
+                        a,
+                    );
                     push_style(PropertyDeclaration::BackgroundColor(
-                        Color::from_absolute_color(AbsoluteColor::srgb_legacy(r, g, b, a)),
+                        Color::from_absolute_color(color),
                     ));
                 }
             }
@@ -1003,3 +1024,171 @@ fn parse_inline() {
 
     // let val = CSSInlineStyleDeclaration();
 }
+
+#[test]
+fn custom_properties_are_inherited_and_resolved_with_fallbacks() {
+    use crate::testing::{by_id, parse};
+    use crate::Document;
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="theme" style="--accent: rgb(0, 128, 0)">
+            <p id="inherited" style="color: var(--accent)"></p>
+            <p id="fallback" style="color: var(--missing, blue)"></p>
+            <p id="overridden" style="--accent: red; color: var(--accent)"></p>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let color = |doc: &Document, name: &str| doc.computed_styles(by_id(doc, name))["color"].clone();
+
+    assert_eq!(color(&doc, "inherited"), "rgb(0, 128, 0)");
+    assert_eq!(color(&doc, "fallback"), "rgb(0, 0, 255)");
+    assert_eq!(color(&doc, "overridden"), "rgb(255, 0, 0)");
+
+    // Changing the variable restyles the elements which use it
+    doc.set_style_attribute(by_id(&doc, "theme"), "--accent: rgb(1, 2, 3)");
+    doc.resolve();
+    assert_eq!(color(&doc, "inherited"), "rgb(1, 2, 3)");
+}
+
+#[test]
+fn css_wide_keywords_resolve_against_parent_and_initial_values() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div style="color: rgb(0, 128, 0); background-color: blue; font-weight: bold">
+            <p id="inherit" style="color: red; color: inherit; background-color: inherit"></p>
+            <p id="initial" style="color: initial; font-weight: initial"></p>
+            <p id="unset" style="color: red; color: unset; background-color: red; background-color: unset"></p>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let style =
+        |name: &str, property: &str| doc.computed_styles(by_id(&doc, name))[property].clone();
+
+    // `inherit` takes the parent's value, even for properties which aren't inherited by default
+    assert_eq!(style("inherit", "color"), "rgb(0, 128, 0)");
+    assert_eq!(style("inherit", "background-color"), "rgb(0, 0, 255)");
+
+    // `initial` resets to the property's default rather than inheriting
+    assert_eq!(style("initial", "color"), "rgb(0, 0, 0)");
+    assert_eq!(style("initial", "font-weight"), "400");
+
+    // `unset` inherits inherited properties and resets the others
+    assert_eq!(style("unset", "color"), "rgb(0, 128, 0)");
+    assert_eq!(style("unset", "background-color"), "rgba(0, 0, 0, 0)");
+}
+
+#[test]
+fn margin_and_padding_shorthands_expand_to_each_side() {
+    use crate::testing::{by_id, parse};
+    use taffy::LengthPercentageAuto;
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="one" style="margin: 4px; padding: 4px"></div>
+        <div id="two" style="margin: 4px 8px; padding: 4px 8px"></div>
+        <div id="three" style="margin: 4px 8px 12px; padding: 4px 8px 12px"></div>
+        <div id="four" style="margin: 4px 8px 12px 16px; padding: 4px 8px 12px 16px"></div>
+        <div id="overridden" style="margin: 4px; margin-left: 20px; padding: 4px; padding-top: 20px"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    // Top, right, bottom and left
+    let sides = |name: &str| {
+        let node = &doc.nodes[by_id(&doc, name)];
+        let margin = node.style.margin.map(|margin| match margin {
+            LengthPercentageAuto::Length(length) => length,
+            _ => panic!("Unexpected margin {margin:?}"),
+        });
+        let padding = node.final_layout.padding;
+        (
+            [margin.top, margin.right, margin.bottom, margin.left],
+            [padding.top, padding.right, padding.bottom, padding.left],
+        )
+    };
+
+    assert_eq!(sides("one"), ([4.0; 4], [4.0; 4]));
+    assert_eq!(sides("two"), ([4.0, 8.0, 4.0, 8.0], [4.0, 8.0, 4.0, 8.0]));
+    assert_eq!(
+        sides("three"),
+        ([4.0, 8.0, 12.0, 8.0], [4.0, 8.0, 12.0, 8.0])
+    );
+    assert_eq!(
+        sides("four"),
+        ([4.0, 8.0, 12.0, 16.0], [4.0, 8.0, 12.0, 16.0])
+    );
+    // Longhands declared after the shorthand override it
+    assert_eq!(
+        sides("overridden"),
+        ([4.0, 4.0, 4.0, 20.0], [20.0, 4.0, 4.0, 4.0])
+    );
+}
+
+#[test]
+fn retargeting_a_transition_starts_from_the_current_value() {
+    use crate::testing::{by_id, parse};
+    use crate::Document;
+
+    let html = r#"<!DOCTYPE html><html><head>
+        <style>#box { transition: color 1s linear }</style>
+    </head><body>
+        <div id="box" style="color: rgb(255, 0, 0)"></div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let node_id = by_id(&doc, "box");
+    let color = |doc: &Document| doc.computed_styles(node_id)["color"].clone();
+
+    // Red to blue, sampled halfway through
+    doc.set_style_attribute(node_id, "color: rgb(0, 0, 255)");
+    doc.resolve();
+    doc.set_animation_time(0.5);
+    doc.resolve();
+    assert_eq!(color(&doc), "rgb(128, 0, 128)");
+
+    // Retargeting to green starts a new transition from the midpoint color
+    doc.set_style_attribute(node_id, "color: rgb(0, 255, 0)");
+    doc.resolve();
+    assert_eq!(color(&doc), "rgb(128, 0, 128)");
+    assert!(doc.has_active_animations());
+
+    doc.set_animation_time(1.0);
+    doc.resolve();
+    assert_eq!(color(&doc), "rgb(64, 128, 64)");
+
+    doc.set_animation_time(1.5);
+    doc.resolve();
+    assert_eq!(color(&doc), "rgb(0, 255, 0)");
+    assert!(!doc.has_active_animations());
+}
+
+#[test]
+fn legacy_attribute_hints_follow_attribute_changes() {
+    use crate::testing::{by_id, parse};
+    use crate::Document;
+    use html5ever::{ns, QualName};
+    use style::invalidation::element::restyle_hints::RestyleHint;
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <table id="table" bgcolor="red"></table>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let node_id = by_id(&doc, "table");
+    let background = |doc: &Document| doc.computed_styles(node_id)["background-color"].clone();
+    assert_eq!(background(&doc), "rgb(255, 0, 0)");
+
+    // The cached parse of the old value isn't reused once the attribute changes
+    doc.snapshot_node_and(node_id, |node| {
+        let element = node.raw_dom_data.downcast_element_mut().unwrap();
+        element.set_attribute(
+            QualName::new(None, ns!(), local_name!("bgcolor")),
+            "blue".to_string(),
+        );
+        node.set_restyle_hint(RestyleHint::restyle_subtree());
+    });
+    doc.resolve();
+    assert_eq!(background(&doc), "rgb(0, 0, 255)");
+}
@@ -4,12 +4,15 @@ use crate::util::ToPenikoColor;
 
 // Module of type aliases so we can refer to stylo types with nicer names
 pub(crate) mod stylo {
+    pub(crate) use style::computed_values::text_decoration_style::T as TextDecorationStyle;
     pub(crate) use style::computed_values::white_space_collapse::T as WhiteSpaceCollapse;
     pub(crate) use style::properties::ComputedValues;
     pub(crate) use style::values::computed::font::FontStyle;
     pub(crate) use style::values::computed::font::GenericFontFamily;
     pub(crate) use style::values::computed::font::LineHeight;
     pub(crate) use style::values::computed::font::SingleFontFamily;
+    pub(crate) use style::values::computed::text::TextDecorationsInEffect;
+    pub(crate) use style::values::computed::text::WordSpacing;
 }
 
 pub(crate) mod parley {
@@ -27,6 +30,90 @@ pub(crate) fn white_space_collapse(input: stylo::WhiteSpaceCollapse) -> parley::
     }
 }
 
+/// The text decorations which apply to an element's text, after propagation from ancestors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextDecoration {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+    pub color: peniko::Color,
+    pub style: stylo::TextDecorationStyle,
+}
+
+impl TextDecoration {
+    fn new(
+        lines: stylo::TextDecorationsInEffect,
+        color: peniko::Color,
+        style: stylo::TextDecorationStyle,
+    ) -> Self {
+        Self {
+            underline: lines.underline,
+            overline: lines.overline,
+            line_through: lines.line_through,
+            color,
+            style,
+        }
+    }
+
+    /// Whether any decoration line should be drawn
+    pub fn is_some(&self) -> bool {
+        self.underline || self.overline || self.line_through
+    }
+}
+
+/// Resolve the text decorations in effect for an element. A `currentColor` decoration colour resolves to the text colour.
+pub fn text_decoration(style: &stylo::ComputedValues) -> TextDecoration {
+    let text_styles = style.get_text();
+    let color = text_styles
+        .text_decoration_color
+        .as_absolute()
+        .unwrap_or(&style.get_inherited_text().color)
+        .as_peniko();
+
+    TextDecoration::new(
+        style.get_inherited_text().text_decorations_in_effect,
+        color,
+        text_styles.text_decoration_style,
+    )
+}
+
+/// The multiple of the font size used for `line-height: normal`
+const NORMAL_LINE_HEIGHT: f32 = 1.2;
+
+/// Resolve a computed `line-height` against the element's font size, returning the line advance in CSS pixels
+pub(crate) fn line_height(line_height: &stylo::LineHeight, font_size: f32) -> f32 {
+    match line_height {
+        stylo::LineHeight::Normal => font_size * NORMAL_LINE_HEIGHT,
+        stylo::LineHeight::Number(num) => font_size * num.0,
+        stylo::LineHeight::Length(value) => value.0.px(),
+    }
+}
+
+/// Resolve a computed `word-spacing` to the extra space (in CSS pixels) added to each word separator.
+/// Negative values are allowed and tighten the spacing between words.
+pub(crate) fn word_spacing(word_spacing: &stylo::WordSpacing) -> f32 {
+    // TODO: percentages are relative to the advance width of the space character, which isn't known until shaping
+    word_spacing
+        .to_length()
+        .map(|length| length.px())
+        .unwrap_or(0.0)
+}
+
+/// The numeric weight used to select a font face. Keywords (including the relative `bolder` and `lighter`) are
+/// resolved during the cascade, so `normal` is 400 and `bold` is 700.
+pub fn font_weight(style: &stylo::ComputedValues) -> parley::FontWeight {
+    parley::FontWeight::new(style.get_font().font_weight.value())
+}
+
+/// The slant used to select a font face
+pub fn font_style(style: &stylo::ComputedValues) -> parley::FontStyle {
+    match style.get_font().font_style {
+        stylo::FontStyle::NORMAL => parley::FontStyle::Normal,
+        stylo::FontStyle::ITALIC => parley::FontStyle::Italic,
+        val => parley::FontStyle::Oblique(Some(val.oblique_degrees())),
+    }
+}
+
 pub(crate) fn style(style: &stylo::ComputedValues) -> parley::TextStyle<'static, TextBrush> {
     let font_styles = style.get_font();
     // let text_styles = style.get_text();
@@ -34,21 +121,17 @@ pub(crate) fn style(style: &stylo::ComputedValues) -> parley::TextStyle<'static,
 
     // Convert font size and line height
     let font_size = font_styles.font_size.used_size.0.px();
-    let line_height: f32 = match font_styles.line_height {
-        stylo::LineHeight::Normal => font_size * 1.2,
-        stylo::LineHeight::Number(num) => font_size * num.0,
-        stylo::LineHeight::Length(value) => value.0.px(),
-    };
+    let line_height = line_height(&font_styles.line_height, font_size);
     // Parley expects line height as a multiple of font size!
-    let line_height = line_height / font_size;
+    let line_height = if font_size > 0.0 {
+        line_height / font_size
+    } else {
+        NORMAL_LINE_HEIGHT
+    };
 
     // Convert Bold/Italic
-    let font_weight = parley::FontWeight::new(font_styles.font_weight.value());
-    let font_style = match font_styles.font_style {
-        stylo::FontStyle::NORMAL => parley::FontStyle::Normal,
-        stylo::FontStyle::ITALIC => parley::FontStyle::Italic,
-        val => parley::FontStyle::Oblique(Some(val.oblique_degrees())),
-    };
+    let font_weight = font_weight(style);
+    let font_style = font_style(style);
 
     // Convert font family
     let families: Vec<_> = font_styles
@@ -95,12 +178,8 @@ pub(crate) fn style(style: &stylo::ComputedValues) -> parley::TextStyle<'static,
     // Convert text colour
     let color = itext_styles.color.as_peniko();
 
-    let decoration_brush = style
-        .get_text()
-        .text_decoration_color
-        .as_absolute()
-        .map(ToPenikoColor::as_peniko)
-        .map(|color| TextBrush::Normal(peniko::Brush::Solid(color)));
+    let decoration = text_decoration(style);
+    let decoration_brush = Some(TextBrush::Normal(peniko::Brush::Solid(decoration.color)));
 
     parley::TextStyle {
         // font_stack: parley::FontStack::Single(FontFamily::Generic(GenericFamily::SystemUi)),
@@ -113,16 +192,146 @@ pub(crate) fn style(style: &stylo::ComputedValues) -> parley::TextStyle<'static,
         font_features: parley::FontSettings::List(&[]),
         locale: Default::default(),
         brush: TextBrush::Normal(peniko::Brush::Solid(color)),
-        has_underline: itext_styles.text_decorations_in_effect.underline,
+        has_underline: decoration.underline,
         underline_offset: Default::default(),
         underline_size: Default::default(),
         underline_brush: decoration_brush.clone(),
-        has_strikethrough: itext_styles.text_decorations_in_effect.line_through,
+        has_strikethrough: decoration.line_through,
         strikethrough_offset: Default::default(),
         strikethrough_size: Default::default(),
         strikethrough_brush: decoration_brush,
         line_height,
-        word_spacing: Default::default(),
+        word_spacing: word_spacing(&itext_styles.word_spacing),
         letter_spacing: itext_styles.letter_spacing.0.px(),
     }
 }
+
+#[test]
+fn resolves_unitless_and_length_line_heights() {
+    use style::values::computed::Length;
+    use style::values::generics::NonNegative;
+
+    let font_size = 16.0;
+    let unitless = line_height(&stylo::LineHeight::Number(NonNegative(1.5)), font_size);
+    let length = line_height(
+        &stylo::LineHeight::Length(NonNegative(Length::new(20.0))),
+        font_size,
+    );
+
+    assert_eq!(unitless, 24.0);
+    assert_eq!(length, 20.0);
+    assert_eq!(line_height(&stylo::LineHeight::Normal, font_size), 19.2);
+}
+
+#[test]
+fn resolves_text_decoration_lines() {
+    let lines = |underline, line_through| stylo::TextDecorationsInEffect {
+        underline,
+        overline: false,
+        line_through,
+    };
+    let color = peniko::Color::rgb8(0, 0, 255);
+
+    let underline =
+        TextDecoration::new(lines(true, false), color, stylo::TextDecorationStyle::Solid);
+    assert!(underline.underline && !underline.line_through && !underline.overline);
+    assert!(underline.is_some());
+
+    let combined =
+        TextDecoration::new(lines(true, true), color, stylo::TextDecorationStyle::Dashed);
+    assert!(combined.underline && combined.line_through);
+    assert_eq!(combined.style, stylo::TextDecorationStyle::Dashed);
+    assert_eq!(combined.color, color);
+
+    assert!(!TextDecoration::new(
+        lines(false, false),
+        color,
+        stylo::TextDecorationStyle::Solid
+    )
+    .is_some());
+}
+
+#[test]
+fn resolves_word_spacing_lengths() {
+    use style::values::computed::{Length, LengthPercentage, Percentage};
+
+    let spacing = |px| LengthPercentage::new_length(Length::new(px));
+    assert_eq!(word_spacing(&spacing(4.0)), 4.0);
+    assert_eq!(word_spacing(&spacing(-2.0)), -2.0);
+    assert_eq!(
+        word_spacing(&LengthPercentage::new_percent(Percentage(0.5))),
+        0.0
+    );
+}
+
+#[test]
+fn resolves_inherited_font_weight_and_style() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <div id="bold" style="font-weight: bold">
+            <span id="inherits-bold">Bold</span>
+            <span id="light" style="font-weight: 300">Light</span>
+        </div>
+        <p id="numeric" style="font-weight: 600">Semibold</p>
+        <p id="italic" style="font-style: italic"><span id="inherits-italic">Italic</span></p>
+        <p id="oblique" style="font-style: oblique 10deg">Oblique</p>
+        <p id="normal">Normal</p>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let style = |id: &str| doc.nodes[by_id(&doc, id)].primary_styles().unwrap();
+
+    assert_eq!(font_weight(&style("bold")), parley::FontWeight::BOLD);
+    assert_eq!(
+        font_weight(&style("inherits-bold")),
+        parley::FontWeight::BOLD
+    );
+    assert_eq!(font_weight(&style("light")), parley::FontWeight::new(300.0));
+    assert_eq!(
+        font_weight(&style("numeric")),
+        parley::FontWeight::new(600.0)
+    );
+    assert_eq!(font_weight(&style("normal")), parley::FontWeight::NORMAL);
+
+    assert_eq!(font_style(&style("italic")), parley::FontStyle::Italic);
+    assert_eq!(
+        font_style(&style("inherits-italic")),
+        parley::FontStyle::Italic
+    );
+    assert_eq!(
+        font_style(&style("oblique")),
+        parley::FontStyle::Oblique(Some(10.0))
+    );
+    assert_eq!(font_style(&style("normal")), parley::FontStyle::Normal);
+}
+
+#[test]
+fn letter_spacing_changes_the_width_of_text() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body>
+        <p id="plain">abcdef</p>
+        <p id="spaced" style="letter-spacing: 3px">abcdef</p>
+        <p id="tight" style="letter-spacing: -1px">abcdef</p>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+    let width = |name: &str| {
+        doc.nodes[by_id(&doc, name)]
+            .raw_dom_data
+            .downcast_element()
+            .and_then(|element| element.inline_layout_data())
+            .map(|text| text.layout.width())
+            .unwrap()
+    };
+
+    // The spacing is added between each pair of the six letters (and possibly after the last)
+    let extra = width("spaced") - width("plain");
+    assert!(
+        (15.0..=18.0).contains(&extra),
+        "letter-spacing added {extra}px"
+    );
+    // Negative spacing tightens the text
+    assert!(width("tight") < width("plain"));
+}
@@ -14,6 +14,7 @@ mod stylo {
     pub(crate) use style::values::computed::GridLine;
     pub(crate) use style::values::computed::GridTemplateComponent;
     pub(crate) use style::values::computed::ImplicitGridTracks;
+    pub(crate) use style::values::computed::Length;
     pub(crate) use style::values::computed::LengthPercentage;
     pub(crate) use style::values::generics::flex::GenericFlexBasis;
     pub(crate) use style::values::generics::grid::RepeatCount;
@@ -41,6 +42,176 @@ mod stylo {
     pub(crate) use style::properties::ComputedValues;
 }
 
+/// Whether the computed value of a property differs between two styles
+type PropertyComparator = fn(&stylo::ComputedValues, &stylo::ComputedValues) -> bool;
+
+/// Compare a field of one of the style structs of two styles
+macro_rules! field_differs {
+    ($style_struct:ident . $field:ident) => {
+        |a, b| a.$style_struct().$field != b.$style_struct().$field
+    };
+}
+
+/// The CSS properties whose computed values can affect layout, sorted by name. Nodes whose styles only differ in
+/// other (paint-only) properties keep their cached layout when they are restyled.
+///
+/// `color` and the text decoration properties are included because they are baked into the brushes of inline
+/// layouts, which are only rebuilt when a node's layout is invalidated.
+pub const LAYOUT_AFFECTING_PROPERTIES: &[(&str, PropertyComparator)] = &[
+    ("align-content", field_differs!(get_position.align_content)),
+    ("align-items", field_differs!(get_position.align_items)),
+    ("align-self", field_differs!(get_position.align_self)),
+    ("aspect-ratio", field_differs!(get_position.aspect_ratio)),
+    (
+        "border-bottom-width",
+        field_differs!(get_border.border_bottom_width),
+    ),
+    (
+        "border-left-width",
+        field_differs!(get_border.border_left_width),
+    ),
+    (
+        "border-right-width",
+        field_differs!(get_border.border_right_width),
+    ),
+    (
+        "border-top-width",
+        field_differs!(get_border.border_top_width),
+    ),
+    ("bottom", field_differs!(get_position.bottom)),
+    ("box-sizing", field_differs!(get_position.box_sizing)),
+    ("color", field_differs!(get_inherited_text.color)),
+    ("column-gap", field_differs!(get_position.column_gap)),
+    (
+        "content-visibility",
+        field_differs!(get_box.content_visibility),
+    ),
+    ("direction", field_differs!(get_inherited_box.direction)),
+    ("display", field_differs!(get_box.display)),
+    ("flex-basis", field_differs!(get_position.flex_basis)),
+    (
+        "flex-direction",
+        field_differs!(get_position.flex_direction),
+    ),
+    ("flex-grow", field_differs!(get_position.flex_grow)),
+    ("flex-shrink", field_differs!(get_position.flex_shrink)),
+    ("flex-wrap", field_differs!(get_position.flex_wrap)),
+    ("font-family", field_differs!(get_font.font_family)),
+    ("font-size", field_differs!(get_font.font_size)),
+    ("font-style", field_differs!(get_font.font_style)),
+    ("font-weight", field_differs!(get_font.font_weight)),
+    (
+        "grid-auto-columns",
+        field_differs!(get_position.grid_auto_columns),
+    ),
+    (
+        "grid-auto-flow",
+        field_differs!(get_position.grid_auto_flow),
+    ),
+    (
+        "grid-auto-rows",
+        field_differs!(get_position.grid_auto_rows),
+    ),
+    (
+        "grid-column-end",
+        field_differs!(get_position.grid_column_end),
+    ),
+    (
+        "grid-column-start",
+        field_differs!(get_position.grid_column_start),
+    ),
+    ("grid-row-end", field_differs!(get_position.grid_row_end)),
+    (
+        "grid-row-start",
+        field_differs!(get_position.grid_row_start),
+    ),
+    (
+        "grid-template-columns",
+        field_differs!(get_position.grid_template_columns),
+    ),
+    (
+        "grid-template-rows",
+        field_differs!(get_position.grid_template_rows),
+    ),
+    ("height", field_differs!(get_position.height)),
+    (
+        "justify-content",
+        field_differs!(get_position.justify_content),
+    ),
+    ("justify-items", field_differs!(get_position.justify_items)),
+    ("justify-self", field_differs!(get_position.justify_self)),
+    ("left", field_differs!(get_position.left)),
+    (
+        "letter-spacing",
+        field_differs!(get_inherited_text.letter_spacing),
+    ),
+    ("line-height", field_differs!(get_font.line_height)),
+    ("margin-bottom", field_differs!(get_margin.margin_bottom)),
+    ("margin-left", field_differs!(get_margin.margin_left)),
+    ("margin-right", field_differs!(get_margin.margin_right)),
+    ("margin-top", field_differs!(get_margin.margin_top)),
+    ("max-height", field_differs!(get_position.max_height)),
+    ("max-width", field_differs!(get_position.max_width)),
+    ("min-height", field_differs!(get_position.min_height)),
+    ("min-width", field_differs!(get_position.min_width)),
+    ("object-fit", field_differs!(get_position.object_fit)),
+    ("order", field_differs!(get_position.order)),
+    ("overflow-x", field_differs!(get_box.overflow_x)),
+    ("overflow-y", field_differs!(get_box.overflow_y)),
+    ("padding-bottom", field_differs!(get_padding.padding_bottom)),
+    ("padding-left", field_differs!(get_padding.padding_left)),
+    ("padding-right", field_differs!(get_padding.padding_right)),
+    ("padding-top", field_differs!(get_padding.padding_top)),
+    ("position", field_differs!(get_box.position)),
+    ("right", field_differs!(get_position.right)),
+    ("row-gap", field_differs!(get_position.row_gap)),
+    ("tab-size", field_differs!(get_inherited_text.tab_size)),
+    ("text-align", field_differs!(get_inherited_text.text_align)),
+    (
+        "text-decoration-color",
+        field_differs!(get_text.text_decoration_color),
+    ),
+    (
+        "text-decoration-line",
+        field_differs!(get_text.text_decoration_line),
+    ),
+    (
+        "text-decoration-style",
+        field_differs!(get_text.text_decoration_style),
+    ),
+    (
+        "text-wrap-mode",
+        field_differs!(get_inherited_text.text_wrap_mode),
+    ),
+    ("top", field_differs!(get_position.top)),
+    (
+        "white-space-collapse",
+        field_differs!(get_inherited_text.white_space_collapse),
+    ),
+    ("width", field_differs!(get_position.width)),
+    (
+        "word-spacing",
+        field_differs!(get_inherited_text.word_spacing),
+    ),
+];
+
+/// Whether a change to the CSS property `name` can affect layout
+pub fn affects_layout(name: &str) -> bool {
+    LAYOUT_AFFECTING_PROPERTIES
+        .binary_search_by_key(&name, |(name, _)| *name)
+        .is_ok()
+}
+
+/// Whether two styles differ in any of the [`LAYOUT_AFFECTING_PROPERTIES`]
+pub fn layout_affecting_properties_differ(
+    a: &stylo::ComputedValues,
+    b: &stylo::ComputedValues,
+) -> bool {
+    LAYOUT_AFFECTING_PROPERTIES
+        .iter()
+        .any(|(_, differs)| differs(a, b))
+}
+
 pub(crate) fn entire_style(style: &stylo::ComputedValues) -> taffy::Style {
     let margin = style.get_margin();
     let padding = style.get_padding();
@@ -50,11 +221,16 @@ pub(crate) fn entire_style(style: &stylo::ComputedValues) -> taffy::Style {
     let text_align = style.clone_text_align();
 
     let position = self::position(box_styles.position);
-    let inset = taffy::Rect {
-        left: self::length_percentage_auto(&pos.left),
-        right: self::length_percentage_auto(&pos.right),
-        top: self::length_percentage_auto(&pos.top),
-        bottom: self::length_percentage_auto(&pos.bottom),
+    let inset = match box_styles.position {
+        // Insets have no effect on statically positioned elements. The insets of sticky elements are thresholds
+        // which are applied when painting (see `Document::sticky_offset`) rather than offsets.
+        stylo::Position::Static | stylo::Position::Sticky => taffy::Rect::auto(),
+        _ => taffy::Rect {
+            left: self::length_percentage_auto(&pos.left),
+            right: self::length_percentage_auto(&pos.right),
+            top: self::length_percentage_auto(&pos.top),
+            bottom: self::length_percentage_auto(&pos.bottom),
+        },
     };
 
     // HACK: Emulate float with 'position: absolute'
@@ -79,8 +255,7 @@ pub(crate) fn entire_style(style: &stylo::ComputedValues) -> taffy::Style {
             y: self::overflow(box_styles.overflow_y),
         },
 
-        // TODO: we'll eventually want to support visible scrollbars
-        // But we really ought to implement "overflow: auto" first
+        // Set by the document, which also decides whether `overflow: auto` axes show a scrollbar
         scrollbar_width: 0.0,
 
         size: taffy::Size {
@@ -147,11 +322,88 @@ pub(crate) fn length_percentage(val: &stylo::LengthPercentage) -> taffy::LengthP
     } else if let Some(val) = val.to_percentage() {
         taffy::LengthPercentage::Percent(val.0)
     } else {
-        // TODO: Support calc
+        // TODO: Support calc (outside of sizes, which are resolved by `resolve_calc_sizes`)
         taffy::LengthPercentage::Percent(0.0)
     }
 }
 
+/// Whether a length-percentage is a `calc()` expression mixing lengths and percentages (including `min()`, `max()`
+/// and `clamp()`, such as `clamp(100px, 50%, 300px)`), which can't be represented in Taffy
+pub(crate) fn is_calc(val: &stylo::LengthPercentage) -> bool {
+    val.to_length().is_none() && val.to_percentage().is_none()
+}
+
+/// Resolve the sizes of a node which are `calc()` expressions (see [`is_calc`]) against `basis`, the size of the
+/// node's containing block. [`entire_style`] leaves these as `0%`, so they are resolved each time the node's parent
+/// is laid out. Sizes whose basis is indefinite are left as they are.
+pub(crate) fn resolve_calc_sizes(
+    style: &stylo::ComputedValues,
+    taffy_style: &mut taffy::Style,
+    basis: taffy::Size<Option<f32>>,
+) {
+    let resolve = |val: &stylo::LengthPercentage, basis: Option<f32>| {
+        let basis = basis.filter(|_| is_calc(val))?;
+        Some(taffy::Dimension::Length(
+            val.resolve(stylo::Length::new(basis)).px(),
+        ))
+    };
+    let size = |val: &stylo::Size, basis| match val {
+        stylo::Size::LengthPercentage(val) => resolve(&val.0, basis),
+        stylo::Size::Auto => None,
+    };
+    let max_size = |val: &stylo::MaxSize, basis| match val {
+        stylo::MaxSize::LengthPercentage(val) => resolve(&val.0, basis),
+        stylo::MaxSize::None => None,
+    };
+
+    let pos = style.get_position();
+    let resolved = [
+        (&mut taffy_style.size.width, size(&pos.width, basis.width)),
+        (
+            &mut taffy_style.size.height,
+            size(&pos.height, basis.height),
+        ),
+        (
+            &mut taffy_style.min_size.width,
+            size(&pos.min_width, basis.width),
+        ),
+        (
+            &mut taffy_style.min_size.height,
+            size(&pos.min_height, basis.height),
+        ),
+        (
+            &mut taffy_style.max_size.width,
+            max_size(&pos.max_width, basis.width),
+        ),
+        (
+            &mut taffy_style.max_size.height,
+            max_size(&pos.max_height, basis.height),
+        ),
+    ];
+    for (dimension, value) in resolved {
+        if let Some(value) = value {
+            *dimension = value;
+        }
+    }
+}
+
+/// The `top`, `right`, `bottom` and `left` thresholds of a `position: sticky` element, or `None` if the element
+/// isn't sticky
+pub(crate) fn sticky_insets(
+    style: &stylo::ComputedValues,
+) -> Option<taffy::Rect<taffy::LengthPercentageAuto>> {
+    if style.get_box().position != stylo::Position::Sticky {
+        return None;
+    }
+    let pos = style.get_position();
+    Some(taffy::Rect {
+        left: self::length_percentage_auto(&pos.left),
+        right: self::length_percentage_auto(&pos.right),
+        top: self::length_percentage_auto(&pos.top),
+        bottom: self::length_percentage_auto(&pos.bottom),
+    })
+}
+
 pub(crate) fn length_percentage_auto(
     val: &stylo::LengthPercentageAuto,
 ) -> taffy::LengthPercentageAuto {
@@ -249,13 +501,17 @@ pub(crate) fn display(input: stylo::Display) -> taffy::Display {
 
 pub(crate) fn position(input: stylo::Position) -> taffy::Position {
     match input {
-        // TODO: support position:static
+        // Static elements are laid out as relative elements whose insets are ignored (see `entire_style`)
         stylo::Position::Relative => taffy::Position::Relative,
         stylo::Position::Static => taffy::Position::Relative,
 
-        // TODO: support position:fixed and sticky
+        // Fixed elements are laid out as absolute elements, and then positioned relative
+        // to the viewport by the renderer.
         stylo::Position::Absolute => taffy::Position::Absolute,
         stylo::Position::Fixed => taffy::Position::Absolute,
+
+        // Sticky elements are laid out in normal flow, and then shifted to stay within their scroll container
+        // when painted
         stylo::Position::Sticky => taffy::Position::Relative,
     }
 }
@@ -266,7 +522,7 @@ pub(crate) fn overflow(input: stylo::Overflow) -> taffy::Overflow {
         stylo::Overflow::Visible => taffy::Overflow::Visible,
         stylo::Overflow::Hidden => taffy::Overflow::Hidden,
         stylo::Overflow::Scroll => taffy::Overflow::Scroll,
-        // TODO: Support Overflow::Auto in Taffy
+        // Downgraded to `Hidden` by the document when the content doesn't overflow
         stylo::Overflow::Auto => taffy::Overflow::Scroll,
     }
 }
@@ -474,3 +730,23 @@ pub(crate) fn item_alignment(input: stylo::AlignFlags) -> Option<taffy::AlignIte
         _ => None,
     }
 }
+
+#[test]
+fn layout_affecting_properties_are_sorted() {
+    assert!(LAYOUT_AFFECTING_PROPERTIES
+        .windows(2)
+        .all(|pair| pair[0].0 < pair[1].0));
+    for name in [
+        "display",
+        "width",
+        "margin-top",
+        "padding-left",
+        "flex-grow",
+        "position",
+    ] {
+        assert!(affects_layout(name), "{name} should affect layout");
+    }
+    assert!(!affects_layout("background-color"));
+    assert!(!affects_layout("border-top-color"));
+    assert!(!affects_layout("opacity"));
+}
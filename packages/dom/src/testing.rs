@@ -0,0 +1,196 @@
+//! Helpers for building documents in tests and asserting on their structure
+
+use std::fmt::Write;
+
+use crate::{Document, DocumentHtmlParser, NodeData, Viewport};
+
+/// Parse an HTML string into a new 800x600 document. Styles and layout are left for the caller to resolve.
+pub fn parse(html: &str) -> Document {
+    let mut doc = Document::new(Viewport::new(800, 600, 1.0));
+    DocumentHtmlParser::parse_into_doc(&mut doc, html);
+    doc
+}
+
+/// The id of the node whose `id` attribute is `id`. Panics if there is no such node.
+#[track_caller]
+pub fn by_id(doc: &Document, id: &str) -> usize {
+    match doc.nodes_to_id.get(id) {
+        Some(&node_id) => node_id,
+        None => panic!("no node with id {id:?}"),
+    }
+}
+
+/// Assert that the structure of a document (or of the subtree rooted at a node) matches an expected string.
+///
+/// The tree is pretty-printed with [`pretty_print`] and compared against the expected string after both are
+/// normalized with [`normalize_snapshot`], so the expected string can be indented to match the surrounding code.
+/// On mismatch the panic message contains a line diff.
+///
+/// ```ignore
+/// assert_dom_snapshot!(doc, r#"
+///     <html>
+///       <head>
+///       <body>
+///         "Hello"
+/// "#);
+/// ```
+#[macro_export]
+macro_rules! assert_dom_snapshot {
+    ($doc:expr, $expected:expr $(,)?) => {
+        $crate::testing::assert_snapshot_matches(&$doc, $doc.root_element().id, $expected)
+    };
+    ($doc:expr, $node_id:expr, $expected:expr $(,)?) => {
+        $crate::testing::assert_snapshot_matches(&$doc, $node_id, $expected)
+    };
+}
+
+/// Print the subtree rooted at `node_id` with one node per line, indented by two spaces per level.
+/// Elements are printed as their start tag (with attributes in source order), text as a quoted string with
+/// surrounding whitespace trimmed and comments as `<!-- -->`. Whitespace-only text nodes are omitted.
+pub fn pretty_print(doc: &Document, node_id: usize) -> String {
+    let mut output = String::new();
+    print_node(doc, node_id, 0, &mut output);
+    output
+}
+
+fn print_node(doc: &Document, node_id: usize, depth: usize, output: &mut String) {
+    let node = &doc.nodes[node_id];
+    let indent = "  ".repeat(depth);
+    let mut child_depth = depth + 1;
+    match &node.raw_dom_data {
+        NodeData::Element(element) => {
+            write!(output, "{indent}<{}", element.name.local).unwrap();
+            for attr in element.attrs() {
+                write!(output, " {}=\"{}\"", attr.name.local, attr.value).unwrap();
+            }
+            output.push_str(">\n");
+        }
+        NodeData::Text(text) => {
+            let content = text.content.trim();
+            if content.is_empty() {
+                return;
+            }
+            writeln!(output, "{indent}{content:?}").unwrap();
+        }
+        NodeData::Comment => writeln!(output, "{indent}<!-- -->").unwrap(),
+        NodeData::Document | NodeData::AnonymousBlock(_) => child_depth = depth,
+    }
+
+    for &child_id in &node.children {
+        print_node(doc, child_id, child_depth, output);
+    }
+}
+
+/// Normalize a snapshot for comparison: trailing whitespace and blank lines are removed, as is any indentation
+/// common to all lines.
+pub fn normalize_snapshot(snapshot: &str) -> String {
+    let lines: Vec<&str> = snapshot
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let common_indent = lines
+        .iter()
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| &line[common_indent..])
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A line diff of two snapshots. Lines only in `expected` are prefixed with `-`, lines only in `actual` with `+`
+/// and lines in both with a space.
+pub fn snapshot_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence table, where lcs[i][j] is the LCS length of expected[i..] and actual[j..]
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            writeln!(diff, "  {}", expected[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            writeln!(diff, "- {}", expected[i]).unwrap();
+            i += 1;
+        } else {
+            writeln!(diff, "+ {}", actual[j]).unwrap();
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// Implementation of [`assert_dom_snapshot!`]
+#[track_caller]
+pub fn assert_snapshot_matches(doc: &Document, node_id: usize, expected: &str) {
+    let expected = normalize_snapshot(expected);
+    let actual = normalize_snapshot(&pretty_print(doc, node_id));
+    if expected != actual {
+        panic!(
+            "DOM does not match snapshot (- expected, + actual):\n{}",
+            snapshot_diff(&expected, &actual)
+        );
+    }
+}
+
+#[test]
+fn snapshot_of_buttons_matches_ignoring_indentation() {
+    let html = r#"<!DOCTYPE html><html><body>
+        <div class="buttons">
+            <button id="increment">Up high!</button>
+            <button id="decrement">Down low!</button>
+        </div>
+    </body></html>"#;
+    let doc = parse(html);
+
+    assert_dom_snapshot!(
+        doc,
+        r#"
+            <html>
+              <head>
+              <body>
+                <div class="buttons">
+                  <button id="increment">
+                    "Up high!"
+                  <button id="decrement">
+                    "Down low!"
+        "#
+    );
+
+    let buttons = doc.nodes[by_id(&doc, "increment")].parent.unwrap();
+    assert_dom_snapshot!(
+        doc,
+        buttons,
+        r#"
+        <div class="buttons">
+          <button id="increment">
+            "Up high!"
+          <button id="decrement">
+            "Down low!"
+        "#
+    );
+}
+
+#[test]
+fn snapshot_diff_marks_changed_lines() {
+    let diff = snapshot_diff("<div>\n  \"a\"\n  \"b\"", "<div>\n  \"a\"\n  \"c\"");
+    assert_eq!(diff, "  <div>\n    \"a\"\n-   \"b\"\n+   \"c\"\n");
+}
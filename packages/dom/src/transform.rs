@@ -0,0 +1,148 @@
+//! Conversion of CSS transforms into 2D affine transforms for painting and hit testing
+
+use peniko::kurbo::{Affine, Point, Size, Vec2};
+use style::properties::ComputedValues;
+use style::values::computed::transform::TransformOperation;
+use style::values::computed::Length;
+
+use crate::{Document, Node};
+
+/// Compose a list of transform functions (as found in the `transform` property) into a single affine transform.
+/// Percentages are resolved against `size` (the size of the element's border box).
+///
+/// 3D transform functions are not supported and are treated as the identity transform.
+pub fn transform_operations_to_affine(operations: &[TransformOperation], size: Size) -> Affine {
+    let resolve_x = |lp: &style::values::computed::LengthPercentage| {
+        lp.resolve(Length::new(size.width as f32)).px() as f64
+    };
+    let resolve_y = |lp: &style::values::computed::LengthPercentage| {
+        lp.resolve(Length::new(size.height as f32)).px() as f64
+    };
+
+    // Transform functions are applied from left to right, so each function is post-multiplied
+    operations
+        .iter()
+        .fold(Affine::IDENTITY, |transform, operation| {
+            let operation = match operation {
+                TransformOperation::Matrix(m) => Affine::new([
+                    m.a as f64, m.b as f64, m.c as f64, m.d as f64, m.e as f64, m.f as f64,
+                ]),
+                TransformOperation::Translate(x, y) => {
+                    Affine::translate((resolve_x(x), resolve_y(y)))
+                }
+                TransformOperation::TranslateX(x) => Affine::translate((resolve_x(x), 0.0)),
+                TransformOperation::TranslateY(y) => Affine::translate((0.0, resolve_y(y))),
+                TransformOperation::Scale(x, y) => Affine::scale_non_uniform(*x as f64, *y as f64),
+                TransformOperation::ScaleX(x) => Affine::scale_non_uniform(*x as f64, 1.0),
+                TransformOperation::ScaleY(y) => Affine::scale_non_uniform(1.0, *y as f64),
+                TransformOperation::Rotate(angle) | TransformOperation::RotateZ(angle) => {
+                    Affine::rotate(angle.radians() as f64)
+                }
+                TransformOperation::Skew(x, y) => {
+                    Affine::skew((x.radians() as f64).tan(), (y.radians() as f64).tan())
+                }
+                TransformOperation::SkewX(x) => Affine::skew((x.radians() as f64).tan(), 0.0),
+                TransformOperation::SkewY(y) => Affine::skew(0.0, (y.radians() as f64).tan()),
+                _ => Affine::IDENTITY,
+            };
+            transform * operation
+        })
+}
+
+/// The transform of an element (including its `transform-origin`), mapping points relative to the top-left corner
+/// of its untransformed border box to points relative to the same corner after transformation.
+pub fn element_transform(style: &ComputedValues, size: Size) -> Affine {
+    let box_styles = style.get_box();
+    if box_styles.transform.0.is_empty() {
+        return Affine::IDENTITY;
+    }
+
+    let origin = Vec2::new(
+        box_styles
+            .transform_origin
+            .horizontal
+            .resolve(Length::new(size.width as f32))
+            .px() as f64,
+        box_styles
+            .transform_origin
+            .vertical
+            .resolve(Length::new(size.height as f32))
+            .px() as f64,
+    );
+
+    Affine::translate(origin)
+        * transform_operations_to_affine(&box_styles.transform.0, size)
+        * Affine::translate(-origin)
+}
+
+impl Node {
+    /// The transform applied to this node by its `transform` property (see [`element_transform`])
+    pub fn local_transform(&self) -> Affine {
+        let size = Size::new(
+            self.final_layout.size.width as f64,
+            self.final_layout.size.height as f64,
+        );
+        self.primary_styles()
+            .map(|style| element_transform(&style, size))
+            .unwrap_or(Affine::IDENTITY)
+    }
+}
+
+impl Document {
+    /// The transform mapping points relative to the top-left corner of a node's border box to points relative to the
+    /// top-left corner of the document, taking into account the transforms of the node and all of its ancestors.
+    pub fn node_transform(&self, node_id: usize) -> Affine {
+        let node = &self.nodes[node_id];
+        let parent_transform = node
+            .parent
+            .map(|parent_id| self.node_transform(parent_id))
+            .unwrap_or(Affine::IDENTITY);
+        let location = Vec2::new(
+            node.final_layout.location.x as f64,
+            node.final_layout.location.y as f64,
+        );
+
+        parent_transform * Affine::translate(location) * node.local_transform()
+    }
+
+    /// Map a point relative to the top-left corner of the document to a point relative to the top-left corner of a
+    /// node's (untransformed) border box
+    pub fn document_to_node_point(&self, node_id: usize, point: Point) -> Point {
+        self.node_transform(node_id).inverse() * point
+    }
+}
+
+#[test]
+fn composes_and_inverts_translate_and_scale() {
+    use style::values::computed::LengthPercentage;
+
+    let px = |value| LengthPercentage::new_length(Length::new(value));
+    let operations = [
+        TransformOperation::Translate(px(10.0), px(5.0)),
+        TransformOperation::Scale(2.0, 2.0),
+    ];
+    let transform = transform_operations_to_affine(&operations, Size::new(100.0, 100.0));
+
+    // The point is scaled first and then translated
+    let mapped = transform * Point::new(1.0, 1.0);
+    assert_eq!(mapped, Point::new(12.0, 7.0));
+    assert_eq!(transform.inverse() * mapped, Point::new(1.0, 1.0));
+}
+
+#[test]
+fn elements_collapsed_by_their_transform_are_not_hit() {
+    use crate::testing::{by_id, parse};
+
+    let html = r#"<!DOCTYPE html><html><body id="body" style="margin: 0">
+        <div id="collapsed" style="width: 100px; height: 100px; transform: scale(0)">
+            <div style="width: 100px; height: 100px"></div>
+        </div>
+    </body></html>"#;
+    let mut doc = parse(html);
+    doc.resolve();
+
+    let body = by_id(&doc, "body");
+    for (x, y) in [(0.0, 0.0), (50.0, 50.0), (99.0, 99.0)] {
+        assert_eq!(doc.hit(x, y).map(|hit| hit.node_id), Some(body));
+    }
+}
@@ -208,8 +208,31 @@ pub fn walk_tree(indent: usize, node: &Node) {
 
 use peniko::Color as PenikoColor;
 use style::color::AbsoluteColor;
+use style::computed_values::direction::T as Direction;
+use style::properties::ComputedValues;
+use style::values::computed::Color;
 use url::Url;
 
+/// The direction of the first character in `text` with a strong direction (as used by `dir="auto"`), if any.
+///
+/// Characters from right-to-left scripts (Hebrew, Arabic, Syriac, Thaana, N'Ko and their presentation forms) are
+/// strongly right-to-left, and other alphabetic characters are treated as strongly left-to-right.
+pub fn first_strong_direction(text: &str) -> Option<Direction> {
+    text.chars().find_map(|c| {
+        let is_rtl = matches!(
+            c,
+            '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}'
+        );
+        if is_rtl {
+            Some(Direction::Rtl)
+        } else if c.is_alphabetic() {
+            Some(Direction::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
 pub trait ToPenikoColor {
     fn as_peniko(&self) -> PenikoColor;
 }
@@ -222,3 +245,347 @@ impl ToPenikoColor for AbsoluteColor {
         PenikoColor { r, g, b, a }
     }
 }
+
+/// Resolve a computed colour, using the element's text colour (its computed `color`) for `currentColor`
+pub fn resolve_color<'a>(style: &'a ComputedValues, color: &'a Color) -> &'a AbsoluteColor {
+    color
+        .as_absolute()
+        .unwrap_or(&style.get_inherited_text().color)
+}
+
+/// Parse a color value such as those found in legacy presentational attributes (e.g. `bgcolor`).
+///
+/// Supports named colors, `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa` hex colors along with the `rgb()`, `rgba()`,
+/// `hsl()` and `hsla()` functions. Returns `[r, g, b, a]` with the color channels in the range 0-255 and alpha in
+/// the range 0-1. Out-of-range channel values are clamped rather than rejected.
+pub fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let value = value.trim();
+
+    if let Some(color) = parse_named_color(value) {
+        return Some(color);
+    }
+
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex_color(hex);
+    }
+
+    let (name, args) = value.strip_suffix(')')?.split_once('(')?;
+    let args: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    if args.len() != 3 && args.len() != 4 {
+        return None;
+    }
+
+    let alpha = match args.get(3) {
+        Some(alpha) => parse_alpha(alpha)?,
+        None => 1.0,
+    };
+
+    match name.trim().to_ascii_lowercase().as_str() {
+        "rgb" | "rgba" => {
+            let r = parse_rgb_channel(args[0])?;
+            let g = parse_rgb_channel(args[1])?;
+            let b = parse_rgb_channel(args[2])?;
+            Some([r, g, b, alpha])
+        }
+        "hsl" | "hsla" => {
+            let hue = args[0].strip_suffix("deg").unwrap_or(args[0]);
+            let hue = hue.parse::<f32>().ok()?.rem_euclid(360.0);
+            let saturation = parse_percentage(args[1])?;
+            let lightness = parse_percentage(args[2])?;
+            let [r, g, b] = hsl_to_rgb(hue, saturation, lightness);
+            Some([r, g, b, alpha])
+        }
+        _ => None,
+    }
+}
+
+fn parse_hex_color(hex: &str) -> Option<[f32; 4]> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channels: Vec<f32> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|digit| (digit * 17) as f32))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(f32::from))
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    let alpha = channels.get(3).map(|a| a / 255.0).unwrap_or(1.0);
+    Some([channels[0], channels[1], channels[2], alpha])
+}
+
+/// Parse an rgb() channel (either a number or a percentage) into the range 0-255
+fn parse_rgb_channel(value: &str) -> Option<f32> {
+    let channel = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok()? * 2.55,
+        None => value.parse::<f32>().ok()?,
+    };
+    Some(channel.clamp(0.0, 255.0))
+}
+
+/// Parse an alpha value (either a number or a percentage) into the range 0-1
+fn parse_alpha(value: &str) -> Option<f32> {
+    let alpha = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()?,
+    };
+    Some(alpha.clamp(0.0, 1.0))
+}
+
+/// Parse a percentage into the range 0-1
+fn parse_percentage(value: &str) -> Option<f32> {
+    let percent = value.strip_suffix('%')?.parse::<f32>().ok()?;
+    Some((percent / 100.0).clamp(0.0, 1.0))
+}
+
+/// Convert a hue (degrees) with saturation and lightness (0-1) into rgb channels in the range 0-255
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
+    let channel = |n: f32| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        let value = lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+        value * 255.0
+    };
+    [channel(0.0), channel(8.0), channel(4.0)]
+}
+
+/// Look up a CSS named color (case-insensitively). `transparent` is zero-alpha black.
+pub fn parse_named_color(name: &str) -> Option<[f32; 4]> {
+    let name = name.to_ascii_lowercase();
+    if name == "transparent" {
+        return Some([0.0, 0.0, 0.0, 0.0]);
+    }
+
+    let index = NAMED_COLORS
+        .binary_search_by_key(&name.as_str(), |&(name, _)| name)
+        .ok()?;
+    let [r, g, b] = NAMED_COLORS[index].1;
+    Some([r as f32, g as f32, b as f32, 1.0])
+}
+
+/// The standard CSS named colors, sorted by name (`transparent` is handled separately)
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+#[test]
+fn parses_hex_colors() {
+    assert_eq!(parse_color("#f00"), Some([255.0, 0.0, 0.0, 1.0]));
+    assert_eq!(parse_color("#00ff00"), Some([0.0, 255.0, 0.0, 1.0]));
+    assert_eq!(parse_color("#0000ff00"), Some([0.0, 0.0, 255.0, 0.0]));
+    assert_eq!(parse_color("#112233ff"), Some([17.0, 34.0, 51.0, 1.0]));
+    assert_eq!(parse_color("#12345"), None);
+    assert_eq!(parse_color("#ggg"), None);
+}
+
+#[test]
+fn parses_rgba_and_hsla_colors() {
+    assert_eq!(parse_color("rgb(1, 2, 3)"), Some([1.0, 2.0, 3.0, 1.0]));
+    assert_eq!(
+        parse_color("rgba(1, 2, 3, 0.5)"),
+        Some([1.0, 2.0, 3.0, 0.5])
+    );
+    assert_eq!(parse_color("rgba(1 2 3 / 50%)"), Some([1.0, 2.0, 3.0, 0.5]));
+    assert_eq!(
+        parse_color("hsla(120, 100%, 50%, 0.25)"),
+        Some([0.0, 255.0, 0.0, 0.25])
+    );
+    assert_eq!(
+        parse_color("hsl(0deg, 0%, 100%)"),
+        Some([255.0, 255.0, 255.0, 1.0])
+    );
+    assert_eq!(parse_color("rgba(1, 2)"), None);
+    assert_eq!(parse_color("cmyk(1, 2, 3, 4)"), None);
+}
+
+#[test]
+fn clamps_out_of_range_color_channels() {
+    assert_eq!(
+        parse_color("rgba(300, -5, 0, 1.5)"),
+        Some([255.0, 0.0, 0.0, 1.0])
+    );
+    assert_eq!(parse_color("rgba(0, 0, 0, -1)"), Some([0.0, 0.0, 0.0, 0.0]));
+}
+
+#[test]
+fn parses_named_colors() {
+    assert_eq!(parse_color("red"), Some([255.0, 0.0, 0.0, 1.0]));
+    assert_eq!(
+        parse_color("cornflowerblue"),
+        Some([100.0, 149.0, 237.0, 1.0])
+    );
+    assert_eq!(
+        parse_color("RebeccaPurple"),
+        Some([102.0, 51.0, 153.0, 1.0])
+    );
+    assert_eq!(parse_color("transparent"), Some([0.0, 0.0, 0.0, 0.0]));
+    assert_eq!(parse_color("notacolor"), None);
+    assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+}
+
+#[test]
+fn detects_the_first_strong_direction() {
+    assert_eq!(
+        first_strong_direction("123 שלום hello"),
+        Some(Direction::Rtl)
+    );
+    assert_eq!(first_strong_direction("  hello שלום"), Some(Direction::Ltr));
+    assert_eq!(first_strong_direction("مرحبا"), Some(Direction::Rtl));
+    assert_eq!(first_strong_direction("123 !?"), None);
+}